@@ -2,17 +2,24 @@ use {
     eframe::{
         egui_glow::{self, Painter}, glow::{self}, App, CreationContext, Frame
     }, egui::{
-        mutex::Mutex, CentralPanel, Color32, Context, PaintCallback, Response, Ui 
-    }, emath::{
-        Pos2, Rect, RectTransform
-    }
+        menu, mutex::Mutex, Align2, CentralPanel, Color32, ColorImage, ComboBox, Context, CursorIcon, DragValue, Event, Key, KeyboardShortcut, Modifiers, PaintCallback, PointerButton, Response, SidePanel, TextEdit, TextureHandle, TextureOptions, TopBottomPanel, Ui, Window
+    }, emath::Rect
 };
-use std::sync::Arc;
+use std::{path::PathBuf, sync::Arc};
 
-mod grid; use grid::Grid;
+pub mod grid; use grid::{center_of, shapes, Biome, Generator, Grid, Hex, HexAxis, HexIterators, HexOrientation, ImageOptions, Point, RasterImport, Rng, Rules, Synthesis, Sampling, Selection, SelectionOp, Site, Terrain, TerrainId, Transform, CLIPBOARD_TAG};
 mod renderer; use renderer::Renderer;
+mod history; use history::History;
+mod camera; use camera::Camera;
+mod toolbox; use toolbox::{Overlay, ToolContext, Toolbox};
+pub mod palette; use palette::Palette;
+mod terrain_panel; use terrain_panel::TerrainPanel;
+mod layer_panel;
+pub mod coordinates; use coordinates::CoordSystem;
 
+#[allow(dead_code)]
 struct ArcMutex<T>(pub Arc<Mutex<T>>);
+#[allow(dead_code)]
 impl<T> ArcMutex<T> {
     pub fn new(item: T) -> Self {
         Self(Arc::new(Mutex::new(item)))
@@ -23,22 +30,175 @@ impl<T> ArcMutex<T> {
     }
 }
 
+/// Zoom factor exponent per scrolled point.
+const ZOOM_SPEED: f32 = 0.002;
+const UNDO_SHORTCUT: KeyboardShortcut = KeyboardShortcut::new(Modifiers::COMMAND, Key::Z);
+const REDO_SHORTCUT: KeyboardShortcut = KeyboardShortcut::new(Modifiers::COMMAND.plus(Modifiers::SHIFT), Key::Z);
+const SELECT_ALL_SHORTCUT: KeyboardShortcut = KeyboardShortcut::new(Modifiers::COMMAND, Key::A);
+const DUPLICATE_SHORTCUT: KeyboardShortcut = KeyboardShortcut::new(Modifiers::COMMAND, Key::D);
+const GOTO_SHORTCUT: KeyboardShortcut = KeyboardShortcut::new(Modifiers::COMMAND, Key::G);
+/// Smallest cell size, in screen points, at which coordinate labels are drawn.
+const MIN_LABEL_CELL_SIZE: f32 = 28.0;
+const DESELECT_SHORTCUT: KeyboardShortcut = KeyboardShortcut::new(Modifiers::NONE, Key::Escape);
+const DELETE_SHORTCUT: KeyboardShortcut = KeyboardShortcut::new(Modifiers::NONE, Key::Delete);
+/// Longest side, in pixels, of the generated map preview.
+const PREVIEW_SIDE: f64 = 240.0;
+
 pub struct Editor {
     grid: Grid,
-    color: Color32,
+    history: History,
+    palette: Palette,
+    terrain_panel: TerrainPanel,
+    toolbox: Toolbox,
+    selection: Selection,
+    /// Last copied cells, for the menu's Paste which cannot read the system clipboard.
+    clipboard: Option<String>,
+    /// Cell under the pointer, where pasted cells land.
+    hovered_cell: Option<Hex>,
+    /// Button and last cell of the stroke in progress.
+    stroke: Option<(PointerButton, Hex)>,
+    shape_size: i32,
+    /// Offset of the Transform menu's Move command.
+    move_offset: [i32; 2],
+    /// Whether changing the orientation keeps cells in their rows and columns.
+    relayout: bool,
+    /// Parity of the offset coordinates in the status bar.
+    offset_odd: bool,
+    /// Coordinates drawn on every cell, if any.
+    coordinate_labels: Option<CoordSystem>,
+    goto_dialog: Option<GotoDialog>,
+    image_import: Option<ImageImportDialog>,
+    /// Settings of the last generated map.
+    generator: Generator,
+    generator_dialog: Option<GeneratorDialog>,
+    synthesis_dialog: Option<SynthesisDialog>,
     renderer: Arc<Mutex<Renderer>>,
+    camera: Camera,
+    viewport: Rect,
+    mesh_dirty: bool,
+    file_path: Option<PathBuf>,
+    file_dialog: Option<FileDialog>,
+    /// Settings of the last image export.
+    export_options: ImageOptions,
+    error: Option<String>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum FileAction {
+    Open,
+    SaveAs,
+    ImportPalette,
+    ExportPalette,
+    ExportImage,
+    ExportTiled,
+    ImportImage,
+}
+
+struct FileDialog {
+    action: FileAction,
+    path: String,
+}
+
+struct GotoDialog {
+    system: CoordSystem,
+    text: String,
+    error: Option<String>,
+}
+
+/// An image being placed over the map before it is traced into a new one.
+struct ImageImportDialog {
+    path: PathBuf,
+    image: image::RgbaImage,
+    texture: TextureHandle,
+    options: RasterImport,
+    /// Width of the image in cells, which sets `options.scale`.
+    cells: f64,
+    /// Snaps colors to the palette swatches.
+    quantize: bool,
+}
+
+/// A map being set up before it is generated.
+struct GeneratorDialog {
+    generator: Generator,
+    shape: MapShape,
+    size: i32,
+    /// Rendering of the current settings, if the map has any cells.
+    preview: Option<TextureHandle>,
+    /// Whether the settings changed since the preview was drawn.
+    stale: bool,
+}
+
+/// Rules gathered from the map and applied to the selection, by wave function collapse.
+struct SynthesisDialog {
+    rules: Rules,
+    /// Where the rules came from.
+    source: String,
+    rules_path: String,
+    options: Synthesis,
+    /// Outcome of the last fill or load.
+    report: Option<String>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum MapShape {
+    Hexagon,
+    Rhombus,
+    Triangle,
+}
+
+impl MapShape {
+    const ALL: [MapShape; 3] = [MapShape::Hexagon, MapShape::Rhombus, MapShape::Triangle];
+
+    fn name(self) -> &'static str {
+        match self {
+            MapShape::Hexagon => "Hexagon",
+            MapShape::Rhombus => "Rhombus",
+            MapShape::Triangle => "Triangle",
+        }
+    }
+
+    /// Cells of the shape, the same the Shapes menu paints.
+    fn cells(self, size: i32) -> Vec<Hex> {
+        let origin = Hex::new(0, 0);
+        match self {
+            MapShape::Hexagon => origin.range(size).collect(),
+            MapShape::Rhombus => shapes::rhombus(origin, Hex::new(size, size)),
+            MapShape::Triangle => shapes::triangle(origin, size),
+        }
+    }
 }
 
 impl App for Editor {
     fn update(&mut self, ctx: &Context, _frame: &mut Frame) {
-        /*let toolbox = SidePanel::left("toolbox");
+        self.handle_shortcuts(ctx);
+        self.toolbox.handle_shortcuts(ctx);
+        self.terrain_panel.handle_shortcuts(ctx);
+        let menu_bar = TopBottomPanel::top("menu_bar");
+        menu_bar.show(ctx, |ui| {
+            self.draw_menu_bar(ui)
+        });
+        let status_bar = TopBottomPanel::bottom("status_bar");
+        status_bar.show(ctx, |ui| {
+            self.draw_status_bar(ui)
+        });
+        self.draw_file_dialog(ctx);
+        self.draw_goto_dialog(ctx);
+        self.draw_image_import(ctx);
+        self.draw_generator_dialog(ctx);
+        self.draw_synthesis_dialog(ctx);
+        self.draw_error(ctx);
+        let toolbox = SidePanel::left("toolbox");
         toolbox.show(ctx, |ui| {
             self.draw_toolbox(ui)
         });
+        let terrains = SidePanel::right("terrains");
+        terrains.show(ctx, |ui| {
+            self.draw_terrains(ui)
+        });
         let palette = SidePanel::right("palette");
         palette.show(ctx, |ui| {
             self.draw_palette(ui)
-        });*/
+        });
         let canvas = CentralPanel::default();
         canvas.show(ctx, |ui| {
             self.draw_viewport(ui)
@@ -54,20 +214,958 @@ impl App for Editor {
 
 impl Editor {
 
+    #[allow(clippy::needless_lifetimes, clippy::needless_borrow)]
     pub fn new<'a>(cc: &'a CreationContext) -> Self {
         let gl = cc.gl.as_ref().expect("Aplication initialization failed!");
         //Memory and resource allocation issues likely come from here
         Self {
             grid: Grid::default(),
-            color: Color32::from_rgb(25, 200, 100),
+            history: History::default(),
+            palette: Palette::default(),
+            terrain_panel: TerrainPanel::default(),
+            toolbox: Toolbox::default(),
+            selection: Selection::default(),
+            clipboard: None,
+            hovered_cell: None,
+            stroke: None,
+            shape_size: 3,
+            move_offset: [1, 0],
+            relayout: true,
+            offset_odd: true,
+            coordinate_labels: None,
+            goto_dialog: None,
+            image_import: None,
+            generator: Generator::default(),
+            generator_dialog: None,
+            synthesis_dialog: None,
             renderer: Arc::new(Mutex::new(unsafe{Renderer::new(&gl)})),
+            camera: Camera::default(),
+            viewport: Rect::NOTHING,
+            mesh_dirty: false,
+            file_path: None,
+            file_dialog: None,
+            export_options: ImageOptions::default(),
+            error: None,
+        }
+    }
+    fn draw_menu_bar(&mut self, ui: &mut Ui) {
+        menu::bar(ui, |ui| {
+            ui.menu_button("File", |ui| {
+                if ui.button("New").clicked() {
+                    self.set_grid(Grid::default(), None);
+                    ui.close_menu();
+                }
+                if ui.button("Generate...").clicked() {
+                    self.generator_dialog = Some(GeneratorDialog {
+                        generator: self.generator.clone(),
+                        shape: MapShape::Hexagon,
+                        size: 16,
+                        preview: None,
+                        stale: true,
+                    });
+                    ui.close_menu();
+                }
+                if ui.button("Open...").clicked() {
+                    self.open_file_dialog(FileAction::Open);
+                    ui.close_menu();
+                }
+                if ui.button("Save").clicked() {
+                    match self.file_path.clone() {
+                        Some(path) => self.save(path),
+                        None => self.open_file_dialog(FileAction::SaveAs),
+                    }
+                    ui.close_menu();
+                }
+                if ui.button("Save As...").clicked() {
+                    self.open_file_dialog(FileAction::SaveAs);
+                    ui.close_menu();
+                }
+                ui.separator();
+                if ui.button("Import Image...").clicked() {
+                    self.open_file_dialog(FileAction::ImportImage);
+                    ui.close_menu();
+                }
+                if ui.button("Export Image...").clicked() {
+                    self.open_file_dialog(FileAction::ExportImage);
+                    ui.close_menu();
+                }
+                if ui.button("Export Tiled Map...").clicked() {
+                    self.open_file_dialog(FileAction::ExportTiled);
+                    ui.close_menu();
+                }
+            });
+            ui.menu_button("Edit", |ui| {
+                let undo = egui::Button::new("Undo").shortcut_text(ui.ctx().format_shortcut(&UNDO_SHORTCUT));
+                if ui.add_enabled(self.history.can_undo(), undo).clicked() {
+                    self.undo();
+                    ui.close_menu();
+                }
+                let redo = egui::Button::new("Redo").shortcut_text(ui.ctx().format_shortcut(&REDO_SHORTCUT));
+                if ui.add_enabled(self.history.can_redo(), redo).clicked() {
+                    self.redo();
+                    ui.close_menu();
+                }
+                ui.separator();
+                let egui_ctx = ui.ctx().clone();
+                let shortcut_button = |label, shortcut| egui::Button::new(label).shortcut_text(egui_ctx.format_shortcut(shortcut));
+                if ui.add(shortcut_button("Select all", &SELECT_ALL_SHORTCUT)).clicked() {
+                    self.select_all();
+                    ui.close_menu();
+                }
+                let has_selection = !self.selection.is_empty();
+                if ui.add_enabled(has_selection, shortcut_button("Deselect", &DESELECT_SHORTCUT)).clicked() {
+                    self.selection.clear();
+                    ui.close_menu();
+                }
+                if ui.add_enabled(has_selection, egui::Button::new("Cut")).clicked() {
+                    self.copy(ui.ctx());
+                    self.delete_selection();
+                    ui.close_menu();
+                }
+                if ui.add_enabled(has_selection, egui::Button::new("Copy")).clicked() {
+                    self.copy(ui.ctx());
+                    ui.close_menu();
+                }
+                if ui.add_enabled(self.clipboard.is_some(), egui::Button::new("Paste")).clicked() {
+                    if let Some(text) = self.clipboard.clone() {
+                        self.paste(&text, None);
+                    }
+                    ui.close_menu();
+                }
+                if ui.add_enabled(has_selection, shortcut_button("Duplicate", &DUPLICATE_SHORTCUT)).clicked() {
+                    self.duplicate();
+                    ui.close_menu();
+                }
+                if ui.add_enabled(has_selection, shortcut_button("Delete", &DELETE_SHORTCUT)).clicked() {
+                    self.delete_selection();
+                    ui.close_menu();
+                }
+                if ui.button("Fill from Example...").clicked() {
+                    self.synthesis_dialog.get_or_insert_with(|| SynthesisDialog {
+                        rules: Rules::default(),
+                        source: String::new(),
+                        rules_path: String::new(),
+                        options: Synthesis::default(),
+                        report: None,
+                    });
+                    ui.close_menu();
+                }
+                ui.separator();
+                let editable = self.grid.active_layer().is_editable();
+                if ui.add_enabled(editable, egui::Button::new("Clear layer")).clicked() {
+                    let sites: Vec<_> = self.grid.active_layer().sites().map(|(site, _)| (site, None)).collect();
+                    self.history.apply(&mut self.grid, sites);
+                    self.mesh_dirty = true;
+                    ui.close_menu();
+                }
+                if ui.button("Clear map").clicked() {
+                    let layers: Vec<_> = self.grid.layers().iter()
+                        .filter(|layer| layer.is_editable())
+                        .map(|layer| (layer.id(), layer.sites().map(|(site, _)| (site, None)).collect::<Vec<_>>()))
+                        .collect();
+                    self.history.begin();
+                    for (layer, sites) in layers {
+                        self.history.apply_to_layer(&mut self.grid, layer, sites);
+                    }
+                    self.history.end();
+                    self.mesh_dirty = true;
+                    ui.close_menu();
+                }
+            });
+            ui.menu_button("View", |ui| {
+                if ui.button("Fit to content").clicked() {
+                    self.fit_to_content();
+                    ui.close_menu();
+                }
+                if ui.button("Reset view").clicked() {
+                    self.camera.reset();
+                    ui.close_menu();
+                }
+                let goto = egui::Button::new("Go to coordinate...").shortcut_text(ui.ctx().format_shortcut(&GOTO_SHORTCUT));
+                if ui.add(goto).clicked() {
+                    self.open_goto_dialog();
+                    ui.close_menu();
+                }
+                ui.separator();
+                let orientation = self.grid.orientation();
+                let mut labels = self.coordinate_labels.is_some();
+                ui.checkbox(&mut labels, "Coordinate labels");
+                let mut system = self.coordinate_labels.unwrap_or(CoordSystem::Axial);
+                ui.add_enabled_ui(labels, |ui| {
+                    for option in CoordSystem::ALL {
+                        ui.radio_value(&mut system, option, option.name(orientation));
+                    }
+                });
+                self.coordinate_labels = labels.then_some(system);
+            });
+            ui.menu_button("Map", |ui| {
+                ui.label("Orientation:");
+                let current = self.grid.orientation();
+                let mut orientation = current;
+                ui.radio_value(&mut orientation, HexOrientation::Pointy, "Pointy top");
+                ui.radio_value(&mut orientation, HexOrientation::Flat, "Flat top");
+                ui.checkbox(&mut self.relayout, "Keep rows and columns")
+                    .on_hover_text("Otherwise cells keep their coordinates and the map turns by 30°");
+                if orientation != current {
+                    self.grid.set_orientation(orientation, self.relayout);
+                    if self.relayout {
+                        //Recorded edits refer to where cells were before
+                        self.history.clear();
+                        self.selection.clear();
+                    }
+                    self.mesh_dirty = true;
+                    self.fit_to_content();
+                }
+                ui.separator();
+                let Point { x, y } = self.grid.layout().size;
+                let (mut width, mut height) = (x, y);
+                ui.horizontal(|ui| {
+                    ui.label("Hex size:");
+                    ui.add(DragValue::new(&mut width).speed(0.001).range(0.001..=10.0));
+                    ui.add(DragValue::new(&mut height).speed(0.001).range(0.001..=10.0));
+                });
+                if ui.button("Regular hexagons").on_hover_text("Sets the height to the width").clicked() {
+                    height = width;
+                }
+                if (width, height) != (x, y) {
+                    self.grid.set_hex_size(Point { x: width, y: height });
+                    self.mesh_dirty = true;
+                }
+            });
+            ui.menu_button("Transform", |ui| {
+                ui.label(if self.selection.is_empty() { "Whole map" } else { "Selection" });
+                //Pivots are filled in by `transform`
+                let pivot = Hex::new(0, 0);
+                let rotate = |steps| Transform::Rotate { pivot, steps };
+                let reflect = |axis| Transform::Reflect { pivot, axis };
+                let transform = if ui.button("Rotate left").clicked() {
+                    Some(rotate(-1))
+                } else if ui.button("Rotate right").clicked() {
+                    Some(rotate(1))
+                } else if ui.button("Rotate 180°").clicked() {
+                    Some(rotate(3))
+                } else if ui.button("Mirror across q").clicked() {
+                    Some(reflect(HexAxis::Q))
+                } else if ui.button("Mirror across r").clicked() {
+                    Some(reflect(HexAxis::R))
+                } else if ui.button("Mirror across s").clicked() {
+                    Some(reflect(HexAxis::S))
+                } else {
+                    None
+                };
+                let [q, r] = &mut self.move_offset;
+                let moved = ui.horizontal(|ui| {
+                    ui.add(DragValue::new(q).prefix("q: "));
+                    ui.add(DragValue::new(r).prefix("r: "));
+                    ui.button("Move").clicked()
+                }).inner;
+                let transform = transform.or(moved.then_some(Transform::Translate(Hex::new(*q, *r))));
+                if let Some(transform) = transform {
+                    self.transform(transform);
+                    ui.close_menu();
+                }
+            });
+            ui.menu_button("Shapes", |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Size:");
+                    ui.add(DragValue::new(&mut self.shape_size).range(0..=64));
+                });
+                let shape = if ui.button("Hexagon").clicked() {
+                    Some(Grid::make_hex((0, 0), self.shape_size))
+                } else if ui.button("Rhombus").clicked() {
+                    Some(Grid::make_rhombus((0, 0), (self.shape_size, self.shape_size)))
+                } else if ui.button("Triangle").clicked() {
+                    Some(Grid::make_triangle((0, 0), self.shape_size))
+                } else {
+                    None
+                };
+                //Locked and hidden layers are left alone, like with the tools
+                if let Some(shape) = shape.filter(|_| self.grid.active_layer().is_editable()) {
+                    let cells: Vec<_> = shape.cells().map(|hex| (hex, Some(self.terrain_panel.primary))).collect();
+                    self.history.apply(&mut self.grid, cells);
+                    self.mesh_dirty = true;
+                    ui.close_menu();
+                }
+            });
+        });
+    }
+    fn handle_shortcuts(&mut self, ctx: &Context) {
+        //Text fields keep their own undo and shortcuts
+        if ctx.wants_keyboard_input() {
+            return;
+        }
+        //Redo first, since its shortcut also matches undo's
+        if ctx.input_mut(|input| input.consume_shortcut(&REDO_SHORTCUT)) {
+            self.redo();
+        } else if ctx.input_mut(|input| input.consume_shortcut(&UNDO_SHORTCUT)) {
+            self.undo();
+        }
+        if ctx.input_mut(|input| input.consume_shortcut(&GOTO_SHORTCUT)) {
+            self.open_goto_dialog();
+        }
+        if ctx.input_mut(|input| input.consume_shortcut(&SELECT_ALL_SHORTCUT)) {
+            self.select_all();
+        }
+        if ctx.input_mut(|input| input.consume_shortcut(&DESELECT_SHORTCUT)) {
+            self.selection.clear();
+        }
+        if ctx.input_mut(|input| input.consume_shortcut(&DUPLICATE_SHORTCUT)) {
+            self.duplicate();
+        }
+        if ctx.input_mut(|input| input.consume_shortcut(&DELETE_SHORTCUT)) {
+            self.delete_selection();
+        }
+        //The platform turns the clipboard shortcuts into events
+        for event in ctx.input(|input| input.events.clone()) {
+            match event {
+                Event::Copy => self.copy(ctx),
+                Event::Cut => {
+                    self.copy(ctx);
+                    self.delete_selection();
+                }
+                //Text copied from elsewhere is not ours to paste
+                Event::Paste(text) if text.contains(CLIPBOARD_TAG) => self.paste(&text, self.hovered_cell),
+                _ => (),
+            }
+        }
+    }
+    fn select_all(&mut self) {
+        self.selection.apply(SelectionOp::Replace, self.grid.cells());
+    }
+    /// Copies the selected cells of the active layer, with the edges and corners
+    /// between them, to the system clipboard.
+    fn copy(&mut self, ctx: &Context) {
+        if let Some(text) = self.grid.copy_cells(self.selection.cells()) {
+            ctx.copy_text(text.clone());
+            self.clipboard = Some(text);
+        }
+    }
+    /// Pastes copied sites into the active layer, their origin at `at` or where
+    /// they were copied from, and selects their cells.
+    fn paste(&mut self, text: &str, at: Option<Hex>) {
+        match self.grid.paste_cells(text, at) {
+            Ok(cells) => self.place_cells(cells),
+            Err(error) => self.error = Some(format!("Failed to paste: {error}")),
+        }
+    }
+    /// Copies the selected cells next to themselves, one selection width to the right.
+    fn duplicate(&mut self) {
+        let Some(text) = self.grid.copy_cells(self.selection.cells()) else {
+            return;
+        };
+        let (min, max) = self.selection.cells()
+            .fold((i32::MAX, i32::MIN), |(min, max), hex| (min.min(hex.q()), max.max(hex.q())));
+        let offset = Hex::new(max - min + 1, 0);
+        match self.grid.paste_cells(&text, None) {
+            Ok(sites) => {
+                let translate = Transform::Translate(offset);
+                self.place_cells(sites.into_iter().map(|(site, terrain)| (translate.apply_site(site), terrain)).collect());
+            }
+            Err(error) => self.error = Some(format!("Failed to duplicate: {error}")),
+        }
+    }
+    fn place_cells(&mut self, sites: Vec<(Site, TerrainId)>) {
+        if !self.grid.active_layer().is_editable() {
+            return;
+        }
+        self.selection.apply(SelectionOp::Replace, sites.iter().flat_map(|(site, _)| site.cells()));
+        self.history.apply(&mut self.grid, sites.into_iter().map(|(site, terrain)| (site, Some(terrain))));
+        self.mesh_dirty = true;
+    }
+    /// Applies `transform` around the middle of the selection, or of the whole map
+    /// when nothing is selected, as one undo step.
+    fn transform(&mut self, transform: Transform) {
+        if self.selection.is_empty() {
+            //Hidden and locked layers stay where they are, so they do not count either
+            let all = self.grid.layers().iter()
+                .filter(|layer| layer.is_editable())
+                .flat_map(|layer| layer.occupied_cells());
+            let Some(pivot) = center_of(all) else {
+                return;
+            };
+            self.history.begin();
+            for (layer, changes) in self.grid.transform_map(transform.around(pivot)) {
+                self.history.apply_to_layer(&mut self.grid, layer, changes);
+            }
+            self.history.end();
+        } else {
+            let Some(pivot) = center_of(self.selection.cells()) else {
+                return;
+            };
+            if !self.grid.active_layer().is_editable() {
+                return;
+            }
+            let transform = transform.around(pivot);
+            let layer = self.grid.active_layer().id();
+            let changes = self.grid.transform_changes(layer, self.selection.cells(), transform);
+            self.history.apply(&mut self.grid, changes);
+            let cells: Vec<_> = self.selection.cells().map(|hex| transform.apply(hex)).collect();
+            self.selection.apply(SelectionOp::Replace, cells);
+        }
+        self.mesh_dirty = true;
+    }
+    fn delete_selection(&mut self) {
+        if !self.grid.active_layer().is_editable() {
+            return;
+        }
+        let cells: Vec<_> = self.selection.cells().map(|hex| (hex, None)).collect();
+        self.history.apply(&mut self.grid, cells);
+        self.mesh_dirty = true;
+    }
+    fn fit_to_content(&mut self) {
+        match self.grid.bounds() {
+            Some((min, max)) if self.viewport.is_positive() => self.camera.fit(&self.viewport, min, max),
+            _ => self.camera.reset(),
+        }
+    }
+    fn undo(&mut self) {
+        if self.history.undo(&mut self.grid) {
+            self.mesh_dirty = true;
+        }
+    }
+    fn redo(&mut self) {
+        if self.history.redo(&mut self.grid) {
+            self.mesh_dirty = true;
+        }
+    }
+    fn open_file_dialog(&mut self, action: FileAction) {
+        let path = match action {
+            FileAction::Open | FileAction::SaveAs => self.file_path.as_ref().map(|path| path.display().to_string()),
+            FileAction::ImportPalette | FileAction::ExportPalette | FileAction::ImportImage => None,
+            FileAction::ExportImage => {
+                self.export_options.labels = self.coordinate_labels;
+                self.file_path.as_ref().map(|path| path.with_extension("png").display().to_string())
+            }
+            FileAction::ExportTiled => self.file_path.as_ref().map(|path| path.with_extension("tmx").display().to_string()),
+        };
+        let path = path.unwrap_or_default();
+        self.file_dialog = Some(FileDialog { action, path });
+    }
+    fn draw_file_dialog(&mut self, ctx: &Context) {
+        let Some(dialog) = &mut self.file_dialog else {
+            return;
+        };
+        let title = match dialog.action {
+            FileAction::Open => "Open map (.json, .tmx, .tmj)",
+            FileAction::SaveAs => "Save map as",
+            FileAction::ImportPalette => "Import palette (.gpl, .txt, .hex)",
+            FileAction::ExportPalette => "Export palette (.gpl, .txt, .hex)",
+            FileAction::ExportImage => "Export image (.png, .svg)",
+            FileAction::ExportTiled => "Export Tiled map (.tmx, .tmj)",
+            FileAction::ImportImage => "Import image (.png, .jpg)",
+        };
+        let export_options = &mut self.export_options;
+        let orientation = self.grid.orientation();
+        let (mut confirmed, mut cancelled) = (false, false);
+        Window::new(title)
+            .collapsible(false)
+            .resizable(false)
+            .anchor(Align2::CENTER_CENTER, [0.0, 0.0])
+            .show(ctx, |ui| {
+                ui.label("Path:");
+                let input = ui.add(TextEdit::singleline(&mut dialog.path).desired_width(320.0));
+                let submitted = input.lost_focus() && ui.input(|input| input.key_pressed(egui::Key::Enter));
+                if dialog.action == FileAction::ExportImage {
+                    export_options_ui(ui, export_options, orientation);
+                }
+                ui.horizontal(|ui| {
+                    confirmed = ui.button("OK").clicked() || submitted;
+                    cancelled = ui.button("Cancel").clicked();
+                });
+            });
+        if cancelled {
+            self.file_dialog = None;
+        } else if confirmed && !dialog.path.trim().is_empty() {
+            let action = dialog.action;
+            let path = PathBuf::from(dialog.path.trim());
+            self.file_dialog = None;
+            match action {
+                FileAction::Open => self.open(path),
+                FileAction::SaveAs => self.save(path),
+                FileAction::ImportPalette => match Palette::load(&path) {
+                    Ok(palette) => self.palette.swatches.extend(palette.swatches),
+                    Err(error) => self.error = Some(format!("Failed to import {}: {error}", path.display())),
+                },
+                FileAction::ExportPalette => if let Err(error) = self.palette.save(&path) {
+                    self.error = Some(format!("Failed to export {}: {error}", path.display()));
+                },
+                FileAction::ExportImage => self.export_image(path),
+                FileAction::ExportTiled => if let Err(error) = self.grid.save_tiled(&path) {
+                    self.error = Some(format!("Failed to export {}: {error}", path.display()));
+                },
+                FileAction::ImportImage => self.open_image_import(ctx, path),
+            }
+        }
+    }
+    fn open_image_import(&mut self, ctx: &Context, path: PathBuf) {
+        let image = match grid::load_image(&path) {
+            Ok(image) => image,
+            Err(error) => {
+                self.error = Some(format!("Failed to import {}: {error}", path.display()));
+                return;
+            }
+        };
+        let size = [image.width() as usize, image.height() as usize];
+        let texture = ctx.load_texture("image_import", ColorImage::from_rgba_unmultiplied(size, image.as_raw()), TextureOptions::NEAREST);
+        let cells = 32.0;
+        let mut options = RasterImport::default();
+        options.fit_width(&image, self.grid.layout(), cells);
+        //Centered in the view
+        let center = self.camera.center;
+        options.origin = Point {
+            x: center.x as f64 - image.width() as f64 * options.scale / 2.0,
+            y: center.y as f64 - image.height() as f64 * options.scale / 2.0,
+        };
+        self.image_import = Some(ImageImportDialog { path, image, texture, options, cells, quantize: false });
+    }
+    fn draw_image_import(&mut self, ctx: &Context) {
+        let Some(dialog) = &mut self.image_import else {
+            return;
+        };
+        let layout = self.grid.layout();
+        let (mut confirmed, mut cancelled) = (false, false);
+        Window::new("Import image")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(Align2::RIGHT_TOP, [-8.0, 32.0])
+            .show(ctx, |ui| {
+                ui.label(dialog.path.display().to_string());
+                ui.label(format!("{} × {} pixels", dialog.image.width(), dialog.image.height()));
+                egui::Grid::new("image_import").num_columns(2).show(ui, |ui| {
+                    ui.label("Position:");
+                    ui.horizontal(|ui| {
+                        ui.add(DragValue::new(&mut dialog.options.origin.x).speed(0.01).prefix("x: "));
+                        ui.add(DragValue::new(&mut dialog.options.origin.y).speed(0.01).prefix("y: "));
+                    });
+                    ui.end_row();
+                    ui.label("Width:");
+                    let width = ui.add(DragValue::new(&mut dialog.cells).range(1.0..=4096.0).speed(0.1).suffix(" cells"));
+                    if width.changed() {
+                        dialog.options.fit_width(&dialog.image, layout, dialog.cells);
+                    }
+                    ui.end_row();
+                    ui.label("Sampling:");
+                    ComboBox::from_id_source("image_import_sampling")
+                        .selected_text(dialog.options.sampling.name())
+                        .show_ui(ui, |ui| {
+                            for sampling in Sampling::ALL {
+                                ui.selectable_value(&mut dialog.options.sampling, sampling, sampling.name());
+                            }
+                        });
+                    ui.end_row();
+                    ui.label("Skip alpha below:");
+                    ui.add(DragValue::new(&mut dialog.options.min_alpha));
+                    ui.end_row();
+                });
+                ui.checkbox(&mut dialog.quantize, "Snap colors to the palette");
+                ui.label("The image replaces the current map.");
+                ui.horizontal(|ui| {
+                    confirmed = ui.button("Import").clicked();
+                    cancelled = ui.button("Cancel").clicked();
+                });
+            });
+        if cancelled {
+            self.image_import = None;
+        } else if confirmed {
+            let dialog = self.image_import.take().expect("Dialog closed while open!");
+            let mut options = dialog.options;
+            if dialog.quantize {
+                options.palette = self.palette.swatches.iter()
+                    .map(|swatch| Terrain::new(swatch.name.clone(), swatch.color))
+                    .collect();
+            }
+            match Grid::from_image(layout, &dialog.image, &options) {
+                Ok(grid) => {
+                    self.set_grid(grid, None);
+                    self.fit_to_content();
+                }
+                Err(error) => self.error = Some(format!("Failed to import {}: {error}", dialog.path.display())),
+            }
+        }
+    }
+    fn draw_generator_dialog(&mut self, ctx: &Context) {
+        let Some(dialog) = &mut self.generator_dialog else {
+            return;
+        };
+        let layout = self.grid.layout();
+        if dialog.stale {
+            dialog.stale = false;
+            let grid = Grid::generate(layout, dialog.shape.cells(dialog.size), &dialog.generator);
+            dialog.preview = grid.bounds().and_then(|(min, max)| {
+                let extent = (max.x - min.x).max(max.y - min.y);
+                let cell_size = PREVIEW_SIDE * layout.size.x.max(layout.size.y) / extent;
+                let image = grid.render(&ImageOptions { cell_size: cell_size as f32, margin: 0, ..Default::default() }).ok()?;
+                let size = [image.width() as usize, image.height() as usize];
+                Some(ctx.load_texture("generator_preview", ColorImage::from_rgba_unmultiplied(size, image.as_raw()), TextureOptions::LINEAR))
+            });
+        }
+        let (mut confirmed, mut cancelled, mut changed) = (false, false, false);
+        let terrains = self.grid.terrains();
+        let primary = self.palette.primary;
+        Window::new("Generate map")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(Align2::CENTER_CENTER, [0.0, 0.0])
+            .show(ctx, |ui| {
+                let generator = &mut dialog.generator;
+                egui::Grid::new("generator").num_columns(2).show(ui, |ui| {
+                    ui.label("Shape:");
+                    ui.horizontal(|ui| {
+                        ComboBox::from_id_source("generator_shape")
+                            .selected_text(dialog.shape.name())
+                            .show_ui(ui, |ui| {
+                                for shape in MapShape::ALL {
+                                    changed |= ui.selectable_value(&mut dialog.shape, shape, shape.name()).changed();
+                                }
+                            });
+                        changed |= ui.add(DragValue::new(&mut dialog.size).range(0..=128).prefix("size: ")).changed();
+                    });
+                    ui.end_row();
+                    ui.label("Seed:");
+                    ui.horizontal(|ui| {
+                        changed |= ui.add(DragValue::new(&mut generator.seed)).changed();
+                        if ui.button("Random").clicked() {
+                            generator.seed = random_seed();
+                            changed = true;
+                        }
+                    });
+                    ui.end_row();
+                    ui.label("Feature size:");
+                    changed |= ui.add(DragValue::new(&mut generator.scale).range(0.5..=256.0).speed(0.1).suffix(" cells")).changed();
+                    ui.end_row();
+                    ui.label("Octaves:");
+                    changed |= ui.add(DragValue::new(&mut generator.octaves).range(1..=grid::MAX_OCTAVES)).changed();
+                    ui.end_row();
+                    ui.label("Persistence:");
+                    changed |= ui.add(DragValue::new(&mut generator.persistence).range(0.0..=1.0).speed(0.01)).changed();
+                    ui.end_row();
+                    ui.label("Lacunarity:");
+                    changed |= ui.add(DragValue::new(&mut generator.lacunarity).range(1.0..=4.0).speed(0.01)).changed();
+                    ui.end_row();
+                });
+                ui.separator();
+                ui.label("Biomes, first match wins:");
+                let mut removed = None;
+                egui::Grid::new("generator_biomes").num_columns(5).show(ui, |ui| {
+                    ui.label("Terrain");
+                    ui.label("");
+                    ui.label("Elevation ≤");
+                    ui.label("Moisture ≤");
+                    ui.end_row();
+                    for (index, biome) in generator.biomes.iter_mut().enumerate() {
+                        changed |= ui.add(TextEdit::singleline(&mut biome.terrain.name).desired_width(80.0)).changed();
+                        changed |= ui.color_edit_button_srgba(&mut biome.terrain.color).changed();
+                        changed |= ui.add(DragValue::new(&mut biome.max_elevation).range(0.0..=1.0).speed(0.005)).changed();
+                        changed |= ui.add(DragValue::new(&mut biome.max_moisture).range(0.0..=1.0).speed(0.005)).changed();
+                        if ui.small_button("✖").clicked() {
+                            removed = Some(index);
+                        }
+                        ui.end_row();
+                    }
+                });
+                if let Some(index) = removed {
+                    generator.biomes.remove(index);
+                    changed = true;
+                }
+                ui.horizontal(|ui| {
+                    ComboBox::from_id_source("generator_add")
+                        .selected_text("Add terrain")
+                        .show_ui(ui, |ui| {
+                            for (_, terrain) in terrains.iter() {
+                                if ui.selectable_label(false, &terrain.name).clicked() {
+                                    generator.biomes.push(Biome::new(terrain.clone(), 1.0, 1.0));
+                                    changed = true;
+                                }
+                            }
+                        });
+                    if ui.button("Add color").clicked() {
+                        let [r, g, b, _] = primary.to_srgba_unmultiplied();
+                        generator.biomes.push(Biome::new(Terrain::new(format!("#{r:02x}{g:02x}{b:02x}"), primary), 1.0, 1.0));
+                        changed = true;
+                    }
+                    if ui.button("Defaults").clicked() {
+                        generator.biomes = Generator::default().biomes;
+                        changed = true;
+                    }
+                });
+                ui.separator();
+                match &dialog.preview {
+                    Some(texture) => {
+                        ui.image((texture.id(), texture.size_vec2()));
+                    }
+                    None => {
+                        ui.label("The map is empty.");
+                    }
+                }
+                ui.label("The generated map replaces the current one.");
+                ui.horizontal(|ui| {
+                    confirmed = ui.button("Generate").clicked();
+                    cancelled = ui.button("Cancel").clicked();
+                });
+            });
+        dialog.stale |= changed;
+        if cancelled {
+            self.generator_dialog = None;
+        } else if confirmed {
+            let dialog = self.generator_dialog.take().expect("Dialog closed while open!");
+            let grid = Grid::generate(layout, dialog.shape.cells(dialog.size), &dialog.generator);
+            self.generator = dialog.generator;
+            self.set_grid(grid, None);
+            self.fit_to_content();
+        }
+    }
+    fn draw_synthesis_dialog(&mut self, ctx: &Context) {
+        let Some(dialog) = &mut self.synthesis_dialog else {
+            return;
+        };
+        let has_selection = !self.selection.is_empty();
+        let (mut learn, mut load, mut fill, mut closed) = (None, false, false, false);
+        Window::new("Fill from example")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(Align2::RIGHT_TOP, [-8.0, 32.0])
+            .show(ctx, |ui| {
+                ui.label("Learn which terrains lie next to which from painted cells, or load a rule table.");
+                ui.horizontal(|ui| {
+                    if ui.add_enabled(has_selection, egui::Button::new("Learn from selection")).clicked() {
+                        learn = Some(true);
+                    }
+                    if ui.button("Learn from layer").clicked() {
+                        learn = Some(false);
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.add(TextEdit::singleline(&mut dialog.rules_path).hint_text("rules.txt").desired_width(200.0));
+                    load = ui.button("Load").clicked();
+                });
+                match dialog.rules.is_empty() {
+                    true => ui.label("No rules yet."),
+                    false => ui.label(format!(
+                        "{}: {} terrains, {} neighbor rules.", dialog.source, dialog.rules.len(), dialog.rules.adjacencies()
+                    )),
+                };
+                ui.separator();
+                egui::Grid::new("synthesis").num_columns(2).show(ui, |ui| {
+                    ui.label("Seed:");
+                    ui.horizontal(|ui| {
+                        ui.add(DragValue::new(&mut dialog.options.seed));
+                        if ui.button("Random").clicked() {
+                            dialog.options.seed = random_seed();
+                        }
+                    });
+                    ui.end_row();
+                    ui.label("Backtracks:");
+                    ui.add(DragValue::new(&mut dialog.options.max_backtracks).range(0..=100_000));
+                    ui.end_row();
+                });
+                ui.checkbox(&mut dialog.options.keep_painted, "Keep painted cells");
+                if let Some(report) = &dialog.report {
+                    ui.label(report);
+                }
+                ui.horizontal(|ui| {
+                    fill = ui.add_enabled(has_selection && !dialog.rules.is_empty(), egui::Button::new("Fill selection")).clicked();
+                    closed = ui.button("Close").clicked();
+                });
+            });
+        if let Some(selected) = learn {
+            let (source, cells): (_, Vec<_>) = match selected {
+                true => ("Selection", self.selection.cells().collect()),
+                false => ("Layer", self.grid.cells().collect()),
+            };
+            dialog.rules = Rules::learn(&self.grid, cells);
+            dialog.source = source.into();
+            dialog.report = None;
+        }
+        if load {
+            let path = PathBuf::from(dialog.rules_path.trim());
+            match Rules::load(&path, self.grid.terrains()) {
+                Ok(rules) => {
+                    dialog.rules = rules;
+                    dialog.source = path.display().to_string();
+                    dialog.report = None;
+                }
+                Err(error) => dialog.report = Some(format!("Failed to load {}: {error}", path.display())),
+            }
+        }
+        if fill {
+            if !self.grid.active_layer().is_editable() {
+                dialog.report = Some("The active layer is hidden or locked.".into());
+            } else {
+                match self.grid.synthesize(self.selection.cells(), &dialog.rules, &dialog.options) {
+                    Ok(cells) => {
+                        dialog.report = Some(format!("Filled {} cells.", cells.len()));
+                        self.history.apply(&mut self.grid, cells.into_iter().map(|(hex, terrain)| (hex, Some(terrain))));
+                        self.mesh_dirty = true;
+                    }
+                    Err(error) => dialog.report = Some(format!("Failed: {error}.")),
+                }
+            }
+        }
+        if closed {
+            self.synthesis_dialog = None;
+        }
+    }
+    fn open_goto_dialog(&mut self) {
+        let system = self.coordinate_labels.unwrap_or(CoordSystem::Axial);
+        let text = self.hovered_cell.map(|hex| system.format(hex, self.grid.orientation())).unwrap_or_default();
+        self.goto_dialog = Some(GotoDialog { system, text, error: None });
+    }
+    fn draw_goto_dialog(&mut self, ctx: &Context) {
+        let Some(dialog) = &mut self.goto_dialog else {
+            return;
+        };
+        let orientation = self.grid.orientation();
+        let (mut confirmed, mut cancelled) = (false, false);
+        Window::new("Go to coordinate")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(Align2::CENTER_CENTER, [0.0, 0.0])
+            .show(ctx, |ui| {
+                ComboBox::from_label("System")
+                    .selected_text(dialog.system.name(orientation))
+                    .show_ui(ui, |ui| {
+                        for option in CoordSystem::ALL {
+                            ui.selectable_value(&mut dialog.system, option, option.name(orientation));
+                        }
+                    });
+                let input = ui.add(TextEdit::singleline(&mut dialog.text).hint_text("q, r"));
+                let submitted = input.lost_focus() && ui.input(|input| input.key_pressed(egui::Key::Enter));
+                if let Some(error) = &dialog.error {
+                    ui.colored_label(ui.visuals().error_fg_color, error);
+                }
+                ui.horizontal(|ui| {
+                    confirmed = ui.button("Go").clicked() || submitted;
+                    cancelled = ui.button("Cancel").clicked();
+                });
+            });
+        if cancelled {
+            self.goto_dialog = None;
+        } else if confirmed {
+            match dialog.system.parse(&dialog.text, orientation) {
+                Ok(hex) => {
+                    let Point { x, y } = self.grid.cell_center(hex);
+                    self.camera.center = [x as f32, y as f32].into();
+                    self.goto_dialog = None;
+                }
+                Err(error) => dialog.error = Some(format!("Invalid coordinates: {error}")),
+            }
+        }
+    }
+    fn draw_status_bar(&mut self, ui: &mut Ui) {
+        ui.horizontal(|ui| {
+            let Some(hex) = self.hovered_cell else {
+                ui.label("Hover a cell to see its coordinates");
+                return;
+            };
+            let orientation = self.grid.orientation();
+            let offset = CoordSystem::Offset { odd: self.offset_odd };
+            for system in [CoordSystem::Cube, CoordSystem::Axial, offset, CoordSystem::Doubled] {
+                if system == offset {
+                    ComboBox::from_id_source("offset_parity")
+                        .selected_text(system.name(orientation))
+                        .show_ui(ui, |ui| {
+                            for odd in [true, false] {
+                                let option = CoordSystem::Offset { odd };
+                                ui.selectable_value(&mut self.offset_odd, odd, option.name(orientation));
+                            }
+                        });
+                } else {
+                    ui.label(format!("{}:", system.name(orientation)));
+                }
+                ui.monospace(format!("({})", system.format(hex, orientation)));
+                ui.separator();
+            }
+            let terrain = self.grid.cell(hex).and_then(|id| self.grid.terrains().get(id));
+            ui.label(terrain.map_or("Empty", |terrain| terrain.name.as_str()));
+        });
+    }
+    fn draw_error(&mut self, ctx: &Context) {
+        let Some(message) = &self.error else {
+            return;
+        };
+        let mut dismissed = false;
+        Window::new("Error")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(Align2::CENTER_CENTER, [0.0, 0.0])
+            .show(ctx, |ui| {
+                ui.label(message);
+                dismissed = ui.button("OK").clicked();
+            });
+        if dismissed {
+            self.error = None;
+        }
+    }
+    fn open(&mut self, path: PathBuf) {
+        let extension = path.extension().and_then(|extension| extension.to_str()).unwrap_or_default();
+        //Tiled maps are imported: saving them asks for a new path
+        let result = match extension.to_ascii_lowercase().as_str() {
+            "tmx" | "tmj" => Grid::load_tiled(&path).map(|grid| (grid, None)).map_err(|error| error.to_string()),
+            _ => Grid::load(&path).map(|grid| (grid, Some(path.clone()))).map_err(|error| error.to_string()),
+        };
+        match result {
+            Ok((grid, file_path)) => {
+                self.set_grid(grid, file_path);
+                self.fit_to_content();
+            }
+            Err(error) => self.error = Some(format!("Failed to open {}: {error}", path.display())),
+        }
+    }
+    fn save(&mut self, path: PathBuf) {
+        match self.grid.save(&path) {
+            Ok(()) => self.file_path = Some(path),
+            Err(error) => self.error = Some(format!("Failed to save {}: {error}", path.display())),
+        }
+    }
+    fn export_image(&mut self, path: PathBuf) {
+        let extension = path.extension().and_then(|extension| extension.to_str()).unwrap_or_default();
+        let result = match extension.to_ascii_lowercase().as_str() {
+            "png" => self.grid.save_png(&path, &self.export_options),
+            "svg" => self.grid.save_svg(&path, &self.export_options),
+            _ => {
+                self.error = Some(format!("Failed to export {}: use a .png or .svg file", path.display()));
+                return;
+            }
+        };
+        if let Err(error) = result {
+            self.error = Some(format!("Failed to export {}: {error}", path.display()));
+        }
+    }
+    fn set_grid(&mut self, grid: Grid, path: Option<PathBuf>) {
+        self.grid = grid;
+        self.terrain_panel.validate(&self.grid);
+        self.history.clear();
+        self.selection.clear();
+        self.file_path = path;
+        self.mesh_dirty = true;
+    }
+    fn draw_toolbox(&mut self, ui: &mut Ui) {
+        self.toolbox.ui(ui);
+        ui.separator();
+        if layer_panel::ui(ui, &mut self.grid, &mut self.history) {
+            self.mesh_dirty = true;
         }
     }
-    fn _draw_toolbox(&mut self, ui: &mut Ui) {
-        ui.label("Toolbox");
+    fn draw_terrains(&mut self, ui: &mut Ui) {
+        let response = self.terrain_panel.ui(ui, &mut self.grid, self.palette.primary);
+        if response.removed {
+            //Undoing could bring back cells of the deleted terrain
+            self.history.clear();
+        }
+        if response.recolored {
+            self.mesh_dirty = true;
+        }
     }
-    fn _draw_palette(&mut self, ui: &mut Ui) {
-        ui.label("Palette");
+    fn draw_palette(&mut self, ui: &mut Ui) {
+        self.palette.ui(ui);
+        ui.horizontal(|ui| {
+            if ui.button("Import...").clicked() {
+                self.open_file_dialog(FileAction::ImportPalette);
+            }
+            if ui.button("Export...").clicked() {
+                self.open_file_dialog(FileAction::ExportPalette);
+            }
+        });
     }
     fn draw_viewport(&mut self, ui: &mut Ui) -> Response {
 
@@ -76,18 +1174,86 @@ impl Editor {
         let (mut response, painter) = ui.allocate_painter(viewport_size, egui::Sense::click_and_drag());
         //let (_rect, mut response) = ui.allocate_exact_size(viewport_size, egui::Sense::click_and_drag());
  
-        let frustum_to_ui = canvas_to_ui(&response.rect);
-        let ui_to_frustum = frustum_to_ui.inverse();
+        let viewport = response.rect;
+        self.viewport = viewport;
 
-        let space_pressed = ui.ctx().input(|input|{
-            input.key_down( egui::Key::Space)
+        let (space_pressed, middle_pressed) = ui.ctx().input(|input|{
+            (input.key_down(egui::Key::Space), input.pointer.button_down(PointerButton::Middle))
         });
-        
-        if let (Some(screen_pos), false) = (response.interact_pointer_pos(), space_pressed) {
-            let canvas_pos: [f32; 2]  = (ui_to_frustum * screen_pos).into();
-            let cell = self.grid.sample_cell(canvas_pos);
-            self.grid.paint_cell(cell, self.color);
 
+        let hovered_cell = response.hover_pos()
+            .map(|screen_pos| self.grid.sample_cell(self.camera.screen_to_world(&viewport, screen_pos)));
+        self.hovered_cell = hovered_cell;
+        let tool = self.toolbox.active();
+        tool.hover(&self.grid, hovered_cell);
+        tool.hover_point(&self.grid, response.hover_pos().map(|screen_pos| self.camera.screen_to_world(&viewport, screen_pos).into()));
+
+        if space_pressed || middle_pressed {
+            self.camera.pan(&viewport, response.drag_delta());
+            ui.ctx().set_cursor_icon(CursorIcon::Grabbing);
+        } else {
+            if hovered_cell.is_some() {
+                let cursor = if self.grid.active_layer().is_editable() { tool.cursor() } else { CursorIcon::NotAllowed };
+                ui.ctx().set_cursor_icon(cursor);
+            }
+            let pointer_cell = response.interact_pointer_pos()
+                .map(|screen_pos| self.grid.sample_cell(self.camera.screen_to_world(&viewport, screen_pos)));
+            //Still known on release, from the hover position
+            let pointer = response.interact_pointer_pos().or(response.hover_pos())
+                .map_or(Point { x: 0.0, y: 0.0 }, |screen_pos| self.camera.screen_to_world(&viewport, screen_pos).into());
+            let terrains = [self.terrain_panel.primary, self.terrain_panel.secondary];
+            let modifiers = ui.ctx().input(|input| input.modifiers);
+            let moved = response.drag_delta() != egui::Vec2::ZERO;
+            let changed = match (pointer_cell, self.stroke) {
+                (Some(cell), None) => {
+                    let button = if ui.ctx().input(|input| input.pointer.secondary_down()) {
+                        PointerButton::Secondary
+                    } else {
+                        PointerButton::Primary
+                    };
+                    self.stroke = Some((button, cell));
+                    self.history.begin();
+                    let mut ctx = ToolContext::new(&mut self.grid, &mut self.history, &mut self.selection, terrains, button, modifiers, pointer);
+                    tool.pointer_down(&mut ctx, cell);
+                    ctx.changed()
+                }
+                (Some(cell), Some((button, last))) => {
+                    self.stroke = Some((button, cell));
+                    let mut ctx = ToolContext::new(&mut self.grid, &mut self.history, &mut self.selection, terrains, button, modifiers, pointer);
+                    if cell != last {
+                        tool.pointer_drag(&mut ctx, cell);
+                    }
+                    if moved {
+                        tool.pointer_move(&mut ctx);
+                    }
+                    ctx.changed()
+                }
+                (None, Some((button, last))) => {
+                    self.stroke = None;
+                    let mut ctx = ToolContext::new(&mut self.grid, &mut self.history, &mut self.selection, terrains, button, modifiers, pointer);
+                    tool.pointer_up(&mut ctx, last);
+                    let changed = ctx.changed();
+                    //The stroke is over, everything it changed becomes one undo step
+                    self.history.end();
+                    changed
+                }
+                _ => false,
+            };
+            if changed {
+                self.mesh_dirty = true;
+                response.mark_changed();
+            }
+        }
+        if let Some(hover_pos) = response.hover_pos() {
+            let (scroll, pinch) = ui.ctx().input(|input| (input.smooth_scroll_delta.y, input.zoom_delta()));
+            let factor = pinch * (scroll * ZOOM_SPEED).exp();
+            if factor != 1.0 {
+                self.camera.zoom_around(&viewport, hover_pos, factor);
+            }
+        }
+
+        if self.mesh_dirty {
+            self.mesh_dirty = false;
             let mesh = self.grid.build_mesh();
             let renderer_handle = self.renderer.clone();
             let update_mesh_fn = move |_info, painter: &Painter| {
@@ -99,17 +1265,13 @@ impl Editor {
                 callback: Arc::new(update_mesh_fn)
             };
             painter.add(update_mesh_fn);
-
-            response.mark_changed();
-        } /*else*/ {
-            self.renderer.lock().rotate(response.drag_delta() * 0.01);
-            response.mark_changed();
         }
 
 
+        let view_projection = self.camera.view_projection(&viewport);
         let renderer_handle = self.renderer.clone();
         let draw_contents_fn = move |_info, painter: &Painter| {
-            unsafe {renderer_handle.lock().draw(painter.gl());}
+            unsafe {renderer_handle.lock().draw(painter.gl(), &view_projection);}
         };
         let draw_contents_fn = egui_glow::CallbackFn::new(draw_contents_fn);
         let draw_contents_cb = PaintCallback{
@@ -117,28 +1279,73 @@ impl Editor {
             callback: Arc::new(draw_contents_fn)
         };
         painter.add(draw_contents_cb);
-        response
-    }
-}
 
+        if let Some(dialog) = &self.image_import {
+            let Point { x, y } = dialog.options.origin;
+            let scale = dialog.options.scale;
+            let (width, height) = (dialog.image.width() as f64 * scale, dialog.image.height() as f64 * scale);
+            let rect = Rect::from_two_pos(
+                self.camera.world_to_screen(&viewport, [x as f32, y as f32].into()),
+                self.camera.world_to_screen(&viewport, [(x + width) as f32, (y + height) as f32].into()),
+            );
+            let uv = Rect::from_min_max([0.0, 0.0].into(), [1.0, 1.0].into());
+            painter.image(dialog.texture.id(), rect, uv, Color32::from_white_alpha(160));
+        }
 
-fn canvas_to_ui(ui_rect: &Rect) -> RectTransform {
-    RectTransform::from_to(
-        canvas_rect(ui_rect),
-        *ui_rect,
-    )
+        let overlay = Overlay::new(&painter, &self.camera, viewport, &self.grid);
+        self.toolbox.active().draw_overlay(&overlay);
+        for hex in self.selection.cells() {
+            overlay.fill_cell(hex, Color32::from_rgba_premultiplied(40, 70, 120, 90));
+            overlay.outline_cell(hex, (1.0, Color32::LIGHT_BLUE));
+        }
+        let size = self.grid.layout().size;
+        let cell_size = self.camera.scale(&viewport) * size.x.min(size.y) as f32;
+        if let Some(system) = self.coordinate_labels.filter(|_| cell_size >= MIN_LABEL_CELL_SIZE) {
+            //Every position in view, painted or not
+            let min = self.camera.screen_to_world(&viewport, viewport.min).into();
+            let max = self.camera.screen_to_world(&viewport, viewport.max).into();
+            let orientation = self.grid.orientation();
+            for hex in self.grid.positions_near_rect(min, max) {
+                overlay.caption(hex, system.format(hex, orientation));
+            }
+        }
+        response
+    }
 }
 
-fn _ui_to_canvas(ui_rect: &Rect) -> RectTransform {
-    RectTransform::from_to(
-        *ui_rect,
-        canvas_rect(ui_rect),
-    )
+/// A seed from the clock, small enough to read and type back.
+fn random_seed() -> u64 {
+    let time = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |time| time.as_nanos() as u64);
+    Rng::new(time).next_u64() >> 32
 }
 
-fn canvas_rect(ui_rect: &Rect) -> Rect {
-    Rect::from_center_size(
-        Pos2::ZERO, 
-        ui_rect.square_proportions() * 2_f32
-    )
+fn export_options_ui(ui: &mut Ui, options: &mut ImageOptions, orientation: HexOrientation) {
+    egui::Grid::new("export_options").num_columns(2).show(ui, |ui| {
+        ui.label("Cell size:");
+        ui.add(DragValue::new(&mut options.cell_size).range(4.0..=256.0).suffix(" px"));
+        ui.end_row();
+        ui.label("Margin:");
+        ui.add(DragValue::new(&mut options.margin).range(0..=512).suffix(" px"));
+        ui.end_row();
+        ui.label("Background:");
+        ui.color_edit_button_srgba(&mut options.background);
+        ui.end_row();
+        ui.label("Outlines:");
+        ui.horizontal(|ui| {
+            ui.add(DragValue::new(&mut options.outline_width).range(0.0..=16.0).speed(0.1).suffix(" px"));
+            ui.color_edit_button_srgba(&mut options.outline_color);
+        });
+        ui.end_row();
+        ui.label("Labels:");
+        let selected = options.labels.map_or("None", |system| system.name(orientation));
+        ComboBox::from_id_source("export_labels").selected_text(selected).show_ui(ui, |ui| {
+            ui.selectable_value(&mut options.labels, None, "None");
+            for system in CoordSystem::ALL {
+                ui.selectable_value(&mut options.labels, Some(system), system.name(orientation));
+            }
+        });
+        ui.end_row();
+    });
 }
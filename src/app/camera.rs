@@ -0,0 +1,87 @@
+use emath::{Pos2, Rect, Vec2};
+
+use super::grid::Point;
+
+#[cfg(test)]
+mod tests;
+
+const MIN_ZOOM: f32 = 0.01;
+const MAX_ZOOM: f32 = 1000.0;
+/// Fraction of the viewport left empty around the map by [`Camera::fit`].
+const FIT_MARGIN: f32 = 0.9;
+
+/// 2D view over the infinite hex plane.
+///
+/// At zoom 1 the shorter side of the viewport spans two world units, centered on `center`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Camera {
+    pub center: Pos2,
+    pub zoom: f32,
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Self {
+            center: Pos2::ZERO,
+            zoom: 1.0,
+        }
+    }
+}
+
+impl Camera {
+    /// Screen points per world unit inside `viewport`.
+    pub fn scale(&self, viewport: &Rect) -> f32 {
+        self.zoom * viewport.width().min(viewport.height()) / 2.0
+    }
+
+    pub fn world_to_screen(&self, viewport: &Rect, world: Pos2) -> Pos2 {
+        viewport.center() + (world - self.center) * self.scale(viewport)
+    }
+
+    pub fn screen_to_world(&self, viewport: &Rect, screen: Pos2) -> Pos2 {
+        self.center + (screen - viewport.center()) / self.scale(viewport)
+    }
+
+    /// Moves the view so the content follows a pointer dragged by `screen_delta`.
+    pub fn pan(&mut self, viewport: &Rect, screen_delta: Vec2) {
+        self.center -= screen_delta / self.scale(viewport);
+    }
+
+    /// Scales the view by `factor`, keeping the world point under `screen_anchor` in place.
+    pub fn zoom_around(&mut self, viewport: &Rect, screen_anchor: Pos2, factor: f32) {
+        let anchor = self.screen_to_world(viewport, screen_anchor);
+        self.zoom = (self.zoom * factor).clamp(MIN_ZOOM, MAX_ZOOM);
+        self.center = anchor - (screen_anchor - viewport.center()) / self.scale(viewport);
+    }
+
+    /// Frames the world rectangle spanned by `min` and `max`.
+    pub fn fit(&mut self, viewport: &Rect, min: Point, max: Point) {
+        let (min, max) = (to_pos(min), to_pos(max));
+        let size = (max - min).max(Vec2::splat(f32::EPSILON));
+        let unit_scale = viewport.width().min(viewport.height()) / 2.0;
+        let zoom = (viewport.width() / size.x).min(viewport.height() / size.y) / unit_scale;
+        self.center = min + (max - min) / 2.0;
+        self.zoom = (zoom * FIT_MARGIN).clamp(MIN_ZOOM, MAX_ZOOM);
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Column-major transform from world coordinates to clip space, for `u_view_projection`.
+    pub fn view_projection(&self, viewport: &Rect) -> [f32; 9] {
+        let scale = self.scale(viewport);
+        let sx = 2.0 * scale / viewport.width();
+        //World y grows downwards like screen y, clip space y grows upwards
+        let sy = -2.0 * scale / viewport.height();
+        [
+            sx, 0.0, 0.0,
+            0.0, sy, 0.0,
+            -self.center.x * sx, -self.center.y * sy, 1.0,
+        ]
+    }
+}
+
+fn to_pos(point: Point) -> Pos2 {
+    Pos2::new(point.x as f32, point.y as f32)
+}
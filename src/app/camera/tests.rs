@@ -0,0 +1,59 @@
+use emath::{Pos2, Rect};
+
+use super::Camera;
+use crate::app::grid::Point;
+
+fn viewport() -> Rect {
+    Rect::from_min_max(Pos2::new(10.0, 20.0), Pos2::new(810.0, 620.0))
+}
+
+fn assert_close(expected: Pos2, actual: Pos2) {
+    assert!((expected - actual).length() < 1e-3, "{expected:?} != {actual:?}");
+}
+
+#[test]
+fn test_screen_world_roundtrip() {
+    let viewport = viewport();
+    let camera = Camera { center: Pos2::new(3.5, -1.25), zoom: 4.0 };
+    for world in [Pos2::ZERO, Pos2::new(3.5, -1.25), Pos2::new(-7.0, 12.5)] {
+        assert_close(world, camera.screen_to_world(&viewport, camera.world_to_screen(&viewport, world)));
+    }
+    //The center of the view is the center of the viewport
+    assert_close(viewport.center(), camera.world_to_screen(&viewport, camera.center));
+}
+
+#[test]
+fn test_zoom_around_keeps_anchor() {
+    let viewport = viewport();
+    let mut camera = Camera { center: Pos2::new(1.0, 2.0), zoom: 2.0 };
+    let anchor = Pos2::new(700.0, 100.0);
+    let world = camera.screen_to_world(&viewport, anchor);
+    for factor in [1.5, 0.25, 3.0] {
+        camera.zoom_around(&viewport, anchor, factor);
+        assert_close(world, camera.screen_to_world(&viewport, anchor));
+    }
+    assert!((camera.zoom - 2.25).abs() < 1e-4, "{}", camera.zoom);
+
+    //Clamped zooms still keep the anchor
+    camera.zoom_around(&viewport, anchor, 1e9);
+    assert_close(world, camera.screen_to_world(&viewport, anchor));
+}
+
+#[test]
+fn test_fit_contains_bounds() {
+    let viewport = viewport();
+    let mut camera = Camera::default();
+    for (min, max) in [
+        (Point { x: -3.0, y: -1.0 }, Point { x: 5.0, y: 2.0 }),
+        (Point { x: 100.0, y: 100.0 }, Point { x: 101.0, y: 140.0 }),
+        (Point { x: 0.5, y: 0.5 }, Point { x: 0.5, y: 0.5 }),
+    ] {
+        camera.fit(&viewport, min, max);
+        for corner in [(min.x, min.y), (max.x, max.y), (min.x, max.y), (max.x, min.y)] {
+            let screen = camera.world_to_screen(&viewport, Pos2::new(corner.0 as f32, corner.1 as f32));
+            assert!(viewport.expand(1e-3).contains(screen), "{corner:?} is off screen at {screen:?}");
+        }
+        let middle = Pos2::new(((min.x + max.x) / 2.0) as f32, ((min.y + max.y) / 2.0) as f32);
+        assert_close(viewport.center(), camera.world_to_screen(&viewport, middle));
+    }
+}
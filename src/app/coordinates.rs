@@ -0,0 +1,128 @@
+//! Coordinate systems shown in the status bar, drawn as cell labels and
+//! accepted by the go to dialog.
+//!
+//! Offset and doubled coordinates follow the map orientation: pointy maps shift
+//! or double along rows, flat maps along columns.
+
+use std::fmt;
+
+use super::grid::{DoubledCoord, Hex, HexDoubled, HexOrientation, OffsetCoord, Parity};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CoordSystem {
+    Cube,
+    Axial,
+    /// Rows or columns shifted every other line, odd or even ones.
+    Offset { odd: bool },
+    Doubled,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum CoordError {
+    /// Wrong amount of numbers for the coordinate system.
+    Count { expected: usize, found: usize },
+    NotANumber(String),
+    /// Numbers that name no cell, like cube coordinates not summing to zero.
+    NotOnGrid,
+    /// A cell too far away for the map to hold.
+    OutOfRange,
+}
+
+impl fmt::Display for CoordError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Count { expected, found } => write!(f, "expected {expected} numbers, found {found}"),
+            Self::NotANumber(text) => write!(f, "\"{text}\" is not a whole number"),
+            Self::NotOnGrid => write!(f, "these coordinates name no cell"),
+            Self::OutOfRange => write!(f, "these coordinates are too far out"),
+        }
+    }
+}
+
+impl CoordSystem {
+    pub const ALL: [CoordSystem; 5] = [
+        CoordSystem::Cube,
+        CoordSystem::Axial,
+        CoordSystem::Offset { odd: true },
+        CoordSystem::Offset { odd: false },
+        CoordSystem::Doubled,
+    ];
+
+    pub fn name(self, orientation: HexOrientation) -> &'static str {
+        match (self, orientation) {
+            (CoordSystem::Cube, _) => "Cube",
+            (CoordSystem::Axial, _) => "Axial",
+            (CoordSystem::Offset { odd: true }, HexOrientation::Pointy) => "Offset odd-r",
+            (CoordSystem::Offset { odd: false }, HexOrientation::Pointy) => "Offset even-r",
+            (CoordSystem::Offset { odd: true }, HexOrientation::Flat) => "Offset odd-q",
+            (CoordSystem::Offset { odd: false }, HexOrientation::Flat) => "Offset even-q",
+            (CoordSystem::Doubled, HexOrientation::Pointy) => "Doubled width",
+            (CoordSystem::Doubled, HexOrientation::Flat) => "Doubled height",
+        }
+    }
+
+    pub fn format(self, hex: Hex, orientation: HexOrientation) -> String {
+        match self {
+            CoordSystem::Cube => format!("{}, {}, {}", hex.q(), hex.r(), hex.s()),
+            CoordSystem::Axial => format!("{}, {}", hex.q(), hex.r()),
+            CoordSystem::Offset { odd } => {
+                let parity = if odd { Parity::Odd } else { Parity::Even };
+                let OffsetCoord { col, row } = match orientation {
+                    HexOrientation::Pointy => parity.r_from_cube(hex),
+                    HexOrientation::Flat => parity.q_from_cube(hex),
+                };
+                format!("{col}, {row}")
+            }
+            CoordSystem::Doubled => {
+                let DoubledCoord { col, row } = match orientation {
+                    HexOrientation::Pointy => HexDoubled::r_from_cube(hex),
+                    HexOrientation::Flat => HexDoubled::q_from_cube(hex),
+                };
+                format!("{col}, {row}")
+            }
+        }
+    }
+
+    /// Reads numbers separated by commas or spaces, optionally in parentheses.
+    pub fn parse(self, text: &str, orientation: HexOrientation) -> Result<Hex, CoordError> {
+        let numbers = text.trim()
+            .trim_start_matches('(')
+            .trim_end_matches(')')
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|part| !part.is_empty())
+            .map(|part| part.parse::<i32>().map_err(|_| CoordError::NotANumber(part.to_owned())))
+            .collect::<Result<Vec<_>, _>>()?;
+        let expected = if self == CoordSystem::Cube { 3 } else { 2 };
+        if numbers.len() != expected {
+            return Err(CoordError::Count { expected, found: numbers.len() });
+        }
+        let (a, b) = (numbers[0], numbers[1]);
+        match self {
+            CoordSystem::Cube if a.checked_add(b).and_then(|sum| sum.checked_add(numbers[2])) == Some(0) => Ok(Hex::new(a, b)),
+            CoordSystem::Cube => Err(CoordError::NotOnGrid),
+            CoordSystem::Axial => Hex::checked_new(a, b).ok_or(CoordError::OutOfRange),
+            CoordSystem::Offset { odd } => {
+                let parity = if odd { Parity::Odd } else { Parity::Even };
+                let coord = OffsetCoord { col: a, row: b };
+                match orientation {
+                    HexOrientation::Pointy => parity.r_to_cube_checked(coord),
+                    HexOrientation::Flat => parity.q_to_cube_checked(coord),
+                }
+                .ok_or(CoordError::OutOfRange)
+            }
+            //Half the positions of a doubled grid fall between cells
+            CoordSystem::Doubled if (a ^ b) & 1 != 0 => Err(CoordError::NotOnGrid),
+            //The conversions of HexDoubled, checked for overflow
+            CoordSystem::Doubled => {
+                match orientation {
+                    HexOrientation::Pointy => a.checked_sub(b).and_then(|q| Hex::checked_new(q / 2, b)),
+                    HexOrientation::Flat => b.checked_sub(a).and_then(|r| Hex::checked_new(a, r / 2)),
+                }
+                .ok_or(CoordError::OutOfRange)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests;
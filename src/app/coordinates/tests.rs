@@ -0,0 +1,55 @@
+use super::{CoordError, CoordSystem};
+use crate::app::grid::{Hex, HexIterators, HexOrientation};
+
+#[test]
+fn test_format_parse_roundtrip() {
+    for orientation in [HexOrientation::Pointy, HexOrientation::Flat] {
+        for system in CoordSystem::ALL {
+            for hex in Hex::new(0, 0).range(4) {
+                let text = system.format(hex, orientation);
+                assert_eq!(Ok(hex), system.parse(&text, orientation), "{} {text}", system.name(orientation));
+            }
+        }
+    }
+}
+
+#[test]
+fn test_format() {
+    let hex = Hex::new(1, -3);
+    let pointy = HexOrientation::Pointy;
+    assert_eq!("1, -3, 2", CoordSystem::Cube.format(hex, pointy));
+    assert_eq!("1, -3", CoordSystem::Axial.format(hex, pointy));
+    assert_eq!("-1, -3", CoordSystem::Offset { odd: true }.format(hex, pointy));
+    assert_eq!("-1, -3", CoordSystem::Doubled.format(hex, pointy));
+    assert_eq!("1, -5", CoordSystem::Doubled.format(hex, HexOrientation::Flat));
+}
+
+#[test]
+fn test_parse_errors() {
+    let pointy = HexOrientation::Pointy;
+    assert_eq!(Ok(Hex::new(2, -1)), CoordSystem::Axial.parse(" (2 -1) ", pointy));
+    assert_eq!(Err(CoordError::Count { expected: 3, found: 2 }), CoordSystem::Cube.parse("1, 2", pointy));
+    assert_eq!(Err(CoordError::NotOnGrid), CoordSystem::Cube.parse("1, 2, 3", pointy));
+    assert_eq!(Err(CoordError::NotANumber("x".into())), CoordSystem::Axial.parse("1, x", pointy));
+    assert_eq!(Err(CoordError::NotOnGrid), CoordSystem::Doubled.parse("1, 2", pointy));
+}
+
+#[test]
+fn test_parse_out_of_range() {
+    let min = i32::MIN.to_string();
+    let max = i32::MAX.to_string();
+    for orientation in [HexOrientation::Pointy, HexOrientation::Flat] {
+        assert_eq!(Err(CoordError::OutOfRange), CoordSystem::Axial.parse(&format!("{min}, 0"), orientation));
+        for odd in [true, false] {
+            let offset = CoordSystem::Offset { odd };
+            assert_eq!(Err(CoordError::OutOfRange), offset.parse(&format!("{min}, {max}"), orientation));
+            assert_eq!(Err(CoordError::OutOfRange), offset.parse(&format!("{max}, {min}"), orientation));
+        }
+        //Both odd, so on the grid
+        let odd_min = (i32::MIN + 1).to_string();
+        assert_eq!(Err(CoordError::OutOfRange), CoordSystem::Doubled.parse(&format!("{max}, {odd_min}"), orientation));
+        assert_eq!(Err(CoordError::OutOfRange), CoordSystem::Doubled.parse(&format!("{odd_min}, {max}"), orientation));
+    }
+    //The far corners that do fit still parse
+    assert_eq!(Ok(Hex::new(i32::MAX, -i32::MAX)), CoordSystem::Axial.parse(&format!("{max}, -{max}"), HexOrientation::Pointy));
+}
@@ -1,54 +1,74 @@
 mod hex_utils; pub use hex_utils::*;
+mod layer; pub use layer::*;
+mod flood_fill; pub use flood_fill::*;
+pub mod shapes;
+mod pathfinding; pub use pathfinding::*;
+mod visibility; pub use visibility::*;
+mod selection; pub use selection::*;
+mod clipboard; pub use clipboard::CLIPBOARD_TAG;
+mod transform; pub use transform::*;
+mod orientation; pub use orientation::*;
+pub mod map_file;
+pub mod tiled;
+mod export; pub use export::*;
+mod raster; pub use raster::*;
+mod random; pub use random::*;
+mod generator; pub use generator::*;
+mod synthesis; pub use synthesis::*;
+mod terrain; pub use terrain::*;
+#[cfg(test)]
+mod tests;
 
-use {
-    egui::Color32, std::{
-        collections::HashMap, iter::Map, vec::IntoIter
-    }
+use std::{
+    iter::Map, vec::IntoIter
 };
 
+/// A single mesh vertex, laid out the way `Renderer` uploads it to the GPU.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct Vertex {
+    pub position: [f32; 2],
+    /// Premultiplied sRGBA, as stored in `Color32`.
+    pub color: [u8; 4],
+}
+
+/// Width of painted edges, relative to the size of a cell.
+const EDGE_WIDTH: f64 = 0.2;
+/// Radius of painted corner markers, relative to the size of a cell.
+const CORNER_RADIUS: f64 = 0.25;
+
+/// Layer names new maps start with, bottom to top.
+const DEFAULT_LAYERS: [&str; 4] = ["Terrain", "Features", "Units", "Annotations"];
+
 pub struct Grid {
     layout: Layout,
-    data: HashMap<Hex, Color32>,
+    /// Bottom to top, never empty.
+    layers: Vec<Layer>,
+    /// Index into `layers` of the layer cell accessors and tools work on.
+    active_layer: usize,
+    next_layer_id: u32,
+    terrains: TerrainRegistry,
     //rotation: [f32; 2],
 }
 
 impl Grid {
 
-    pub fn _make_rhombus(min: impl Into<Hex>, max: impl Into<Hex>) -> Self {
-        let (min, max): (Hex, Hex) = (min.into(), max.into());
-        let mut instance = Self::default();
-        for q in min.q() ..= max.q() {
-            for r in min.r() ..= max.r() {
-                let key = Hex::new(q, r);
-                instance.data.insert(key, Color32::default());
-            }
-        }
-        instance
+    pub fn make_rhombus(min: impl Into<Hex>, max: impl Into<Hex>) -> Self {
+        Self::from_cells(shapes::rhombus(min.into(), max.into()))
     }
 
-    pub fn _make_triangle(min: impl Into<Hex>, size: i32) -> Self {
-        let min: Hex = min.into();
-        let mut instance = Self::default();
-        for q in min.q() ..=  min.q() + size {
-            for r in min.r() ..= min.r() + size - q {
-                let key = Hex::new(q, r);
-                instance.data.insert(key, Color32::default());
-            }
-        }
-        instance
+    pub fn make_triangle(min: impl Into<Hex>, size: i32) -> Self {
+        Self::from_cells(shapes::triangle(min.into(), size))
     }
 
     pub fn make_hex(center: impl Into<Hex>, size: i32) -> Self {
-        let center: Hex = center.into();
+        Self::from_cells(center.into().range(size))
+    }
+
+    fn from_cells(cells: impl IntoIterator<Item = Hex>) -> Self {
         let mut instance = Self::default();
-        for q in -size ..= size {
-            for r in -size ..= size {
-                let s = -q-r;
-                if (-size <= s) && (s <= size) {
-                    let key = center.add(Hex::new(q, r));
-                    instance.data.insert(key, Color32::default());
-                }
-            }
+        for key in cells {
+            instance.paint_cell(key, TerrainId::default());
         }
         instance
     }
@@ -59,8 +79,139 @@ impl Grid {
         self.rotation[1] += amount[1];
     }*/
 
-    pub fn paint_cell(&mut self, cell: impl Into<Hex>, color: impl Into<Color32>) {
-        self.data.insert(cell.into(), color.into());
+    /// Paints `cell` on the active layer, returning its previous value.
+    pub fn paint_cell(&mut self, cell: impl Into<Hex>, terrain: TerrainId) -> Option<TerrainId> {
+        self.set_cell(cell, Some(terrain))
+    }
+
+    /// Value of `cell` on the active layer.
+    pub fn cell(&self, cell: impl Into<Hex>) -> Option<TerrainId> {
+        self.active_layer().cell(cell)
+    }
+
+    /// Paints or erases `cell` on the active layer, returning its previous value.
+    pub fn set_cell(&mut self, cell: impl Into<Hex>, value: Option<TerrainId>) -> Option<TerrainId> {
+        self.layers[self.active_layer].set_cell(cell, value)
+    }
+
+    /// Paints or erases a cell, edge or corner of any layer, returning its previous
+    /// value. Does nothing if the layer no longer exists.
+    pub fn set_layer_site(&mut self, layer: LayerId, site: impl Into<Site>, value: Option<TerrainId>) -> Option<TerrainId> {
+        self.layer_mut(layer)?.set_site(site, value)
+    }
+
+    /// Every layer, bottom to top.
+    pub fn layers(&self) -> &[Layer] {
+        &self.layers
+    }
+
+    pub fn layer(&self, id: LayerId) -> Option<&Layer> {
+        self.layers.iter().find(|layer| layer.id() == id)
+    }
+
+    pub fn layer_mut(&mut self, id: LayerId) -> Option<&mut Layer> {
+        self.layers.iter_mut().find(|layer| layer.id() == id)
+    }
+
+    fn layer_index(&self, id: LayerId) -> Option<usize> {
+        self.layers.iter().position(|layer| layer.id() == id)
+    }
+
+    pub fn active_layer(&self) -> &Layer {
+        &self.layers[self.active_layer]
+    }
+
+    pub fn set_active_layer(&mut self, id: LayerId) {
+        if let Some(index) = self.layer_index(id) {
+            self.active_layer = index;
+        }
+    }
+
+    /// Creates an empty layer above the active one and activates it.
+    pub fn add_layer(&mut self, name: impl Into<String>) -> LayerId {
+        let id = LayerId(self.next_layer_id);
+        self.next_layer_id += 1;
+        self.active_layer += 1;
+        self.layers.insert(self.active_layer, Layer::new(id, name));
+        id
+    }
+
+    /// Deletes a layer and its cells. The last remaining layer cannot be removed.
+    pub fn remove_layer(&mut self, id: LayerId) -> Option<Layer> {
+        if self.layers.len() == 1 {
+            return None;
+        }
+        let index = self.layer_index(id)?;
+        let layer = self.layers.remove(index);
+        if self.active_layer > index || self.active_layer == self.layers.len() {
+            self.active_layer -= 1;
+        }
+        Some(layer)
+    }
+
+    /// Puts a layer taken out by [`Grid::remove_layer`] back at `index` and activates it.
+    pub fn restore_layer(&mut self, index: usize, layer: Layer) {
+        let index = index.min(self.layers.len());
+        self.layers.insert(index, layer);
+        self.active_layer = index;
+    }
+
+    /// Moves a layer `offset` places up (positive) or down (negative) the stack.
+    pub fn move_layer(&mut self, id: LayerId, offset: isize) {
+        let Some(index) = self.layer_index(id) else {
+            return;
+        };
+        let target = index.saturating_add_signed(offset).min(self.layers.len() - 1);
+        let active = self.active_layer().id();
+        let layer = self.layers.remove(index);
+        self.layers.insert(target, layer);
+        self.set_active_layer(active);
+    }
+
+    pub fn terrains(&self) -> &TerrainRegistry {
+        &self.terrains
+    }
+
+    pub fn terrains_mut(&mut self) -> &mut TerrainRegistry {
+        &mut self.terrains
+    }
+
+    /// Whether any cell, edge or corner is painted with `terrain`.
+    pub fn uses_terrain(&self, terrain: TerrainId) -> bool {
+        self.layers.iter().any(|layer| {
+            layer.data.values().chain(layer.edges.values()).chain(layer.corners.values()).any(|id| *id == terrain)
+        })
+    }
+
+    /// Cells stored on the active layer.
+    pub fn cells(&self) -> impl Iterator<Item = Hex> + '_ {
+        self.active_layer().data.keys().copied()
+    }
+
+    /// Top-left and bottom-right corners of the area covered by the cells, edges
+    /// and corners of every layer.
+    pub fn bounds(&self) -> Option<(Point, Point)> {
+        let cells = self.layers.iter()
+            .flat_map(|layer| layer.data.keys())
+            .flat_map(|hex| LayoutTool::polygon_corners(self.layout, *hex));
+        let edges = self.layers.iter()
+            .flat_map(|layer| layer.edges.keys())
+            .flat_map(|edge| edge.to_pixels(self.layout));
+        let corners = self.layers.iter()
+            .flat_map(|layer| layer.corners.keys())
+            .map(|corner| corner.to_pixel(self.layout));
+        cells.chain(edges).chain(corners)
+            .fold(None, |bounds, Point{x, y}| match bounds {
+                None => Some((Point{x, y}, Point{x, y})),
+                Some((min, max)) => Some((
+                    Point{ x: x.min(min.x), y: y.min(min.y) },
+                    Point{ x: x.max(max.x), y: y.max(max.y) },
+                )),
+            })
+    }
+
+    pub fn cell_center(&self, cell: Hex) -> Point {
+        LayoutTool::hex_to_pixel(self.layout, cell)
     }
 
     pub fn sample_cell(&self, pos: impl Into<Point>) -> Hex {
@@ -69,31 +220,60 @@ impl Grid {
         fractional_coord.round()
     }
 
-    pub fn build_mesh(&self) -> Vec<[f32;2]> {
-        let cells = self.data.iter().map(|(hex, color)|{
-
-            let mut points = LayoutTool::polygon_corners(self.layout, *hex);
-            points.push(points[0]);
-            let midpoint = points.iter().fold(Point{ x: 0.0, y: 0.0}, |acc, elem| { acc + *elem }) / points.len() as f64;
-            points.insert(0, midpoint);
-            let points: Vec<_> = points
-                .iter()
-                .map(|Point{x, y}| {[*x as f32, *y as f32]})
-                .collect();
-            points
-        });
-        let mesh = cells.reduce(|mut acc, mut item| {
-            acc.append(&mut item);
-            acc
-        });
-        if let Some(result) = mesh {
-            return result;
-        } else {
-            return Vec::new();
+    /// Triangle fans of the visible layers, bottom layer first so the renderer
+    /// draws them in stacking order. Within a layer edges are drawn over cells as
+    /// thick segments and corners over both as small hexagons. Layer opacity is
+    /// baked into the vertex colors.
+    pub fn build_mesh(&self) -> Vec<Vertex> {
+        let mut mesh = Vec::new();
+        for layer in self.layers.iter().filter(|layer| layer.visible) {
+            let color = |terrain: TerrainId| self.terrains.color(terrain).gamma_multiply(layer.opacity).to_array();
+            for (hex, terrain) in &layer.data {
+                push_fan(&mut mesh, LayoutTool::polygon_corners(self.layout, *hex), color(*terrain));
+            }
+            for (edge, terrain) in &layer.edges {
+                let [a, b] = edge.to_pixels(self.layout);
+                let length = ((b.x - a.x).powi(2) + (b.y - a.y).powi(2)).sqrt();
+                //Along and across the edge, half its width long
+                let half = self.edge_width() / 2.0;
+                let along = Point { x: (b.x - a.x) / length * half, y: (b.y - a.y) / length * half };
+                let across = Point { x: -along.y, y: along.x };
+                let outline = vec![
+                    a,
+                    a + along + across,
+                    b + Point { x: across.x - along.x, y: across.y - along.y },
+                    b,
+                    b + Point { x: -along.x - across.x, y: -along.y - across.y },
+                    a + Point { x: along.x - across.x, y: along.y - across.y },
+                ];
+                push_fan(&mut mesh, outline, color(*terrain));
+            }
+            for (corner, terrain) in &layer.corners {
+                push_fan(&mut mesh, self.corner_marker(*corner), color(*terrain));
+            }
         }
+        mesh
+    }
+
+    /// Width of painted edges, in layout units.
+    fn edge_width(&self) -> f64 {
+        self.layout.size.x.min(self.layout.size.y) * EDGE_WIDTH
+    }
+
+    /// The small hexagon painted corners are drawn as.
+    fn corner_marker(&self, corner: HexCorner) -> Vec<Point> {
+        let center = corner.to_pixel(self.layout);
+        let radius = self.layout.size.x.min(self.layout.size.y) * CORNER_RADIUS;
+        (0..6)
+            .map(|i| {
+                let angle = i as f64 * std::f64::consts::PI / 3.0;
+                center + Point { x: radius * angle.cos(), y: radius * angle.sin() }
+            })
+            .collect()
     }
 
-    fn _polygon_corners(&self, key: Hex) -> Map<IntoIter<Point>, fn(Point)->[f32; 2]>{
+    #[allow(clippy::type_complexity)]
+    pub fn polygon_corners(&self, key: Hex) -> Map<IntoIter<Point>, fn(Point)->[f32; 2]>{
 
         let convert_point: fn(Point) -> [f32; 2] = |point: Point| {
             [point.x as f32, point.y as f32]
@@ -102,6 +282,14 @@ impl Grid {
     }
 }
 
+/// Appends a fan over the six points of `outline`: its middle, the points and the
+/// first one again, the 8 vertices the renderer expects of every shape.
+fn push_fan(mesh: &mut Vec<Vertex>, mut outline: Vec<Point>, color: [u8; 4]) {
+    outline.push(outline[0]);
+    let midpoint = outline.iter().fold(Point{ x: 0.0, y: 0.0}, |acc, elem| { acc + *elem }) / outline.len() as f64;
+    outline.insert(0, midpoint);
+    mesh.extend(outline.iter().map(|Point{x, y}| Vertex { position: [*x as f32, *y as f32], color }));
+}
 
 impl Default for Grid {
     fn default() -> Self {
@@ -110,10 +298,16 @@ impl Default for Grid {
             size: Point { x:0.1, y:0.1 }, 
             origin: Point { x: 0.0, y: 0.0 },
         };
-        let data = HashMap::new();
+        let layers = DEFAULT_LAYERS.iter()
+            .zip(0..)
+            .map(|(name, id)| Layer::new(LayerId(id), *name))
+            .collect();
         Self {
             layout,
-            data,
+            layers,
+            active_layer: 0,
+            next_layer_id: DEFAULT_LAYERS.len() as u32,
+            terrains: TerrainRegistry::builtin(),
         }
     }
 }
@@ -0,0 +1,150 @@
+//! Text form of copied cells, so they can travel through the system clipboard
+//! between editor windows:
+//!
+//! ```json
+//! {
+//!   "format": "hex-editor-cells",
+//!   "version": 2,
+//!   "origin": [3, -2],
+//!   "terrains": [ { "id": 0, "name": "Grass", "color": "#19c864ff", "movement_cost": 1, "passable": true } ],
+//!   "cells": [ { "q": 0, "r": 0, "terrain": 0 }, { "q": 1, "r": 0, "terrain": 0 } ],
+//!   "edges": [ { "q": 0, "r": 0, "side": "east", "terrain": 0 } ],
+//!   "corners": [ { "q": 1, "r": 0, "corner": "north", "terrain": 0 } ]
+//! }
+//! ```
+//!
+//! `cells`, `edges` and `corners` are relative to `origin`, the position they were
+//! copied from. Edges and corners are named as in map files and may be left out.
+//! `terrains` lists the terrains they use, with the same fields as in map files.
+//! Version 1 had no edges or corners.
+
+use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
+
+use super::{
+    map_file::{checked_cell, CellRecord, CornerRecord, EdgeRecord, MapFileError, TerrainRecord},
+    Grid, Hex, HexCorner, HexEdge, HexMath, Site, TerrainId
+};
+
+pub const CLIPBOARD_TAG: &str = "hex-editor-cells";
+const CLIPBOARD_VERSION: u32 = 2;
+
+#[derive(Serialize, Deserialize)]
+struct Clipping {
+    format: String,
+    version: u32,
+    origin: [i32; 2],
+    terrains: Vec<TerrainRecord>,
+    cells: Vec<CellRecord>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    edges: Vec<EdgeRecord>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    corners: Vec<CornerRecord>,
+}
+
+impl Grid {
+    /// Serializes the stored cells of the active layer among `cells`, with the
+    /// edges and corners lying wholly between them. Returns `None` when none of
+    /// them is stored.
+    pub fn copy_cells(&self, cells: impl IntoIterator<Item = Hex>) -> Option<String> {
+        let layer = self.active_layer();
+        let cells: HashSet<Hex> = cells.into_iter().collect();
+        let mut sites: Vec<_> = layer.sites()
+            .filter(|(site, _)| site.cells().iter().all(|hex| cells.contains(hex)))
+            .map(|(site, terrain)| {
+                let (hex, order) = match site {
+                    Site::Cell(hex) => (hex, 0),
+                    Site::Edge(edge) => (edge.hex(), 1 + edge.direction().index()),
+                    Site::Corner(corner) => (corner.hex(), 4 + corner.diagonal().index()),
+                };
+                (hex, order, site, terrain)
+            })
+            .collect();
+        //Stable output, the origin being the first cell any site is written from
+        sites.sort_by_key(|(hex, order, _, _)| (hex.r(), hex.q(), *order));
+        let origin = sites.first()?.0;
+
+        let mut used: Vec<_> = sites.iter().map(|(_, _, _, terrain)| *terrain).collect();
+        used.sort();
+        used.dedup();
+        let terrains = used.into_iter()
+            .filter_map(|id| Some(TerrainRecord::new(id, self.terrains.get(id)?)))
+            .collect();
+        let mut clipping = Clipping {
+            format: CLIPBOARD_TAG.into(),
+            version: CLIPBOARD_VERSION,
+            origin: [origin.q(), origin.r()],
+            terrains,
+            cells: Vec::new(),
+            edges: Vec::new(),
+            corners: Vec::new(),
+        };
+        for (hex, _, site, TerrainId(terrain)) in sites {
+            let offset = hex.sub(origin);
+            let (q, r) = (offset.q(), offset.r());
+            match site {
+                Site::Cell(_) => clipping.cells.push(CellRecord { q, r, terrain }),
+                Site::Edge(edge) => clipping.edges.push(EdgeRecord { q, r, side: edge.direction().name().into(), terrain }),
+                Site::Corner(corner) => clipping.corners.push(CornerRecord { q, r, corner: corner.diagonal().name().into(), terrain }),
+            }
+        }
+        serde_json::to_string(&clipping).ok()
+    }
+
+    /// Parses sites copied with [`Grid::copy_cells`], possibly in another window, and
+    /// returns them placed so the copied origin lands on `at`, or where they were
+    /// copied from. Terrains are matched by name, missing ones are added to the registry.
+    pub fn paste_cells(&mut self, text: &str, at: Option<Hex>) -> Result<Vec<(Site, TerrainId)>, MapFileError> {
+        let clipping: Clipping = serde_json::from_str(text).map_err(|_| MapFileError::NotAMap)?;
+        if clipping.format != CLIPBOARD_TAG {
+            return Err(MapFileError::NotAMap);
+        }
+        if clipping.version > CLIPBOARD_VERSION {
+            return Err(MapFileError::FutureVersion { found: clipping.version, supported: CLIPBOARD_VERSION });
+        }
+
+        let mut ids = Vec::with_capacity(clipping.terrains.len());
+        for record in clipping.terrains {
+            let (id, terrain) = record.into_terrain()?;
+            let same_name = match self.terrains.get(id) {
+                Some(known) if known.name == terrain.name => Some(id),
+                _ => self.terrains.iter().find(|(_, known)| known.name == terrain.name).map(|(local, _)| local),
+            };
+            let local = match same_name {
+                Some(local) => local,
+                None => self.terrains.add(terrain).ok_or_else(|| MapFileError::Corrupt("too many terrains to paste".into()))?,
+            };
+            ids.push((id, local));
+        }
+
+        let origin = match at {
+            Some(at) => at,
+            None => checked_cell(clipping.origin[0], clipping.origin[1])?,
+        };
+        let local = |q: i32, r: i32, terrain: u16| {
+            ids.iter()
+                .find(|(id, _)| *id == TerrainId(terrain))
+                .map(|(_, local)| *local)
+                .ok_or_else(|| MapFileError::Corrupt(format!("cell ({q}, {r}) uses unknown terrain {terrain}")))
+        };
+        let place = |q: i32, r: i32| {
+            let hex = checked_cell(q, r)?.checked_add(origin)
+                .ok_or_else(|| MapFileError::Corrupt(format!("cell ({q}, {r}) lands out of range")))?;
+            checked_cell(hex.q(), hex.r())
+        };
+        let mut sites = Vec::with_capacity(clipping.cells.len() + clipping.edges.len() + clipping.corners.len());
+        for CellRecord { q, r, terrain } in clipping.cells {
+            sites.push((Site::Cell(place(q, r)?), local(q, r, terrain)?));
+        }
+        for edge in clipping.edges {
+            let (q, r) = (edge.q, edge.r);
+            sites.push((HexEdge::new(place(q, r)?, edge.direction()?).into(), local(q, r, edge.terrain)?));
+        }
+        for corner in clipping.corners {
+            let (q, r) = (corner.q, corner.r);
+            sites.push((HexCorner::new(place(q, r)?, corner.diagonal()?).into(), local(q, r, corner.terrain)?));
+        }
+        Ok(sites)
+    }
+}
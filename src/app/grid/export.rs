@@ -0,0 +1,144 @@
+//! Pictures of maps, drawn on the CPU so they can be made without a window.
+
+use std::{collections::HashMap, fmt, io};
+
+use egui::Color32;
+
+use super::{Grid, Hex, Point};
+use crate::app::coordinates::CoordSystem;
+
+mod png;
+mod svg;
+
+#[derive(Debug)]
+pub enum ExportError {
+    Io(io::Error),
+    /// There are no cells to draw.
+    Empty,
+    /// The picture would exceed [`MAX_IMAGE_SIDE`] pixels on a side.
+    TooLarge { width: u64, height: u64 },
+    /// The image encoder failed.
+    Encode(String),
+}
+
+/// Longest side, in pixels, of exported images.
+pub const MAX_IMAGE_SIDE: u64 = 16384;
+
+/// How [`Grid::render`] and [`Grid::write_svg`] draw a map.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ImageOptions {
+    /// Pixels from the center of a cell to its farthest corner.
+    pub cell_size: f32,
+    /// Empty pixels around the map.
+    pub margin: u32,
+    pub background: Color32,
+    /// Width in pixels of the lines around cells, 0 for none.
+    pub outline_width: f32,
+    pub outline_color: Color32,
+    /// Writes the coordinates of every cell on it.
+    pub labels: Option<CoordSystem>,
+}
+
+impl Default for ImageOptions {
+    fn default() -> Self {
+        Self {
+            cell_size: 32.0,
+            margin: 16,
+            background: Color32::TRANSPARENT,
+            outline_width: 0.0,
+            outline_color: Color32::from_black_alpha(160),
+            labels: None,
+        }
+    }
+}
+
+/// Placement of a map in an image.
+struct Frame {
+    min: Point,
+    scale: f64,
+    margin: f64,
+    width: u64,
+    height: u64,
+}
+
+impl Frame {
+    fn new(grid: &Grid, options: &ImageOptions) -> Result<Self, ExportError> {
+        let (min, max) = grid.bounds().ok_or(ExportError::Empty)?;
+        let scale = options.cell_size as f64 / grid.layout.size.x.max(grid.layout.size.y);
+        let margin = options.margin as f64;
+        let width = ((max.x - min.x) * scale + 2.0 * margin).ceil().max(1.0) as u64;
+        let height = ((max.y - min.y) * scale + 2.0 * margin).ceil().max(1.0) as u64;
+        Ok(Self { min, scale, margin, width, height })
+    }
+
+    fn to_pixels(&self, Point { x, y }: Point) -> (f32, f32) {
+        (
+            ((x - self.min.x) * self.scale + self.margin) as f32,
+            ((y - self.min.y) * self.scale + self.margin) as f32,
+        )
+    }
+
+    /// A length in layout units, in pixels.
+    fn to_length(&self, length: f64) -> f32 {
+        (length * self.scale) as f32
+    }
+}
+
+impl Grid {
+    /// Cells of visible layers with the color of the topmost one, row by row so
+    /// exports come out the same every time.
+    fn visible_cells(&self) -> Vec<(Hex, Color32)> {
+        let mut cells = HashMap::new();
+        for layer in self.layers.iter().filter(|layer| layer.visible) {
+            for (hex, terrain) in layer.cells() {
+                cells.insert(hex, self.terrains.color(terrain));
+            }
+        }
+        let mut cells: Vec<_> = cells.into_iter().collect();
+        cells.sort_by_key(|(hex, _)| (hex.r(), hex.q()));
+        cells
+    }
+}
+
+/// Height of the label `text`, small enough for it to fit in a cell. Monospace
+/// characters are about 0.6 of their height wide.
+fn label_size(options: &ImageOptions, text: &str) -> f32 {
+    let fit = 1.5 * options.cell_size / (0.6 * text.chars().count().max(1) as f32);
+    fit.min(options.cell_size * 0.4)
+}
+
+/// Black or white, whichever reads better over `background`.
+fn label_color(background: Color32) -> Color32 {
+    let [r, g, b, _] = background.to_srgba_unmultiplied();
+    let luma = 0.299 * r as f32 + 0.587 * g as f32 + 0.114 * b as f32;
+    if luma > 140.0 { Color32::BLACK } else { Color32::WHITE }
+}
+
+impl fmt::Display for ExportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "could not write the image: {error}"),
+            Self::Empty => write!(f, "the map has no cells to draw"),
+            Self::TooLarge { width, height } => write!(
+                f,
+                "the image would be {width}×{height} pixels, more than {MAX_IMAGE_SIDE} on a side"
+            ),
+            Self::Encode(reason) => write!(f, "could not encode the image: {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for ExportError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ExportError {
+    fn from(value: io::Error) -> Self {
+        Self::Io(value)
+    }
+}
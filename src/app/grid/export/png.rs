@@ -0,0 +1,158 @@
+use std::{fs::File, io::{BufWriter, Write}, path::Path};
+
+use ab_glyph::{point, Font, FontRef, PxScale, ScaleFont};
+use egui::{Color32, FontDefinitions};
+use image::{codecs::png::PngEncoder, ExtendedColorType, ImageEncoder, Pixel, Rgba, RgbaImage};
+use tiny_skia::{FillRule, Paint, PathBuilder, Pixmap, Stroke, Transform};
+
+use super::{label_color, label_size, ExportError, Frame, ImageOptions, MAX_IMAGE_SIDE};
+use crate::app::grid::{Grid, Hex, Layout, LayoutTool, Point};
+
+/// Font labels are written with, one of the fonts built into egui.
+const LABEL_FONT: &str = "Hack";
+
+impl Grid {
+    /// Draws the visible layers, bottom first, into an image framing every cell,
+    /// edge and corner. Within a layer edges are drawn over cells and corners over both.
+    pub fn render(&self, options: &ImageOptions) -> Result<RgbaImage, ExportError> {
+        let frame = Frame::new(self, options)?;
+        let (width, height) = (frame.width, frame.height);
+        if width > MAX_IMAGE_SIDE || height > MAX_IMAGE_SIDE {
+            return Err(ExportError::TooLarge { width, height });
+        }
+        let mut pixmap = Pixmap::new(width as u32, height as u32).ok_or(ExportError::TooLarge { width, height })?;
+        pixmap.fill(skia_color(options.background));
+
+        for layer in self.layers.iter().filter(|layer| layer.visible) {
+            for (hex, terrain) in layer.cells() {
+                let Some(path) = cell_path(&frame, self.layout, hex) else {
+                    continue;
+                };
+                let mut paint = Paint::default();
+                paint.set_color(skia_color(self.terrains.color(terrain).gamma_multiply(layer.opacity)));
+                //Antialiased neighbors would leave faint seams between them
+                paint.anti_alias = false;
+                pixmap.fill_path(&path, &paint, FillRule::Winding, Transform::identity(), None);
+            }
+            let stroke = Stroke { width: frame.to_length(self.edge_width()), ..Default::default() };
+            for (edge, terrain) in layer.edges() {
+                let [(x1, y1), (x2, y2)] = edge.to_pixels(self.layout).map(|point| frame.to_pixels(point));
+                let mut path = PathBuilder::new();
+                path.move_to(x1, y1);
+                path.line_to(x2, y2);
+                let Some(path) = path.finish() else {
+                    continue;
+                };
+                let mut paint = Paint::default();
+                paint.set_color(skia_color(self.terrains.color(terrain).gamma_multiply(layer.opacity)));
+                pixmap.stroke_path(&path, &paint, &stroke, Transform::identity(), None);
+            }
+            for (corner, terrain) in layer.corners() {
+                let Some(path) = outline_path(&frame, self.corner_marker(corner)) else {
+                    continue;
+                };
+                let mut paint = Paint::default();
+                paint.set_color(skia_color(self.terrains.color(terrain).gamma_multiply(layer.opacity)));
+                pixmap.fill_path(&path, &paint, FillRule::Winding, Transform::identity(), None);
+            }
+        }
+
+        let cells = self.visible_cells();
+        if options.outline_width > 0.0 {
+            let mut paint = Paint::default();
+            paint.set_color(skia_color(options.outline_color));
+            let stroke = Stroke { width: options.outline_width, ..Default::default() };
+            for &(hex, _) in &cells {
+                if let Some(path) = cell_path(&frame, self.layout, hex) {
+                    pixmap.stroke_path(&path, &paint, &stroke, Transform::identity(), None);
+                }
+            }
+        }
+
+        let data = pixmap.pixels().iter()
+            .flat_map(|pixel| {
+                let color = pixel.demultiply();
+                [color.red(), color.green(), color.blue(), color.alpha()]
+            })
+            .collect();
+        let mut image = RgbaImage::from_raw(pixmap.width(), pixmap.height(), data).expect("Pixmap and image sizes differ!");
+
+        if let Some(system) = options.labels {
+            let fonts = FontDefinitions::default();
+            let font = fonts.font_data.get(LABEL_FONT)
+                .and_then(|data| FontRef::try_from_slice_and_index(&data.font, data.index).ok())
+                .ok_or_else(|| ExportError::Encode(format!("the {LABEL_FONT} font is missing")))?;
+            let orientation = self.orientation();
+            for &(hex, color) in &cells {
+                let text = system.format(hex, orientation);
+                let center = frame.to_pixels(LayoutTool::hex_to_pixel(self.layout, hex));
+                draw_text(&mut image, &font, label_size(options, &text), center, &text, label_color(color));
+            }
+        }
+        Ok(image)
+    }
+
+    pub fn write_png(&self, writer: impl Write, options: &ImageOptions) -> Result<(), ExportError> {
+        let image = self.render(options)?;
+        PngEncoder::new(writer)
+            .write_image(image.as_raw(), image.width(), image.height(), ExtendedColorType::Rgba8)
+            .map_err(|error| ExportError::Encode(error.to_string()))
+    }
+
+    pub fn save_png(&self, path: impl AsRef<Path>, options: &ImageOptions) -> Result<(), ExportError> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write_png(&mut writer, options)?;
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+fn cell_path(frame: &Frame, layout: Layout, hex: Hex) -> Option<tiny_skia::Path> {
+    outline_path(frame, LayoutTool::polygon_corners(layout, hex))
+}
+
+/// A closed path through `corners`, in layout units.
+fn outline_path(frame: &Frame, corners: Vec<Point>) -> Option<tiny_skia::Path> {
+    let mut path = PathBuilder::new();
+    for (index, corner) in corners.into_iter().enumerate() {
+        let (x, y) = frame.to_pixels(corner);
+        if index == 0 {
+            path.move_to(x, y);
+        } else {
+            path.line_to(x, y);
+        }
+    }
+    path.close();
+    path.finish()
+}
+
+/// Writes one line of `text` centered on `center`.
+fn draw_text(image: &mut RgbaImage, font: &FontRef, size: f32, center: (f32, f32), text: &str, color: Color32) {
+    let font = font.as_scaled(PxScale::from(size));
+    let width: f32 = text.chars().map(|c| font.h_advance(font.glyph_id(c))).sum();
+    let baseline = center.1 + (font.ascent() + font.descent()) / 2.0;
+    let mut x = center.0 - width / 2.0;
+    let [r, g, b, a] = color.to_srgba_unmultiplied();
+    for c in text.chars() {
+        let mut glyph = font.scaled_glyph(c);
+        glyph.position = point(x, baseline);
+        x += font.h_advance(glyph.id);
+        let Some(outline) = font.outline_glyph(glyph) else {
+            continue;
+        };
+        let bounds = outline.px_bounds();
+        outline.draw(|gx, gy, coverage| {
+            let (px, py) = (bounds.min.x as i64 + gx as i64, bounds.min.y as i64 + gy as i64);
+            if px < 0 || py < 0 || px >= image.width() as i64 || py >= image.height() as i64 {
+                return;
+            }
+            let alpha = (a as f32 * coverage.clamp(0.0, 1.0)).round() as u8;
+            image.get_pixel_mut(px as u32, py as u32).blend(&Rgba([r, g, b, alpha]));
+        });
+    }
+}
+
+fn skia_color(color: Color32) -> tiny_skia::Color {
+    let [r, g, b, a] = color.to_srgba_unmultiplied();
+    tiny_skia::Color::from_rgba8(r, g, b, a)
+}
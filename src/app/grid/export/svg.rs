@@ -0,0 +1,148 @@
+use std::{fs::File, io::{BufWriter, Write}, path::Path};
+
+use egui::Color32;
+
+use super::{label_color, label_size, ExportError, Frame, ImageOptions};
+use crate::app::grid::{Grid, Hex, LayoutTool, Point};
+
+impl Grid {
+    /// Writes the visible layers as an SVG document, one group per layer with polygons
+    /// for cells, lines for edges and polygons for corners, followed by the outlines
+    /// and labels of cells. It has the size [`Grid::render`] would give.
+    pub fn write_svg(&self, mut writer: impl Write, options: &ImageOptions) -> Result<(), ExportError> {
+        let frame = Frame::new(self, options)?;
+        let (width, height) = (frame.width, frame.height);
+        writeln!(writer, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+        writeln!(writer, r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="{height}" viewBox="0 0 {width} {height}">"#)?;
+        if options.background.a() > 0 {
+            writeln!(writer, r#"  <rect width="100%" height="100%"{}/>"#, paint("fill", options.background))?;
+        }
+
+        let outline = |corners: Vec<Point>| {
+            corners.into_iter()
+                .map(|corner| {
+                    let (x, y) = frame.to_pixels(corner);
+                    format!("{},{}", number(x), number(y))
+                })
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        let points = |hex: Hex| outline(LayoutTool::polygon_corners(self.layout, hex));
+        for layer in self.layers.iter().filter(|layer| layer.visible) {
+            let mut cells: Vec<_> = layer.cells().collect();
+            let mut edges: Vec<_> = layer.edges().collect();
+            let mut corners: Vec<_> = layer.corners().collect();
+            if cells.is_empty() && edges.is_empty() && corners.is_empty() {
+                continue;
+            }
+            cells.sort_by_key(|(hex, _)| (hex.r(), hex.q()));
+            edges.sort_by_key(|(edge, _)| (edge.hex().r(), edge.hex().q(), edge.direction().index()));
+            corners.sort_by_key(|(corner, _)| (corner.hex().r(), corner.hex().q(), corner.diagonal().index()));
+            let opacity = if layer.opacity < 1.0 { format!(r#" opacity="{}""#, number(layer.opacity)) } else { String::new() };
+            //Antialiased neighbors would leave faint seams between them
+            writeln!(writer, r#"  <g data-layer="{}"{opacity} shape-rendering="crispEdges">"#, escape(&layer.name))?;
+            for (hex, terrain) in cells {
+                writeln!(writer, r#"    <polygon points="{}"{}/>"#, points(hex), paint("fill", self.terrains.color(terrain)))?;
+            }
+            if !edges.is_empty() || !corners.is_empty() {
+                //Edges and corners stand alone, so they may be smoothed
+                writeln!(
+                    writer,
+                    r#"    <g shape-rendering="geometricPrecision" stroke-width="{}">"#,
+                    number(frame.to_length(self.edge_width())),
+                )?;
+                for (edge, terrain) in edges {
+                    let [(x1, y1), (x2, y2)] = edge.to_pixels(self.layout).map(|point| frame.to_pixels(point));
+                    writeln!(
+                        writer,
+                        r#"      <line x1="{}" y1="{}" x2="{}" y2="{}"{}/>"#,
+                        number(x1),
+                        number(y1),
+                        number(x2),
+                        number(y2),
+                        paint("stroke", self.terrains.color(terrain)),
+                    )?;
+                }
+                for (corner, terrain) in corners {
+                    writeln!(
+                        writer,
+                        r#"      <polygon points="{}"{}/>"#,
+                        outline(self.corner_marker(corner)),
+                        paint("fill", self.terrains.color(terrain)),
+                    )?;
+                }
+                writeln!(writer, "    </g>")?;
+            }
+            writeln!(writer, "  </g>")?;
+        }
+
+        let cells = self.visible_cells();
+        if options.outline_width > 0.0 && !cells.is_empty() {
+            writeln!(
+                writer,
+                r#"  <g fill="none"{} stroke-width="{}" stroke-linejoin="round">"#,
+                paint("stroke", options.outline_color),
+                number(options.outline_width),
+            )?;
+            for &(hex, _) in &cells {
+                writeln!(writer, r#"    <polygon points="{}"/>"#, points(hex))?;
+            }
+            writeln!(writer, "  </g>")?;
+        }
+
+        if let Some(system) = options.labels.filter(|_| !cells.is_empty()) {
+            writeln!(writer, r#"  <g font-family="monospace" text-anchor="middle" dominant-baseline="central">"#)?;
+            let orientation = self.orientation();
+            for &(hex, color) in &cells {
+                let text = system.format(hex, orientation);
+                let (x, y) = frame.to_pixels(LayoutTool::hex_to_pixel(self.layout, hex));
+                writeln!(
+                    writer,
+                    r#"    <text x="{}" y="{}" font-size="{}"{}>{}</text>"#,
+                    number(x),
+                    number(y),
+                    number(label_size(options, &text)),
+                    paint("fill", label_color(color)),
+                    escape(&text),
+                )?;
+            }
+            writeln!(writer, "  </g>")?;
+        }
+        writeln!(writer, "</svg>")?;
+        Ok(())
+    }
+
+    pub fn save_svg(&self, path: impl AsRef<Path>, options: &ImageOptions) -> Result<(), ExportError> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write_svg(&mut writer, options)?;
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+/// A `fill` or `stroke` attribute, with an opacity when the color is translucent.
+fn paint(attribute: &str, color: Color32) -> String {
+    let [r, g, b, a] = color.to_srgba_unmultiplied();
+    let mut text = format!(r##" {attribute}="#{r:02x}{g:02x}{b:02x}""##);
+    if a < 255 {
+        text += &format!(r#" {attribute}-opacity="{}""#, number(a as f32 / 255.0));
+    }
+    text
+}
+
+/// Shortest form of `value` to two decimals.
+fn number(value: f32) -> String {
+    let text = format!("{value:.2}");
+    let text = text.trim_end_matches('0').trim_end_matches('.');
+    match text {
+        "-0" => "0".into(),
+        text => text.into(),
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -0,0 +1,108 @@
+use std::collections::{HashSet, VecDeque};
+
+use egui::Color32;
+
+use super::{Grid, Hex, HexIterators, HexRange, HexUtility, TerrainId};
+
+/// Which neighbors of the start cell belong to the filled region.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FillMatch {
+    /// Cells painted with exactly the start cell's terrain.
+    SameTerrain,
+    /// Cells whose terrain color differs from the start cell's by at most
+    /// `tolerance` on every channel.
+    SimilarColor { tolerance: u8 },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FillOptions {
+    pub matching: FillMatch,
+    /// Only fill cells at most this far from the start cell.
+    pub radius: Option<i32>,
+    /// Starting on an empty cell fills the connected empty cells inside the map bounds.
+    pub fill_empty: bool,
+}
+
+impl Default for FillOptions {
+    fn default() -> Self {
+        Self {
+            matching: FillMatch::SameTerrain,
+            radius: None,
+            fill_empty: true,
+        }
+    }
+}
+
+impl Grid {
+    /// Smallest range of cube coordinates holding every cell of every layer.
+    fn cube_bounds(&self) -> Option<HexRange> {
+        self.layers.iter()
+            .flat_map(|layer| layer.data.keys())
+            .fold(None, |bounds: Option<([i32; 3], [i32; 3])>, hex| {
+                let coords = [hex.q(), hex.r(), hex.s()];
+                let (mut min, mut max) = bounds.unwrap_or((coords, coords));
+                for axis in 0..3 {
+                    min[axis] = min[axis].min(coords[axis]);
+                    max[axis] = max[axis].max(coords[axis]);
+                }
+                Some((min, max))
+            })
+            .map(|(min, max)| HexRange::new(min, max))
+    }
+
+    /// Cells of the active layer connected to `start` through matching neighbors,
+    /// `start` included. Returns nothing when `start` cannot be filled.
+    pub fn flood_fill(&self, start: Hex, options: &FillOptions) -> Vec<Hex> {
+        let layer = self.active_layer();
+        let target = layer.cell(start);
+        //Empty space is unbounded, the map bounds keep the fill finite
+        let bounds = match target {
+            Some(_) => None,
+            None if options.fill_empty => match self.cube_bounds() {
+                Some(bounds) => Some(bounds),
+                None => return Vec::new(),
+            },
+            None => return Vec::new(),
+        };
+        let matches = |value: Option<TerrainId>| match (target, value) {
+            (None, None) => true,
+            (Some(target), Some(value)) => self.fill_matches(target, value, options),
+            _ => false,
+        };
+        let inside = |hex: Hex| {
+            options.radius.is_none_or(|radius| start.distance(hex) <= radius)
+                && bounds.as_ref().is_none_or(|bounds| bounds.contains(hex))
+        };
+        if !inside(start) {
+            return Vec::new();
+        }
+
+        let mut region = vec![start];
+        let mut visited = HashSet::from([start]);
+        let mut frontier = VecDeque::from([start]);
+        while let Some(hex) = frontier.pop_front() {
+            for neighbor in hex.neighbors() {
+                if inside(neighbor) && visited.insert(neighbor) && matches(layer.cell(neighbor)) {
+                    region.push(neighbor);
+                    frontier.push_back(neighbor);
+                }
+            }
+        }
+        region
+    }
+
+    /// Whether a cell painted with `value` joins a region started on `target`.
+    pub(super) fn fill_matches(&self, target: TerrainId, value: TerrainId, options: &FillOptions) -> bool {
+        match options.matching {
+            FillMatch::SameTerrain => target == value,
+            FillMatch::SimilarColor { tolerance } => {
+                colors_within(self.terrains.color(target), self.terrains.color(value), tolerance)
+            }
+        }
+    }
+}
+
+fn colors_within(a: Color32, b: Color32, tolerance: u8) -> bool {
+    let (a, b) = (a.to_srgba_unmultiplied(), b.to_srgba_unmultiplied());
+    a.iter().zip(b).all(|(a, b)| a.abs_diff(b) <= tolerance)
+}
@@ -0,0 +1,158 @@
+//! Maps grown from fractal noise.
+//!
+//! Two noise fields, elevation and moisture, are sampled at the center of every
+//! cell. The cell then takes the terrain of the first biome whose thresholds both
+//! values fall under. The same seed and settings always give the same map.
+
+use super::{Grid, Hex, Layout, LayoutTool, Point, Rng, Terrain, TerrainRegistry};
+
+/// Most octaves a [`Generator`] adds up.
+pub const MAX_OCTAVES: u32 = 8;
+
+/// Set apart from the elevation seed so the two fields do not look alike.
+const MOISTURE_SEED: u64 = 0x6d6f_6973_7475_7265;
+
+/// Gradient noise over the plane, Perlin's improved flavour.
+pub struct Noise {
+    /// Shuffled bytes, twice over so lookups can run past the end.
+    permutation: [u8; 512],
+    /// Where each octave is sampled from, so they do not all vanish at the origin.
+    offsets: [Point; MAX_OCTAVES as usize],
+}
+
+impl Noise {
+    pub fn new(seed: u64) -> Self {
+        let mut rng = Rng::new(seed);
+        let mut bytes: [u8; 256] = std::array::from_fn(|index| index as u8);
+        rng.shuffle(&mut bytes);
+        let offsets = std::array::from_fn(|_| Point { x: rng.next_f64() * 256.0, y: rng.next_f64() * 256.0 });
+        Self { permutation: std::array::from_fn(|index| bytes[index % 256]), offsets }
+    }
+
+    /// Smooth noise, zero on whole coordinates and within `-1.0..=1.0`.
+    pub fn sample(&self, x: f64, y: f64) -> f64 {
+        let (x0, y0) = (x.floor(), y.floor());
+        let (dx, dy) = (x - x0, y - y0);
+        let (i, j) = ((x0 as i64 & 255) as usize, (y0 as i64 & 255) as usize);
+        let hash = |i: usize, j: usize| self.permutation[self.permutation[i] as usize + j];
+        let gradient = |hash: u8, x: f64, y: f64| match hash & 7 {
+            0 => x + y,
+            1 => y - x,
+            2 => x - y,
+            3 => -x - y,
+            4 => x,
+            5 => -x,
+            6 => y,
+            _ => -y,
+        };
+        let fade = |t: f64| t * t * t * (t * (t * 6.0 - 15.0) + 10.0);
+        let lerp = |a: f64, b: f64, t: f64| a + t * (b - a);
+        let (u, v) = (fade(dx), fade(dy));
+        let bottom = lerp(gradient(hash(i, j), dx, dy), gradient(hash(i + 1, j), dx - 1.0, dy), u);
+        let top = lerp(gradient(hash(i, j + 1), dx, dy - 1.0), gradient(hash(i + 1, j + 1), dx - 1.0, dy - 1.0), u);
+        lerp(bottom, top, v)
+    }
+
+    /// Octaves of noise summed, each finer and fainter than the last, within `0.0..=1.0`.
+    pub fn fractal(&self, x: f64, y: f64, octaves: u32, persistence: f64, lacunarity: f64) -> f64 {
+        let (mut sum, mut total) = (0.0, 0.0);
+        let (mut frequency, mut amplitude) = (1.0, 1.0);
+        for offset in self.offsets.iter().take(octaves.clamp(1, MAX_OCTAVES) as usize) {
+            sum += amplitude * self.sample(x * frequency + offset.x, y * frequency + offset.y);
+            total += amplitude;
+            frequency *= lacunarity;
+            amplitude *= persistence;
+        }
+        ((sum / total + 1.0) / 2.0).clamp(0.0, 1.0)
+    }
+}
+
+/// A terrain and the elevations and moistures it covers.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Biome {
+    pub terrain: Terrain,
+    pub max_elevation: f64,
+    pub max_moisture: f64,
+}
+
+impl Biome {
+    pub fn new(terrain: Terrain, max_elevation: f64, max_moisture: f64) -> Self {
+        Self { terrain, max_elevation, max_moisture }
+    }
+}
+
+/// Settings of [`Grid::generate`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct Generator {
+    pub seed: u64,
+    /// Width of the broadest features, in cells.
+    pub scale: f64,
+    /// Layers of detail, from 1 to [`MAX_OCTAVES`].
+    pub octaves: u32,
+    /// Weight of each octave relative to the one before.
+    pub persistence: f64,
+    /// Frequency of each octave relative to the one before.
+    pub lacunarity: f64,
+    /// Tried in order. Cells no biome takes fall to the last one.
+    pub biomes: Vec<Biome>,
+}
+
+impl Default for Generator {
+    fn default() -> Self {
+        let builtin = TerrainRegistry::builtin();
+        let terrain = |name: &str| builtin.iter()
+            .find(|(_, terrain)| terrain.name == name)
+            .map(|(_, terrain)| terrain.clone())
+            .expect("Missing builtin terrain!");
+        Self {
+            seed: 0,
+            scale: 12.0,
+            octaves: 4,
+            persistence: 0.5,
+            lacunarity: 2.0,
+            biomes: vec![
+                Biome::new(terrain("Water"), 0.47, 1.0),
+                Biome::new(terrain("Sand"), 0.5, 1.0),
+                Biome::new(terrain("Grass"), 0.6, 0.5),
+                Biome::new(terrain("Forest"), 0.6, 1.0),
+                Biome::new(terrain("Mountain"), 0.65, 1.0),
+                Biome::new(terrain("Snow"), 1.0, 1.0),
+            ],
+        }
+    }
+}
+
+impl Generator {
+    /// Index of the biome a cell with `elevation` and `moisture` belongs to.
+    pub fn biome(&self, elevation: f64, moisture: f64) -> Option<usize> {
+        self.biomes.iter()
+            .position(|biome| elevation <= biome.max_elevation && moisture <= biome.max_moisture)
+            .or(self.biomes.len().checked_sub(1))
+    }
+}
+
+impl Grid {
+    /// Builds a map with `layout` by painting each of `cells` with the biome
+    /// `generator` picks for it.
+    pub fn generate(layout: Layout, cells: impl IntoIterator<Item = Hex>, generator: &Generator) -> Grid {
+        let mut grid = Grid { layout, terrains: TerrainRegistry::default(), ..Default::default() };
+        let terrains: Vec<_> = generator.biomes.iter()
+            .map(|biome| grid.terrains.add(biome.terrain.clone()).expect("Fewer biomes than terrain ids"))
+            .collect();
+        let elevation = Noise::new(generator.seed);
+        let moisture = Noise::new(generator.seed ^ MOISTURE_SEED);
+        //Noise coordinates step by one every `scale` cells
+        let step = LayoutTool::hex_to_pixel(layout, (1, 0).into()).x - LayoutTool::hex_to_pixel(layout, (0, 0).into()).x;
+        let unit = step.abs() * generator.scale;
+        let field = |noise: &Noise, Point { x, y }: Point| {
+            noise.fractal(x / unit, y / unit, generator.octaves, generator.persistence, generator.lacunarity)
+        };
+        for hex in cells {
+            let center = LayoutTool::hex_to_pixel(layout, hex);
+            if let Some(index) = generator.biome(field(&elevation, center), field(&moisture, center)) {
+                grid.paint_cell(hex, terrains[index]);
+            }
+        }
+        grid
+    }
+}
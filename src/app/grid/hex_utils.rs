@@ -1,22 +1,42 @@
-#[allow(unused)]
 pub mod hexagon;
-#[allow(unused)]
 pub mod point;
-#[allow(unused)]
 pub mod tools;
-#[allow(unused)]
 pub mod layout;
+pub mod iterators;
+pub mod edges;
 #[cfg(test)]
 mod tests;
 
 pub use layout::{
     Layout,
     LayoutTool,
+    LAYOUT_ORIENTATION_FLAT,
     LAYOUT_ORIENTATION_POINTY,
 };
 pub use hexagon::{
+    DoubledCoord,
+    FractionalHex,
     Hex,
+    HexAxis,
     HexMath,
+    HexReflection,
+    HexRotation,
     HexRound,
+    HexUtility,
+    OffsetCoord,
+};
+pub use tools::{
+    Diagonal,
+    Direction,
+    HexDoubled,
+    Parity,
+};
+pub use iterators::{
+    HexIterators,
+    HexRange,
+};
+pub use edges::{
+    HexCorner,
+    HexEdge,
 };
 pub use point::Point;
\ No newline at end of file
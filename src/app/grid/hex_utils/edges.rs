@@ -0,0 +1,186 @@
+use super::hexagon::{Hex, HexMath, HexRound};
+use super::layout::{Layout, LayoutTool};
+use super::point::Point;
+use super::tools::{Diagonal, Direction};
+
+/// The side shared by two neighboring cells.
+///
+/// Every edge has a single canonical form, owned by the cell it lies east, north-east
+/// or north-west of, so edges can be compared and used as map keys.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub struct HexEdge {
+    hex: Hex,
+    direction: Direction,
+}
+
+/// The point where three cells meet.
+///
+/// Like [`HexEdge`] every corner has a single canonical form, owned by the cell it
+/// lies east-north-east or north of.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub struct HexCorner {
+    hex: Hex,
+    diagonal: Diagonal,
+}
+
+impl HexEdge {
+    /// The side of `hex` facing `direction`.
+    pub fn new(hex: Hex, direction: Direction) -> Self {
+        if direction.index() < 3 {
+            Self { hex, direction }
+        } else {
+            Self { hex: hex.neighbor(direction), direction: direction.opposite() }
+        }
+    }
+
+    /// The edge between two cells, if they are neighbors.
+    pub fn between(a: Hex, b: Hex) -> Option<Self> {
+        let offset = b.sub(a);
+        Direction::iter()
+            .find(|direction| direction.offset() == offset)
+            .map(|direction| Self::new(a, direction))
+    }
+
+    /// The six sides of `hex`, in [`Direction`] order.
+    pub fn around(hex: Hex) -> [Self; 6] {
+        Direction::ALL.map(|direction| Self::new(hex, direction))
+    }
+
+    pub fn hex(self) -> Hex {
+        self.hex
+    }
+
+    /// Always `East`, `NorthEast` or `NorthWest`.
+    pub fn direction(self) -> Direction {
+        self.direction
+    }
+
+    /// The two cells this edge separates, the owning one first.
+    pub fn cells(self) -> [Hex; 2] {
+        [self.hex, self.hex.neighbor(self.direction)]
+    }
+
+    /// The two corners at the ends of this edge, clockwise one first.
+    pub fn endpoints(self) -> [HexCorner; 2] {
+        let index = self.direction.index() as i32;
+        [
+            HexCorner::new(self.hex, Diagonal::from_index(index - 1)),
+            HexCorner::new(self.hex, Diagonal::from_index(index)),
+        ]
+    }
+
+    /// The four edges sharing an endpoint with this one.
+    #[allow(dead_code)]
+    pub fn neighbors(self) -> Vec<HexEdge> {
+        self.endpoints()
+            .into_iter()
+            .flat_map(HexCorner::edges)
+            .filter(|edge| *edge != self)
+            .collect()
+    }
+
+    /// Middle of the edge.
+    pub fn center(self, layout: Layout) -> Point {
+        let [a, b] = self.cells().map(|hex| LayoutTool::hex_to_pixel(layout, hex));
+        (a + b) / 2.0
+    }
+
+    /// Positions of the two endpoints.
+    pub fn to_pixels(self, layout: Layout) -> [Point; 2] {
+        self.endpoints().map(|corner| corner.to_pixel(layout))
+    }
+
+    /// The edge nearest to `point`.
+    pub fn from_pixel(layout: Layout, point: Point) -> Self {
+        let hex = LayoutTool::pixel_to_hex(layout, point).round();
+        nearest(Self::around(hex), |edge| edge.center(layout), point)
+    }
+}
+
+impl HexCorner {
+    /// The corner of `hex` facing `diagonal`.
+    pub fn new(hex: Hex, diagonal: Diagonal) -> Self {
+        //The same corner seen from the two other cells around it
+        let (first, second) = diagonal.sides();
+        let forms = [
+            (hex, diagonal),
+            (hex.neighbor(first), diagonal.rotate(-2)),
+            (hex.neighbor(second), diagonal.rotate(-4)),
+        ];
+        let (hex, diagonal) = forms.into_iter()
+            .find(|(_, diagonal)| diagonal.index() < 2)
+            .expect("One of three alternate diagonals is canonical");
+        Self { hex, diagonal }
+    }
+
+    /// The corner where three cells meet, if they do.
+    pub fn between(a: Hex, b: Hex, c: Hex) -> Option<Self> {
+        if a == b || b == c || c == a {
+            return None;
+        }
+        Diagonal::iter()
+            .map(|diagonal| Self::new(a, diagonal))
+            .find(|corner| corner.cells().contains(&b) && corner.cells().contains(&c))
+    }
+
+    /// The six corners of `hex`, in [`Diagonal`] order.
+    pub fn around(hex: Hex) -> [Self; 6] {
+        Diagonal::ALL.map(|diagonal| Self::new(hex, diagonal))
+    }
+
+    pub fn hex(self) -> Hex {
+        self.hex
+    }
+
+    /// Always `EastNorthEast` or `North`.
+    pub fn diagonal(self) -> Diagonal {
+        self.diagonal
+    }
+
+    /// The three cells meeting here, the owning one first.
+    pub fn cells(self) -> [Hex; 3] {
+        let (first, second) = self.diagonal.sides();
+        [self.hex, self.hex.neighbor(first), self.hex.neighbor(second)]
+    }
+
+    /// The three edges ending here.
+    pub fn edges(self) -> [HexEdge; 3] {
+        let (first, second) = self.diagonal.sides();
+        [
+            HexEdge::new(self.hex, first),
+            HexEdge::new(self.hex, second),
+            HexEdge::new(self.hex.neighbor(first), second.rotate(-1)),
+        ]
+    }
+
+    /// The three corners one edge away.
+    #[allow(dead_code)]
+    pub fn neighbors(self) -> [HexCorner; 3] {
+        self.edges().map(|edge| {
+            let [a, b] = edge.endpoints();
+            if a == self { b } else { a }
+        })
+    }
+
+    pub fn to_pixel(self, layout: Layout) -> Point {
+        let [a, b, c] = self.cells().map(|hex| LayoutTool::hex_to_pixel(layout, hex));
+        (a + b + c) / 3.0
+    }
+
+    /// The corner nearest to `point`.
+    pub fn from_pixel(layout: Layout, point: Point) -> Self {
+        let hex = LayoutTool::pixel_to_hex(layout, point).round();
+        nearest(Self::around(hex), |corner| corner.to_pixel(layout), point)
+    }
+}
+
+/// The candidate whose position lies closest to `point`.
+fn nearest<T: Copy>(candidates: [T; 6], position: impl Fn(T) -> Point, point: Point) -> T {
+    let distance = |candidate: &T| {
+        let Point { x, y } = position(*candidate);
+        (x - point.x).powi(2) + (y - point.y).powi(2)
+    };
+    candidates.into_iter()
+        .min_by(|a, b| distance(a).total_cmp(&distance(b)))
+        .expect("There are six candidates")
+}
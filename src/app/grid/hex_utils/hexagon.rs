@@ -9,6 +9,7 @@ pub struct Hex {
     s: i32,
 }
 
+#[allow(clippy::needless_return)]
 impl Hex {
     pub fn new(q: i32, r: i32) -> Hex {
         let s = q.neg().sub(r);
@@ -26,6 +27,17 @@ impl Hex {
     pub fn s(&self) -> i32 {
         self.s
     }
+
+    /// Like [`Hex::new`], or `None` when `s` would not fit in an `i32`.
+    pub fn checked_new(q: i32, r: i32) -> Option<Hex> {
+        let s = q.checked_neg()?.checked_sub(r)?;
+        Some(Hex { q, r, s })
+    }
+
+    /// Like [`HexMath::add`], or `None` when a coordinate would overflow.
+    pub fn checked_add(&self, other: Hex) -> Option<Hex> {
+        Hex::checked_new(self.q.checked_add(other.q)?, self.r.checked_add(other.r)?)
+    }
 }
 
 
@@ -60,20 +72,24 @@ pub struct FractionalHex {
     s: f64,
 }
 
+#[allow(clippy::needless_return)]
 impl FractionalHex {
     pub fn new(q: f64, r: f64) -> FractionalHex {
         let s = q.neg().sub(r);
         return FractionalHex { q, r, s };
     }
 
+    #[allow(dead_code)]
     pub fn q(&self) -> f64 {
         self.q
     }
 
+    #[allow(dead_code)]
     pub fn r(&self) -> f64 {
         self.r
     }
 
+    #[allow(dead_code)]
     pub fn s(&self) -> f64 {
         self.s
     }
@@ -124,8 +140,11 @@ impl HexMath for Hex {
 }
 
 pub trait HexRotation {
+    #[allow(dead_code)]
     fn rotate_left(&self) -> Hex;
     fn rotate_right(&self) -> Hex;
+    /// Rotates around `pivot` by `steps` sixths of a turn, rightwards when positive.
+    fn rotate_around(&self, pivot: Hex, steps: i32) -> Hex;
 }
 
 impl HexRotation for Hex {
@@ -144,14 +163,55 @@ impl HexRotation for Hex {
             s: -self.q,
         }
     }
+
+    fn rotate_around(&self, pivot: Hex, steps: i32) -> Hex {
+        let mut offset = self.sub(pivot);
+        for _ in 0..steps.rem_euclid(6) {
+            offset = offset.rotate_right();
+        }
+        pivot.add(offset)
+    }
+}
+
+/// Cube axis a reflection keeps fixed.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum HexAxis {
+    Q,
+    R,
+    S,
+}
+
+pub trait HexReflection {
+    /// Mirrors across the line through the origin where `axis` is constant,
+    /// swapping the other two coordinates.
+    fn reflect(&self, axis: HexAxis) -> Hex;
+    /// Mirrors across the line through `pivot` parallel to `axis`.
+    fn reflect_around(&self, pivot: Hex, axis: HexAxis) -> Hex;
+}
+
+impl HexReflection for Hex {
+    fn reflect(&self, axis: HexAxis) -> Hex {
+        let Hex { q, r, s } = *self;
+        match axis {
+            HexAxis::Q => Hex { q, r: s, s: r },
+            HexAxis::R => Hex { q: s, r, s: q },
+            HexAxis::S => Hex { q: r, r: q, s },
+        }
+    }
+
+    fn reflect_around(&self, pivot: Hex, axis: HexAxis) -> Hex {
+        pivot.add(self.sub(pivot).reflect(axis))
+    }
 }
 
 pub trait HexUtility {
+    #[allow(dead_code)]
     fn length(&self) -> i32;
     fn distance(&self, other: Hex) -> i32;
     fn line(&self, b: Hex) -> Vec<Hex>;
 }
 
+#[allow(clippy::needless_return, clippy::unnecessary_cast)]
 impl HexUtility for Hex {
     fn length(&self) -> i32 {
         return (self.q.abs() + self.r.abs() + self.s.abs()) / 2 as i32;
@@ -196,6 +256,7 @@ pub trait HexRound {
     fn linear_interpolation(&self, other: FractionalHex, t: f64) -> FractionalHex;
 }
 
+#[allow(clippy::needless_return)]
 impl HexRound for FractionalHex {
     fn round(&self) -> Hex {
         let mut qi: i32 = self.q.round() as i32;
@@ -0,0 +1,221 @@
+use std::cmp::{max, min};
+
+use super::hexagon::{Hex, HexMath, HEX_DIAGONALS, HEX_DIRECTIONS};
+
+/// Lazy walks over the cells around a hex.
+pub trait HexIterators {
+    /// Cells exactly `radius` steps away, starting from the one in direction 4 and
+    /// walking along directions 0 to 5.
+    fn ring(&self, radius: i32) -> HexRing;
+    /// The center followed by every ring up to `radius`, innermost first.
+    #[allow(dead_code)]
+    fn spiral(&self, radius: i32) -> HexSpiral;
+    /// Every cell at most `n` steps away.
+    fn range(&self, n: i32) -> HexRange;
+    /// The six adjacent cells, in `HEX_DIRECTIONS` order.
+    fn neighbors(&self) -> HexNeighbors;
+    /// The six cells across the corners, in `HEX_DIAGONALS` order.
+    #[allow(dead_code)]
+    fn diagonals(&self) -> HexNeighbors;
+}
+
+impl HexIterators for Hex {
+    fn ring(&self, radius: i32) -> HexRing {
+        HexRing::new(*self, radius)
+    }
+
+    fn spiral(&self, radius: i32) -> HexSpiral {
+        HexSpiral {
+            center: *self,
+            radius,
+            ring: HexRing::new(*self, if radius < 0 { -1 } else { 0 }),
+        }
+    }
+
+    fn range(&self, n: i32) -> HexRange {
+        HexRange::new(
+            [self.q() - n, self.r() - n, self.s() - n],
+            [self.q() + n, self.r() + n, self.s() + n],
+        )
+    }
+
+    fn neighbors(&self) -> HexNeighbors {
+        HexNeighbors { hex: *self, offsets: &HEX_DIRECTIONS, index: 0 }
+    }
+
+    fn diagonals(&self) -> HexNeighbors {
+        HexNeighbors { hex: *self, offsets: &HEX_DIAGONALS, index: 0 }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct HexRing {
+    current: Hex,
+    radius: i32,
+    direction: usize,
+    step: i32,
+    remaining: usize,
+}
+
+impl HexRing {
+    fn new(center: Hex, radius: i32) -> Self {
+        let remaining = match radius {
+            ..=-1 => 0,
+            0 => 1,
+            _ => 6 * radius as usize,
+        };
+        Self {
+            current: center.add(HEX_DIRECTIONS[4].scale(radius.max(0))),
+            radius,
+            direction: 0,
+            step: 0,
+            remaining,
+        }
+    }
+}
+
+impl Iterator for HexRing {
+    type Item = Hex;
+
+    fn next(&mut self) -> Option<Hex> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+        let hex = self.current;
+        if self.remaining > 0 {
+            self.current = self.current.add(HEX_DIRECTIONS[self.direction]);
+            self.step += 1;
+            if self.step == self.radius {
+                self.step = 0;
+                self.direction += 1;
+            }
+        }
+        Some(hex)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl ExactSizeIterator for HexRing {}
+
+#[derive(Clone, Debug)]
+pub struct HexSpiral {
+    center: Hex,
+    radius: i32,
+    ring: HexRing,
+}
+
+impl Iterator for HexSpiral {
+    type Item = Hex;
+
+    fn next(&mut self) -> Option<Hex> {
+        loop {
+            if let Some(hex) = self.ring.next() {
+                return Some(hex);
+            }
+            if self.ring.radius < 0 || self.ring.radius >= self.radius {
+                return None;
+            }
+            self.ring = HexRing::new(self.center, self.ring.radius + 1);
+        }
+    }
+}
+
+/// Every cell whose cube coordinates lie between `min` and `max` on all three axes.
+///
+/// Ranges around a center are hexagons; intersecting them gives the cells
+/// reachable from all of their centers.
+#[derive(Clone, Debug)]
+pub struct HexRange {
+    min: [i32; 3],
+    max: [i32; 3],
+    q: i32,
+    r: i32,
+    r_end: i32,
+}
+
+impl HexRange {
+    /// `min` and `max` are `[q, r, s]`.
+    pub fn new(min: [i32; 3], max: [i32; 3]) -> Self {
+        let mut range = Self { min, max, q: min[0], r: 0, r_end: -1 };
+        range.start_column();
+        range
+    }
+
+    fn start_column(&mut self) {
+        self.r = max(self.min[1], -self.q - self.max[2]);
+        self.r_end = min(self.max[1], -self.q - self.min[2]);
+    }
+
+    /// Cells in both ranges, starting over from the first cell.
+    pub fn intersection(&self, other: &HexRange) -> HexRange {
+        HexRange::new(
+            [0, 1, 2].map(|axis| max(self.min[axis], other.min[axis])),
+            [0, 1, 2].map(|axis| min(self.max[axis], other.max[axis])),
+        )
+    }
+
+    /// Positions in the box spanned on the q and r axes, at least how many the range yields.
+    pub fn span_area(&self) -> i64 {
+        let span = |axis: usize| (self.max[axis] as i64 - self.min[axis] as i64 + 1).max(0);
+        span(0) * span(1)
+    }
+
+    pub fn contains(&self, hex: Hex) -> bool {
+        let coords = [hex.q(), hex.r(), hex.s()];
+        (0..3).all(|axis| self.min[axis] <= coords[axis] && coords[axis] <= self.max[axis])
+    }
+}
+
+impl Iterator for HexRange {
+    type Item = Hex;
+
+    fn next(&mut self) -> Option<Hex> {
+        while self.q <= self.max[0] {
+            if self.r <= self.r_end {
+                let hex = Hex::new(self.q, self.r);
+                self.r += 1;
+                return Some(hex);
+            }
+            self.q += 1;
+            self.start_column();
+        }
+        None
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct HexNeighbors {
+    hex: Hex,
+    offsets: &'static [Hex; 6],
+    index: usize,
+}
+
+impl Iterator for HexNeighbors {
+    type Item = Hex;
+
+    fn next(&mut self) -> Option<Hex> {
+        let offset = self.offsets.get(self.index)?;
+        self.index += 1;
+        Some(self.hex.add(*offset))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = 6 - self.index;
+        (remaining, Some(remaining))
+    }
+}
+
+impl ExactSizeIterator for HexNeighbors {}
+
+/// Cells at most `n` steps away from every `center` of `ranges`, `None` if `ranges` is empty.
+#[allow(dead_code)]
+pub fn range_intersection(ranges: &[(Hex, i32)]) -> Option<HexRange> {
+    ranges.iter()
+        .map(|(center, n)| center.range(*n))
+        .reduce(|a, b| a.intersection(&b))
+}
+
@@ -29,7 +29,7 @@ pub const LAYOUT_ORIENTATION_FLAT: Orientation = Orientation {
     start_angle: 0.0,
 };
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Orientation {
     pub f0: f64,
     pub f1: f64,
@@ -42,7 +42,7 @@ pub struct Orientation {
     pub start_angle: f64,
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Layout {
     pub orientation: Orientation,
     pub size: Point,
@@ -51,6 +51,7 @@ pub struct Layout {
 
 pub struct LayoutTool;
 
+#[allow(clippy::needless_return, clippy::needless_parens_on_range_literals)]
 impl LayoutTool {
     pub fn hex_to_pixel(layout: Layout, hex: Hex) -> Point {
         let orientation: Orientation = layout.orientation;
@@ -2,13 +2,16 @@
 use float_eq::assert_float_eq;
 
 use super::hexagon::{
-    DoubledCoord, FractionalHex, Hex, HexMath, HexRotation, HexRound, HexUtility, OffsetCoord,
+    DoubledCoord, FractionalHex, Hex, HexAxis, HexMath, HexReflection, HexRotation, HexRound, HexUtility, OffsetCoord,
 };
 use super::layout::{Layout, LayoutTool, LAYOUT_ORIENTATION_FLAT, LAYOUT_ORIENTATION_POINTY};
 use super::point::Point;
-use super::tools::{HexDirection, HexDoubled, HexOffset, HEX_EVEN, HEX_ODD};
+use super::tools::{Diagonal, Direction, HexDirection, HexDoubled, HexOffset, Parity, HEX_EVEN, HEX_ODD};
+use super::iterators::{range_intersection, HexIterators};
+use super::edges::{HexCorner, HexEdge};
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::f64::consts::PI;
 
 #[test]
 fn test_s_component() {
@@ -35,12 +38,14 @@ fn test_hex_arithmetic() {
 }
 
 #[test]
+#[allow(deprecated)]
 fn test_hex_direction() {
     let expected_direction = Hex::new(0, -1);
     assert_eq!(expected_direction, HexDirection::direction(2));
 }
 
 #[test]
+#[allow(deprecated)]
 fn test_hex_neighbor() {
     let expected_neighbor = Hex::new(1, -3);
     assert_eq!(
@@ -50,6 +55,7 @@ fn test_hex_neighbor() {
 }
 
 #[test]
+#[allow(deprecated)]
 fn test_hex_diagonal() {
     let expected_neighbor = Hex::new(-1, -1);
     assert_eq!(
@@ -58,6 +64,64 @@ fn test_hex_diagonal() {
     );
 }
 
+#[test]
+fn test_direction_enums() {
+    assert_eq!(Hex::new(0, -1), Direction::NorthWest.offset());
+    assert_eq!(Direction::NorthWest, Direction::from_index(2));
+    assert_eq!(Direction::NorthWest, Direction::from_index(-4));
+    assert_eq!(Hex::new(1, -3), Hex::new(1, -2).neighbor(Direction::NorthWest));
+    assert_eq!(Hex::new(-1, -1), Hex::new(1, -2).diagonal_neighbor(Diagonal::WestSouthWest));
+}
+
+#[test]
+fn test_direction_helpers() {
+    assert_eq!(Direction::ALL.to_vec(), Direction::iter().collect::<Vec<_>>());
+    for direction in Direction::iter() {
+        assert_eq!(direction.offset().scale(-1), direction.opposite().offset());
+        assert_eq!(direction.offset().rotate_right(), direction.rotate(1).offset());
+        assert_eq!(direction.offset().rotate_left(), direction.rotate(-1).offset());
+        assert_eq!(direction, direction.rotate(6));
+    }
+    for diagonal in Diagonal::iter() {
+        assert_eq!(diagonal.offset().scale(-1), diagonal.opposite().offset());
+        assert_eq!(diagonal.offset().rotate_right(), diagonal.rotate(1).offset());
+        let (a, b) = diagonal.sides();
+        assert_eq!(diagonal.offset(), a.offset().add(b.offset()));
+        assert_float_eq!(a.angle() + PI / 6.0, diagonal.angle(), abs <= 1e-12);
+    }
+    assert_float_eq!(PI, Direction::West.angle(), abs <= 1e-12);
+    assert_float_eq!(PI / 2.0, Diagonal::North.angle(), abs <= 1e-12);
+}
+
+#[test]
+#[allow(deprecated)]
+fn test_integer_shims() {
+    assert_eq!(Direction::SouthEast.offset(), HexDirection::direction(5));
+
+    let hex = Hex::new(3, -5);
+    assert_eq!(Parity::Odd.q_from_cube(hex), HexOffset::q_from_cube(HEX_ODD, hex));
+    assert_eq!(Parity::Even.r_from_cube(hex), HexOffset::r_from_cube(HEX_EVEN, hex));
+    let coord = OffsetCoord { col: 2, row: 7 };
+    assert_eq!(Parity::Even.q_to_cube(coord), HexOffset::q_to_cube(HEX_EVEN, coord));
+    assert_eq!(Parity::Odd.r_to_cube(coord), HexOffset::r_to_cube(HEX_ODD, coord));
+    assert_eq!(Ok(Parity::Odd), Parity::try_from(HEX_ODD));
+    assert_eq!(Err(0), Parity::try_from(0));
+}
+
+#[test]
+#[should_panic]
+#[allow(deprecated)]
+fn test_integer_shim_rejects_bad_parity() {
+    HexOffset::q_from_cube(0, Hex::new(1, 1));
+}
+
+#[test]
+#[should_panic]
+#[allow(deprecated)]
+fn test_integer_shim_rejects_bad_direction() {
+    HexDirection::neighbor(Hex::new(1, 1), -1);
+}
+
 #[test]
 fn test_hex_distance() {
     let hex_from = Hex::new(3, -7);
@@ -83,6 +147,35 @@ fn test_hex_rotate_left() {
     assert_eq!(hex_expected, hex.rotate_left());
 }
 
+#[test]
+fn test_hex_rotate_around() {
+    let pivot = Hex::new(2, -1);
+    let hex = pivot.add(Hex::new(1, -3));
+
+    assert_eq!(pivot.add(Hex::new(3, -2)), hex.rotate_around(pivot, 1));
+    assert_eq!(pivot.add(Hex::new(-2, -1)), hex.rotate_around(pivot, -1));
+    assert_eq!(hex, hex.rotate_around(pivot, 6));
+    assert_eq!(hex.rotate_around(pivot, 2), hex.rotate_around(pivot, -4));
+    assert_eq!(pivot, pivot.rotate_around(pivot, 3));
+}
+
+#[test]
+fn test_hex_reflect() {
+    let hex = Hex::new(1, -3);
+
+    assert_eq!(Hex::new(1, 2), hex.reflect(HexAxis::Q));
+    assert_eq!(Hex::new(2, -3), hex.reflect(HexAxis::R));
+    assert_eq!(Hex::new(-3, 1), hex.reflect(HexAxis::S));
+    for axis in [HexAxis::Q, HexAxis::R, HexAxis::S] {
+        assert_eq!(hex, hex.reflect(axis).reflect(axis));
+        assert_eq!(hex.length(), hex.reflect(axis).length());
+    }
+
+    let pivot = Hex::new(-2, 5);
+    assert_eq!(pivot.add(Hex::new(1, 2)), pivot.add(hex).reflect_around(pivot, HexAxis::Q));
+    assert_eq!(pivot, pivot.reflect_around(pivot, HexAxis::S));
+}
+
 #[test]
 fn test_hex_round() {
     let a: FractionalHex = FractionalHex::new(0.0, 0.0);
@@ -235,6 +328,7 @@ fn test_hex_layout() {
 }
 
 #[test]
+#[allow(deprecated)]
 fn test_offset_roundtrip() {
     let expected_hex: Hex = Hex::new(3, 4);
     let expected_coord: OffsetCoord = OffsetCoord { col: 1, row: -3 };
@@ -273,6 +367,7 @@ fn test_offset_roundtrip() {
 }
 
 #[test]
+#[allow(deprecated)]
 fn test_offset_from_cube() {
     let hex1 = Hex::new(1, 2);
     assert_eq!(
@@ -288,6 +383,7 @@ fn test_offset_from_cube() {
 }
 
 #[test]
+#[allow(deprecated)]
 fn test_offset_to_cube() {
     let coord1 = OffsetCoord { col: 1, row: 3 };
     assert_eq!(Hex::new(1, 2), HexOffset::q_to_cube(HEX_EVEN, coord1));
@@ -296,6 +392,21 @@ fn test_offset_to_cube() {
     assert_eq!(Hex::new(1, 2), HexOffset::q_to_cube(HEX_ODD, coord2));
 }
 
+#[test]
+fn test_offset_parity() {
+    let hex = Hex::new(3, 4);
+    let coord = OffsetCoord { col: 1, row: -3 };
+    for parity in [Parity::Even, Parity::Odd] {
+        assert_eq!(hex, parity.q_to_cube(parity.q_from_cube(hex)));
+        assert_eq!(hex, parity.r_to_cube(parity.r_from_cube(hex)));
+        assert_eq!(coord, parity.q_from_cube(parity.q_to_cube(coord)));
+        assert_eq!(coord, parity.r_from_cube(parity.r_to_cube(coord)));
+    }
+    assert_eq!(OffsetCoord { col: 1, row: 3 }, Parity::Even.q_from_cube(Hex::new(1, 2)));
+    assert_eq!(OffsetCoord { col: 1, row: 2 }, Parity::Odd.q_from_cube(Hex::new(1, 2)));
+    assert_eq!(Hex::new(1, 2), Parity::Even.q_to_cube(OffsetCoord { col: 1, row: 3 }));
+}
+
 #[test]
 fn test_doubled_roundtrip() {
     let expected_hex: Hex = Hex::new(3, 4);
@@ -357,6 +468,7 @@ fn test_fractional_behavior() {
 }
 
 #[test]
+#[allow(clippy::unnecessary_get_then_check)]
 pub fn test_hashing() {
     let mut map: HashMap<Hex, &'static str> = HashMap::new();
     map.insert(Hex::new(1, 1), "foo");
@@ -368,4 +480,172 @@ pub fn test_hashing() {
     assert!(map.get(&hex1).is_some());
 
     assert_eq!(*map.get(&hex1).unwrap(), "foo");
-}
\ No newline at end of file
+}
+/// Every hex inside a box big enough for the iterator tests, to check them against.
+fn brute_force_area() -> impl Iterator<Item = Hex> {
+    (-12..=12).flat_map(|q| (-12..=12).map(move |r| Hex::new(q, r)))
+}
+
+#[test]
+fn test_hex_ring() {
+    let center = Hex::new(3, -2);
+    assert_eq!(vec![center], center.ring(0).collect::<Vec<_>>());
+    assert_eq!(0, center.ring(-1).count());
+
+    for radius in 1..=5 {
+        let ring: Vec<_> = center.ring(radius).collect();
+        assert_eq!(6 * radius as usize, ring.len());
+        assert_eq!(ring.len(), center.ring(radius).len());
+        assert_eq!(center.add(Direction::SouthWest.offset().scale(radius)), ring[0]);
+
+        let expected: HashSet<_> = brute_force_area().filter(|hex| hex.distance(center) == radius).collect();
+        assert_eq!(expected, ring.iter().copied().collect::<HashSet<_>>());
+        //Consecutive cells touch, the ring closes on its first cell
+        for (i, hex) in ring.iter().enumerate() {
+            assert_eq!(1, hex.distance(ring[(i + 1) % ring.len()]));
+        }
+    }
+}
+
+#[test]
+fn test_hex_spiral() {
+    let center = Hex::new(-1, 4);
+    assert_eq!(vec![center], center.spiral(0).collect::<Vec<_>>());
+    assert_eq!(0, center.spiral(-2).count());
+
+    for radius in 1..=5 {
+        let spiral: Vec<_> = center.spiral(radius).collect();
+        assert_eq!((3 * radius * (radius + 1) + 1) as usize, spiral.len());
+        assert_eq!(center, spiral[0]);
+        assert!(spiral.windows(2).all(|pair| pair[0].distance(center) <= pair[1].distance(center)));
+
+        let expected: HashSet<_> = center.range(radius).collect();
+        let actual: HashSet<_> = spiral.iter().copied().collect();
+        assert_eq!(spiral.len(), actual.len());
+        assert_eq!(expected, actual);
+    }
+}
+
+#[test]
+fn test_hex_range() {
+    let center = Hex::new(2, -5);
+    assert_eq!(0, center.range(-1).count());
+    for n in 0..=6 {
+        let range: Vec<_> = center.range(n).collect();
+        let expected: HashSet<_> = brute_force_area().filter(|hex| hex.distance(center) <= n).collect();
+        assert_eq!(expected.len(), range.len());
+        assert_eq!(expected, range.iter().copied().collect::<HashSet<_>>());
+        assert!(brute_force_area().all(|hex| center.range(n).contains(hex) == expected.contains(&hex)));
+    }
+}
+
+#[test]
+fn test_hex_range_intersection() {
+    let ranges = [(Hex::new(0, 0), 4), (Hex::new(3, -1), 2), (Hex::new(2, 1), 3)];
+    for (a, n) in ranges {
+        for (b, m) in ranges {
+            let expected: HashSet<_> = brute_force_area()
+                .filter(|hex| hex.distance(a) <= n && hex.distance(b) <= m)
+                .collect();
+            let actual: Vec<_> = a.range(n).intersection(&b.range(m)).collect();
+            assert_eq!(expected.len(), actual.len());
+            assert_eq!(expected, actual.into_iter().collect::<HashSet<_>>());
+        }
+    }
+
+    let expected: HashSet<_> = brute_force_area()
+        .filter(|hex| ranges.iter().all(|(center, n)| hex.distance(*center) <= *n))
+        .collect();
+    let actual: HashSet<_> = range_intersection(&ranges).unwrap().collect();
+    assert_eq!(expected, actual);
+
+    assert_eq!(0, Hex::new(0, 0).range(1).intersection(&Hex::new(5, 0).range(1)).count());
+    assert!(range_intersection(&[]).is_none());
+}
+
+#[test]
+fn test_hex_neighbors_and_diagonals() {
+    let hex = Hex::new(1, -2);
+    let neighbors: Vec<_> = hex.neighbors().collect();
+    let diagonals: Vec<_> = hex.diagonals().collect();
+    assert_eq!(6, hex.neighbors().len());
+    assert_eq!(6, hex.diagonals().len());
+    for (direction, diagonal) in Direction::iter().zip(Diagonal::iter()) {
+        assert_eq!(hex.neighbor(direction), neighbors[direction.index()]);
+        assert_eq!(hex.diagonal_neighbor(diagonal), diagonals[diagonal.index()]);
+    }
+    assert_eq!(hex.ring(1).collect::<HashSet<_>>(), neighbors.iter().copied().collect::<HashSet<_>>());
+    assert!(diagonals.iter().all(|diagonal| diagonal.distance(hex) == 2));
+}
+
+#[test]
+fn test_hex_edges() {
+    let hex = Hex::new(2, -1);
+    for direction in Direction::iter() {
+        let edge = HexEdge::new(hex, direction);
+        assert!(edge.direction().index() < 3);
+        assert_eq!(edge, HexEdge::new(hex.neighbor(direction), direction.opposite()));
+        assert_eq!(Some(edge), HexEdge::between(hex, hex.neighbor(direction)));
+        assert!(edge.cells().contains(&hex) && edge.cells().contains(&hex.neighbor(direction)));
+        //Neighbors share an endpoint, and are all different
+        let neighbors: HashSet<_> = edge.neighbors().into_iter().collect();
+        assert_eq!(4, neighbors.len());
+        for neighbor in neighbors {
+            assert!(neighbor.endpoints().iter().any(|corner| edge.endpoints().contains(corner)));
+        }
+    }
+    assert_eq!(None, HexEdge::between(hex, hex));
+    assert_eq!(None, HexEdge::between(hex, hex.diagonal_neighbor(Diagonal::North)));
+    assert_eq!(6, HexEdge::around(hex).iter().collect::<HashSet<_>>().len());
+}
+
+#[test]
+fn test_hex_corners() {
+    let hex = Hex::new(-1, 3);
+    let mut seen = HashSet::new();
+    for diagonal in Diagonal::iter() {
+        let corner = HexCorner::new(hex, diagonal);
+        assert!(corner.diagonal().index() < 2);
+        let [a, b, c] = corner.cells();
+        let (first, second) = diagonal.sides();
+        assert_eq!(Some(corner), HexCorner::between(hex, hex.neighbor(second), hex.neighbor(first)));
+        assert_eq!(Some(corner), HexCorner::between(c, a, b));
+        //Each corner ends three edges, each running to one of its neighbors
+        for (edge, neighbor) in corner.edges().iter().zip(corner.neighbors()) {
+            assert!(edge.endpoints().contains(&corner));
+            assert!(edge.endpoints().contains(&neighbor));
+        }
+        seen.insert(corner);
+    }
+    assert_eq!(6, seen.len());
+    assert_eq!(None, HexCorner::between(hex, hex.neighbor(Direction::East), hex.neighbor(Direction::West)));
+    assert_eq!(None, HexCorner::between(hex, hex, hex.neighbor(Direction::East)));
+}
+
+#[test]
+fn test_hex_edges_and_corners_to_pixels() {
+    for orientation in [LAYOUT_ORIENTATION_POINTY, LAYOUT_ORIENTATION_FLAT] {
+        let layout = Layout { orientation, size: Point { x: 10.0, y: 15.0 }, origin: Point { x: 35.0, y: 71.0 } };
+        let hex = Hex::new(3, 4);
+        let corners = LayoutTool::polygon_corners(layout, hex);
+        //Every position lies on the outline of the cell
+        let on_outline = |point: Point| corners.iter().any(|corner| {
+            (corner.x - point.x).abs() < 1e-9 && (corner.y - point.y).abs() < 1e-9
+        });
+        for corner in HexCorner::around(hex) {
+            assert!(on_outline(corner.to_pixel(layout)));
+            assert_eq!(corner, HexCorner::from_pixel(layout, corner.to_pixel(layout)));
+        }
+        for edge in HexEdge::around(hex) {
+            let [a, b] = edge.to_pixels(layout);
+            assert!(on_outline(a) && on_outline(b));
+            let center = edge.center(layout);
+            assert_float_eq!((a.x + b.x) / 2.0, center.x, abs <= 1e-9);
+            assert_float_eq!((a.y + b.y) / 2.0, center.y, abs <= 1e-9);
+            //Slightly inside the cell still finds the edge
+            let inside = LayoutTool::hex_to_pixel(layout, hex);
+            let near = Point { x: center.x * 0.9 + inside.x * 0.1, y: center.y * 0.9 + inside.y * 0.1 };
+            assert_eq!(edge, HexEdge::from_pixel(layout, near));
+        }
+    }
+}
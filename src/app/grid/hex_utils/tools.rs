@@ -1,72 +1,294 @@
+use std::f64::consts::PI;
+
 use super::hexagon::{DoubledCoord, Hex, OffsetCoord, HexMath, HEX_DIRECTIONS, HEX_DIAGONALS};
 
+/// Integer form of [`Parity::Even`], for the deprecated offset conversions.
 pub const HEX_EVEN: i32 = 1;
+/// Integer form of [`Parity::Odd`], for the deprecated offset conversions.
 pub const HEX_ODD: i32 = -1;
 
+/// Which lines of an offset grid are shoved half a cell: the even or the odd ones.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum Parity {
+    Even,
+    Odd,
+}
+
+impl Parity {
+    /// `+1` for even, `-1` for odd, the sign the conversion formulas use.
+    pub fn offset(self) -> i32 {
+        match self {
+            Parity::Even => HEX_EVEN,
+            Parity::Odd => HEX_ODD,
+        }
+    }
+
+    /// Offset coordinates of `hex` on a grid of columns, flat maps.
+    pub fn q_from_cube(self, hex: Hex) -> OffsetCoord {
+        let col: i32 = hex.q();
+        let row: i32 = hex.r() + (hex.q() + self.offset() * (hex.q() & 1)) / 2;
+        OffsetCoord { col, row }
+    }
+
+    pub fn q_to_cube(self, coord: OffsetCoord) -> Hex {
+        let q: i32 = coord.col;
+        let r: i32 = coord.row - (coord.col + self.offset() * (coord.col & 1)) / 2;
+        Hex::new(q, r)
+    }
+
+    /// Offset coordinates of `hex` on a grid of rows, pointy maps.
+    pub fn r_from_cube(self, hex: Hex) -> OffsetCoord {
+        let col: i32 = hex.q() + (hex.r() + self.offset() * (hex.r() & 1)) / 2;
+        let row: i32 = hex.r();
+        OffsetCoord { col, row }
+    }
+
+    pub fn r_to_cube(self, coord: OffsetCoord) -> Hex {
+        let q: i32 = coord.col - (coord.row + self.offset() * (coord.row & 1)) / 2;
+        let r: i32 = coord.row;
+        Hex::new(q, r)
+    }
+
+    /// Like [`Parity::q_to_cube`], or `None` when the cell would not fit in an `i32`.
+    pub fn q_to_cube_checked(self, coord: OffsetCoord) -> Option<Hex> {
+        let shift = coord.col.checked_add(self.offset() * (coord.col & 1))? / 2;
+        Hex::checked_new(coord.col, coord.row.checked_sub(shift)?)
+    }
+
+    /// Like [`Parity::r_to_cube`], or `None` when the cell would not fit in an `i32`.
+    pub fn r_to_cube_checked(self, coord: OffsetCoord) -> Option<Hex> {
+        let shift = coord.row.checked_add(self.offset() * (coord.row & 1))? / 2;
+        Hex::checked_new(coord.col.checked_sub(shift)?, coord.row)
+    }
+}
+
+impl TryFrom<i32> for Parity {
+    type Error = i32;
+
+    /// Reads [`HEX_EVEN`] or [`HEX_ODD`], giving back any other value.
+    fn try_from(offset: i32) -> Result<Self, i32> {
+        match offset {
+            HEX_EVEN => Ok(Parity::Even),
+            HEX_ODD => Ok(Parity::Odd),
+            _ => Err(offset),
+        }
+    }
+}
+
+/// The six neighbors of a cell, in `HEX_DIRECTIONS` order.
+///
+/// Names are compass points on pointy maps, where `East` is the `+q` side. On flat
+/// maps every direction lies 30° further clockwise, `East` pointing east-south-east.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum Direction {
+    East,
+    NorthEast,
+    NorthWest,
+    West,
+    SouthWest,
+    SouthEast,
+}
+
+/// The six cells across the corners of a cell, in `HEX_DIAGONALS` order. Diagonal `i`
+/// lies between [`Direction`]s `i` and `i + 1`, named like them.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum Diagonal {
+    EastNorthEast,
+    North,
+    WestNorthWest,
+    WestSouthWest,
+    South,
+    EastSouthEast,
+}
+
+impl Direction {
+    pub const ALL: [Direction; 6] = [
+        Direction::East,
+        Direction::NorthEast,
+        Direction::NorthWest,
+        Direction::West,
+        Direction::SouthWest,
+        Direction::SouthEast,
+    ];
+
+    /// All directions counter-clockwise, starting east.
+    pub fn iter() -> impl Iterator<Item = Direction> {
+        Self::ALL.into_iter()
+    }
+
+    /// Any integer names a direction, counting counter-clockwise from east modulo six.
+    pub fn from_index(index: i32) -> Direction {
+        Self::ALL[index.rem_euclid(6) as usize]
+    }
+
+    pub fn index(self) -> usize {
+        self as usize
+    }
+
+    /// Coordinates of the neighbor of the origin on this side.
+    pub fn offset(self) -> Hex {
+        HEX_DIRECTIONS[self.index()]
+    }
+
+    pub fn opposite(self) -> Direction {
+        self.rotate(3)
+    }
+
+    /// Turns by `steps` sixths of a turn, rightwards (clockwise) when positive like
+    /// `HexRotation::rotate_around`.
+    pub fn rotate(self, steps: i32) -> Direction {
+        Self::from_index(self.index() as i32 - steps)
+    }
+
+    /// Counter-clockwise angle from east on pointy maps, in radians. Subtract `PI / 6` on flat maps.
+    #[allow(dead_code)]
+    pub fn angle(self) -> f64 {
+        self.index() as f64 * PI / 3.0
+    }
+
+    /// Lowercase compass name, like `north-east`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Direction::East => "east",
+            Direction::NorthEast => "north-east",
+            Direction::NorthWest => "north-west",
+            Direction::West => "west",
+            Direction::SouthWest => "south-west",
+            Direction::SouthEast => "south-east",
+        }
+    }
+}
+
+impl Diagonal {
+    pub const ALL: [Diagonal; 6] = [
+        Diagonal::EastNorthEast,
+        Diagonal::North,
+        Diagonal::WestNorthWest,
+        Diagonal::WestSouthWest,
+        Diagonal::South,
+        Diagonal::EastSouthEast,
+    ];
+
+    /// All diagonals counter-clockwise, starting east-north-east.
+    pub fn iter() -> impl Iterator<Item = Diagonal> {
+        Self::ALL.into_iter()
+    }
+
+    /// Any integer names a diagonal, counting counter-clockwise modulo six.
+    pub fn from_index(index: i32) -> Diagonal {
+        Self::ALL[index.rem_euclid(6) as usize]
+    }
+
+    pub fn index(self) -> usize {
+        self as usize
+    }
+
+    /// Coordinates of the diagonal neighbor of the origin on this side.
+    pub fn offset(self) -> Hex {
+        HEX_DIAGONALS[self.index()]
+    }
+
+    #[allow(dead_code)]
+    pub fn opposite(self) -> Diagonal {
+        self.rotate(3)
+    }
+
+    /// Turns by `steps` sixths of a turn, rightwards (clockwise) when positive.
+    pub fn rotate(self, steps: i32) -> Diagonal {
+        Self::from_index(self.index() as i32 - steps)
+    }
+
+    /// The two directions this diagonal lies between, clockwise one first.
+    pub fn sides(self) -> (Direction, Direction) {
+        let index = self.index() as i32;
+        (Direction::from_index(index), Direction::from_index(index + 1))
+    }
+
+    /// Counter-clockwise angle from east on pointy maps, in radians. Subtract `PI / 6` on flat maps.
+    #[allow(dead_code)]
+    pub fn angle(self) -> f64 {
+        (self.index() as f64 + 0.5) * PI / 3.0
+    }
+
+    /// Lowercase compass name, like `east-north-east`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Diagonal::EastNorthEast => "east-north-east",
+            Diagonal::North => "north",
+            Diagonal::WestNorthWest => "west-north-west",
+            Diagonal::WestSouthWest => "west-south-west",
+            Diagonal::South => "south",
+            Diagonal::EastSouthEast => "east-south-east",
+        }
+    }
+}
+
+impl Hex {
+    pub fn neighbor(&self, direction: Direction) -> Hex {
+        self.add(direction.offset())
+    }
+
+    #[allow(dead_code)]
+    pub fn diagonal_neighbor(&self, diagonal: Diagonal) -> Hex {
+        self.add(diagonal.offset())
+    }
+}
+
+/// Integer direction helpers, kept for older callers. Indices outside `0..6` panic.
 pub struct HexDirection;
 
+#[allow(dead_code)]
 impl HexDirection {
+    #[deprecated(note = "use `Direction::from_index(direction).offset()`")]
     pub fn direction(direction: i32) -> Hex {
         HEX_DIRECTIONS[direction as usize]
     }
 
+    #[deprecated(note = "use `Hex::neighbor` with a `Direction`")]
     pub fn neighbor(hex: Hex, direction: i32) -> Hex {
-        hex.add(Self::direction(direction))
+        hex.add(HEX_DIRECTIONS[direction as usize])
     }
 
+    #[deprecated(note = "use `Hex::diagonal_neighbor` with a `Diagonal`")]
     pub fn diagonal_neighbor(hex: Hex, direction: i32) -> Hex {
         hex.add(HEX_DIAGONALS[direction as usize])
     }
 }
 
+/// Offset conversions taking the parity as [`HEX_EVEN`] or [`HEX_ODD`].
 pub struct HexOffset;
 
+#[allow(dead_code)]
 impl HexOffset {
+    #[deprecated(note = "use `Parity::q_from_cube`")]
     pub fn q_from_cube(offset: i32, hex: Hex) -> OffsetCoord {
-        let col: i32 = hex.q();
-        let row: i32 = hex.r() + (hex.q() + offset * (hex.q() & 1)) / 2 as i32;
-        if offset != HEX_EVEN && offset != HEX_ODD {
-            panic!("offset must be EVEN (+1) or ODD (-1)");
-        }
-        return OffsetCoord { col, row };
+        raw_parity(offset).q_from_cube(hex)
     }
 
+    #[deprecated(note = "use `Parity::q_to_cube`")]
     pub fn q_to_cube(offset: i32, coord: OffsetCoord) -> Hex {
-        let q: i32 = coord.col;
-        let r: i32 = coord.row - (coord.col + offset * (coord.col & 1)) / 2 as i32;
-
-        if offset != HEX_EVEN && offset != HEX_ODD {
-            panic!("offset must be EVEN (+1) or ODD (-1)");
-        }
-
-        return Hex::new(q, r);
+        raw_parity(offset).q_to_cube(coord)
     }
 
+    #[deprecated(note = "use `Parity::r_from_cube`")]
     pub fn r_from_cube(offset: i32, hex: Hex) -> OffsetCoord {
-        let col: i32 = hex.q() + (hex.r() + offset * (hex.r() & 1)) / 2 as i32;
-        let row: i32 = hex.r();
-
-        if offset != HEX_EVEN && offset != HEX_ODD {
-            panic!("offset must be EVEN (+1) or ODD (-1)");
-        }
-
-        return OffsetCoord { col, row };
+        raw_parity(offset).r_from_cube(hex)
     }
 
+    #[deprecated(note = "use `Parity::r_to_cube`")]
     pub fn r_to_cube(offset: i32, coord: OffsetCoord) -> Hex {
-        let q: i32 = coord.col - (coord.row + offset * (coord.row & 1)) / 2 as i32;
-        let r: i32 = coord.row;
-
-        if offset != HEX_EVEN && offset != HEX_ODD {
-            panic!("offset must be EVEN (+1) or ODD (-1)");
-        }
-
-        return Hex::new(q, r);
+        raw_parity(offset).r_to_cube(coord)
     }
 }
 
+/// The integer conversions always panicked on anything but the two parities.
+fn raw_parity(offset: i32) -> Parity {
+    Parity::try_from(offset).expect("offset must be EVEN (+1) or ODD (-1)")
+}
+
 pub struct HexDoubled;
 
+#[allow(clippy::needless_return, clippy::unnecessary_cast)]
 impl HexDoubled {
     pub fn q_from_cube(h: Hex) -> DoubledCoord {
         let col: i32 = h.q();
@@ -74,6 +296,7 @@ impl HexDoubled {
         return DoubledCoord { col, row };
     }
 
+    #[allow(dead_code)]
     pub fn q_to_cube(h: DoubledCoord) -> Hex {
         let q: i32 = h.col;
         let r: i32 = (h.row - h.col) / 2 as i32;
@@ -86,6 +309,7 @@ impl HexDoubled {
         return DoubledCoord { col, row };
     }
 
+    #[allow(dead_code)]
     pub fn r_to_cube(h: DoubledCoord) -> Hex {
         let q: i32 = (h.col - h.row) / 2 as i32;
         let r: i32 = h.row;
@@ -0,0 +1,166 @@
+use std::collections::HashMap;
+
+use super::{Hex, HexCorner, HexEdge, TerrainId};
+
+/// Stable key of a [`Layer`], kept when layers are reordered.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct LayerId(pub u32);
+
+/// Anything of a map that holds a terrain: a cell, the edge between two cells or
+/// the corner between three.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Site {
+    Cell(Hex),
+    Edge(HexEdge),
+    Corner(HexCorner),
+}
+
+impl From<Hex> for Site {
+    fn from(value: Hex) -> Self {
+        Site::Cell(value)
+    }
+}
+
+impl From<HexEdge> for Site {
+    fn from(value: HexEdge) -> Self {
+        Site::Edge(value)
+    }
+}
+
+impl From<HexCorner> for Site {
+    fn from(value: HexCorner) -> Self {
+        Site::Corner(value)
+    }
+}
+
+impl Site {
+    /// The cells this site is, or lies between.
+    pub fn cells(self) -> Vec<Hex> {
+        match self {
+            Site::Cell(hex) => vec![hex],
+            Site::Edge(edge) => edge.cells().to_vec(),
+            Site::Corner(corner) => corner.cells().to_vec(),
+        }
+    }
+}
+
+/// One sheet of cells of a map. Layers are drawn bottom to top.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Layer {
+    id: LayerId,
+    pub name: String,
+    pub visible: bool,
+    /// Locked layers ignore painting tools.
+    pub locked: bool,
+    /// Between 0 (transparent) and 1 (opaque).
+    pub opacity: f32,
+    pub(super) data: HashMap<Hex, TerrainId>,
+    /// Walls, rivers and roads running between cells.
+    pub(super) edges: HashMap<HexEdge, TerrainId>,
+    /// Markers where cells meet.
+    pub(super) corners: HashMap<HexCorner, TerrainId>,
+}
+
+impl Layer {
+    pub(super) fn new(id: LayerId, name: impl Into<String>) -> Self {
+        Self {
+            id,
+            name: name.into(),
+            visible: true,
+            locked: false,
+            opacity: 1.0,
+            data: HashMap::new(),
+            edges: HashMap::new(),
+            corners: HashMap::new(),
+        }
+    }
+
+    pub fn id(&self) -> LayerId {
+        self.id
+    }
+
+    /// Whether tools may paint on this layer.
+    pub fn is_editable(&self) -> bool {
+        self.visible && !self.locked
+    }
+
+    pub fn cell(&self, cell: impl Into<Hex>) -> Option<TerrainId> {
+        self.data.get(&cell.into()).copied()
+    }
+
+    /// Paints or erases `cell`, returning its previous value.
+    pub fn set_cell(&mut self, cell: impl Into<Hex>, value: Option<TerrainId>) -> Option<TerrainId> {
+        let cell = cell.into();
+        match value {
+            Some(terrain) => self.data.insert(cell, terrain),
+            None => self.data.remove(&cell),
+        }
+    }
+
+    pub fn cells(&self) -> impl Iterator<Item = (Hex, TerrainId)> + '_ {
+        self.data.iter().map(|(hex, terrain)| (*hex, *terrain))
+    }
+
+    /// Painted cells and the cells along painted edges and corners, possibly repeated.
+    pub fn occupied_cells(&self) -> impl Iterator<Item = Hex> + '_ {
+        self.cells().map(|(hex, _)| hex)
+            .chain(self.edges().flat_map(|(edge, _)| edge.cells()))
+            .chain(self.corners().flat_map(|(corner, _)| corner.cells()))
+    }
+
+    pub fn edge(&self, edge: HexEdge) -> Option<TerrainId> {
+        self.edges.get(&edge).copied()
+    }
+
+    /// Paints or erases `edge`, returning its previous value.
+    pub fn set_edge(&mut self, edge: HexEdge, value: Option<TerrainId>) -> Option<TerrainId> {
+        match value {
+            Some(terrain) => self.edges.insert(edge, terrain),
+            None => self.edges.remove(&edge),
+        }
+    }
+
+    pub fn edges(&self) -> impl Iterator<Item = (HexEdge, TerrainId)> + '_ {
+        self.edges.iter().map(|(edge, terrain)| (*edge, *terrain))
+    }
+
+    pub fn corner(&self, corner: HexCorner) -> Option<TerrainId> {
+        self.corners.get(&corner).copied()
+    }
+
+    /// Paints or erases `corner`, returning its previous value.
+    pub fn set_corner(&mut self, corner: HexCorner, value: Option<TerrainId>) -> Option<TerrainId> {
+        match value {
+            Some(terrain) => self.corners.insert(corner, terrain),
+            None => self.corners.remove(&corner),
+        }
+    }
+
+    pub fn corners(&self) -> impl Iterator<Item = (HexCorner, TerrainId)> + '_ {
+        self.corners.iter().map(|(corner, terrain)| (*corner, *terrain))
+    }
+
+    pub fn site(&self, site: impl Into<Site>) -> Option<TerrainId> {
+        match site.into() {
+            Site::Cell(hex) => self.cell(hex),
+            Site::Edge(edge) => self.edge(edge),
+            Site::Corner(corner) => self.corner(corner),
+        }
+    }
+
+    /// Every painted cell, edge and corner.
+    pub fn sites(&self) -> impl Iterator<Item = (Site, TerrainId)> + '_ {
+        self.cells().map(|(hex, terrain)| (Site::Cell(hex), terrain))
+            .chain(self.edges().map(|(edge, terrain)| (Site::Edge(edge), terrain)))
+            .chain(self.corners().map(|(corner, terrain)| (Site::Corner(corner), terrain)))
+    }
+
+    /// Paints or erases any site, returning its previous value.
+    pub fn set_site(&mut self, site: impl Into<Site>, value: Option<TerrainId>) -> Option<TerrainId> {
+        match site.into() {
+            Site::Cell(hex) => self.set_cell(hex, value),
+            Site::Edge(edge) => self.set_edge(edge, value),
+            Site::Corner(corner) => self.set_corner(corner, value),
+        }
+    }
+}
@@ -0,0 +1,493 @@
+//! Native on-disk format for a [`Grid`].
+//!
+//! Maps are stored as UTF-8 JSON documents:
+//!
+//! ```json
+//! {
+//!   "format": "hex-editor-map",
+//!   "version": 4,
+//!   "layout": { "orientation": "pointy", "size": [0.1, 0.1], "origin": [0.0, 0.0] },
+//!   "terrains": [
+//!     { "id": 0, "name": "Grass", "color": "#19c864ff", "movement_cost": 1, "passable": true,
+//!       "opaque": false, "properties": { "biome": "temperate" } }
+//!   ],
+//!   "layers": [
+//!     { "name": "Terrain", "visible": true, "locked": false, "opacity": 1.0,
+//!       "cells": [ { "q": 0, "r": -1, "terrain": 0 } ],
+//!       "edges": [ { "q": 0, "r": -1, "side": "east", "terrain": 2 } ],
+//!       "corners": [ { "q": 0, "r": -1, "corner": "north", "terrain": 4 } ] }
+//!   ]
+//! }
+//! ```
+//!
+//! * `format` is always `"hex-editor-map"` and tells maps apart from other JSON files.
+//! * `version` is the schema version the file was written with. Files written by an
+//!   older release are upgraded in memory by the `MIGRATIONS` table before being
+//!   parsed; files from a newer release are rejected instead of being misread.
+//! * `layout.orientation` is `"pointy"` or `"flat"`, `size` and `origin` are `[x, y]`.
+//! * `terrains` is the map's terrain registry. Colors are unmultiplied sRGBA written
+//!   as `#rrggbbaa`. `opaque` and `properties` may be left out.
+//! * `layers` are listed bottom to top. `opacity` is between 0 and 1.
+//! * `cells` lists every cell of a layer by its axial `q`/`r` coordinates (`s` is
+//!   implied) and its payload, the `id` of one of the `terrains`.
+//! * `edges` and `corners` may be left out. An edge is the `side` of a cell, one of
+//!   `east`, `north-east`, `north-west`, `west`, `south-west` or `south-east`. A
+//!   corner is the `corner` of a cell between two sides, one of `east-north-east`,
+//!   `north`, `west-north-west`, `west-south-west`, `south` or `east-south-east`.
+//!   Every edge and corner can be named from any cell touching it, files are
+//!   written from the cell that lies west or south of it.
+//!
+//! Version 1 stored a `color` per cell instead of a terrain id. Loading it creates
+//! one terrain per distinct color. Versions 1 and 2 had a single top level `cells`
+//! list, which becomes a layer named `Terrain`. Version 3 had no edges or corners.
+
+use std::{
+    collections::BTreeMap, fmt, fs::{self, File}, io::{self, BufReader, Read, Write}, path::Path
+};
+
+use egui::Color32;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::{
+    Diagonal, Direction, Grid, Hex, HexCorner, HexEdge, Layer, LayerId, Layout, Point, Terrain, TerrainId, TerrainRegistry,
+    LAYOUT_ORIENTATION_FLAT, LAYOUT_ORIENTATION_POINTY
+};
+
+pub const FORMAT_TAG: &str = "hex-editor-map";
+pub const CURRENT_VERSION: u32 = 4;
+
+/// Upgrades a document from version `index + 1` to version `index + 2`.
+type Migration = fn(Value) -> Result<Value, MapFileError>;
+
+/// One entry per schema change, in order. Bumping `CURRENT_VERSION` requires
+/// appending the function that rewrites the previous version's document.
+const MIGRATIONS: [Migration; (CURRENT_VERSION - 1) as usize] = [
+    migrate_colors_to_terrains,
+    migrate_cells_to_layers,
+    migrate_add_edges,
+];
+
+#[derive(Debug)]
+pub enum MapFileError {
+    Io(io::Error),
+    /// The file is not valid JSON or does not match the schema of its version.
+    Corrupt(String),
+    /// The file is valid JSON but not a hex-editor map.
+    NotAMap,
+    /// The file was written by a newer release of the editor.
+    FutureVersion { found: u32, supported: u32 },
+    /// The map cannot be represented in the format.
+    Unsupported(String),
+}
+
+impl fmt::Display for MapFileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "could not access the map file: {error}"),
+            Self::Corrupt(reason) => write!(f, "the map file is corrupt: {reason}"),
+            Self::NotAMap => write!(f, "the file is not a hex-editor map"),
+            Self::FutureVersion { found, supported } => write!(
+                f,
+                "the map was saved with format version {found}, but this editor only reads up to version {supported}"
+            ),
+            Self::Unsupported(reason) => write!(f, "the map cannot be saved: {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for MapFileError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for MapFileError {
+    fn from(value: io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+impl From<serde_json::Error> for MapFileError {
+    fn from(value: serde_json::Error) -> Self {
+        if value.is_io() {
+            Self::Io(value.into())
+        } else {
+            Self::Corrupt(value.to_string())
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct MapFile {
+    format: String,
+    version: u32,
+    layout: LayoutRecord,
+    terrains: Vec<TerrainRecord>,
+    layers: Vec<LayerRecord>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct LayoutRecord {
+    orientation: OrientationRecord,
+    size: [f64; 2],
+    origin: [f64; 2],
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+enum OrientationRecord {
+    Pointy,
+    Flat,
+}
+
+#[derive(Serialize, Deserialize)]
+pub(super) struct TerrainRecord {
+    id: u16,
+    name: String,
+    color: String,
+    movement_cost: u32,
+    passable: bool,
+    #[serde(default)]
+    opaque: bool,
+    #[serde(default)]
+    properties: BTreeMap<String, String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct LayerRecord {
+    name: String,
+    visible: bool,
+    locked: bool,
+    opacity: f32,
+    cells: Vec<CellRecord>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    edges: Vec<EdgeRecord>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    corners: Vec<CornerRecord>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub(super) struct CellRecord {
+    pub q: i32,
+    pub r: i32,
+    pub terrain: u16,
+}
+
+#[derive(Serialize, Deserialize)]
+pub(super) struct EdgeRecord {
+    pub q: i32,
+    pub r: i32,
+    pub side: String,
+    pub terrain: u16,
+}
+
+#[derive(Serialize, Deserialize)]
+pub(super) struct CornerRecord {
+    pub q: i32,
+    pub r: i32,
+    pub corner: String,
+    pub terrain: u16,
+}
+
+impl EdgeRecord {
+    pub(super) fn direction(&self) -> Result<Direction, MapFileError> {
+        let Self { q, r, side, .. } = self;
+        Direction::iter()
+            .find(|direction| direction.name() == side)
+            .ok_or_else(|| MapFileError::Corrupt(format!("edge of cell ({q}, {r}) has an unknown side {side:?}")))
+    }
+}
+
+impl CornerRecord {
+    pub(super) fn diagonal(&self) -> Result<Diagonal, MapFileError> {
+        let Self { q, r, corner, .. } = self;
+        Diagonal::iter()
+            .find(|diagonal| diagonal.name() == corner)
+            .ok_or_else(|| MapFileError::Corrupt(format!("corner of cell ({q}, {r}) has an unknown name {corner:?}")))
+    }
+}
+
+impl TerrainRecord {
+    pub(super) fn new(TerrainId(id): TerrainId, terrain: &Terrain) -> Self {
+        Self {
+            id,
+            name: terrain.name.clone(),
+            color: color_to_string(terrain.color),
+            movement_cost: terrain.movement_cost,
+            passable: terrain.passable,
+            opaque: terrain.opaque,
+            properties: terrain.properties.clone(),
+        }
+    }
+
+    pub(super) fn into_terrain(self) -> Result<(TerrainId, Terrain), MapFileError> {
+        let TerrainRecord { id, name, color, movement_cost, passable, opaque, properties } = self;
+        let color = color_from_str(&color)
+            .ok_or_else(|| MapFileError::Corrupt(format!("invalid color {color:?} for terrain {id}")))?;
+        Ok((TerrainId(id), Terrain { name, color, movement_cost, passable, opaque, properties }))
+    }
+}
+
+impl Grid {
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), MapFileError> {
+        //Serialized first, so a refused map leaves the file it would replace alone
+        let mut buffer = Vec::new();
+        self.write_to(&mut buffer)?;
+        fs::write(path, buffer)?;
+        Ok(())
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, MapFileError> {
+        Self::read_from(BufReader::new(File::open(path)?))
+    }
+
+    pub fn write_to(&self, writer: impl Write) -> Result<(), MapFileError> {
+        let orientation = if self.layout.orientation == LAYOUT_ORIENTATION_POINTY {
+            OrientationRecord::Pointy
+        } else if self.layout.orientation == LAYOUT_ORIENTATION_FLAT {
+            OrientationRecord::Flat
+        } else {
+            return Err(MapFileError::Unsupported("custom layout orientation".into()));
+        };
+        let layout = LayoutRecord {
+            orientation,
+            size: [self.layout.size.x, self.layout.size.y],
+            origin: [self.layout.origin.x, self.layout.origin.y],
+        };
+
+        let terrains = self.terrains.iter()
+            .map(|(id, terrain)| TerrainRecord::new(id, terrain))
+            .collect();
+        let layers = self.layers.iter()
+            .map(|layer| {
+                let mut cells: Vec<_> = layer.cells()
+                    .map(|(hex, TerrainId(terrain))| CellRecord { q: hex.q(), r: hex.r(), terrain })
+                    .collect();
+                //Stable output keeps saved maps diffable
+                cells.sort_by_key(|cell| (cell.r, cell.q));
+                let mut edges: Vec<_> = layer.edges().collect();
+                edges.sort_by_key(|(edge, _)| (edge.hex().r(), edge.hex().q(), edge.direction().index()));
+                let mut corners: Vec<_> = layer.corners().collect();
+                corners.sort_by_key(|(corner, _)| (corner.hex().r(), corner.hex().q(), corner.diagonal().index()));
+                LayerRecord {
+                    name: layer.name.clone(),
+                    visible: layer.visible,
+                    locked: layer.locked,
+                    opacity: layer.opacity,
+                    cells,
+                    edges: edges.into_iter()
+                        .map(|(edge, TerrainId(terrain))| EdgeRecord {
+                            q: edge.hex().q(),
+                            r: edge.hex().r(),
+                            side: edge.direction().name().into(),
+                            terrain,
+                        })
+                        .collect(),
+                    corners: corners.into_iter()
+                        .map(|(corner, TerrainId(terrain))| CornerRecord {
+                            q: corner.hex().q(),
+                            r: corner.hex().r(),
+                            corner: corner.diagonal().name().into(),
+                            terrain,
+                        })
+                        .collect(),
+                }
+            })
+            .collect();
+
+        let file = MapFile {
+            format: FORMAT_TAG.into(),
+            version: CURRENT_VERSION,
+            layout,
+            terrains,
+            layers,
+        };
+        serde_json::to_writer_pretty(writer, &file)?;
+        Ok(())
+    }
+
+    pub fn read_from(mut reader: impl Read) -> Result<Self, MapFileError> {
+        let mut contents = String::new();
+        reader.read_to_string(&mut contents)?;
+        let document: Value = serde_json::from_str(&contents)?;
+
+        if document.get("format").and_then(Value::as_str) != Some(FORMAT_TAG) {
+            return Err(MapFileError::NotAMap);
+        }
+        let version = document.get("version")
+            .and_then(Value::as_u64)
+            .and_then(|version| u32::try_from(version).ok())
+            .filter(|version| *version >= 1)
+            .ok_or_else(|| MapFileError::Corrupt("missing or invalid version".into()))?;
+        if version > CURRENT_VERSION {
+            return Err(MapFileError::FutureVersion { found: version, supported: CURRENT_VERSION });
+        }
+
+        let document = MIGRATIONS[(version - 1) as usize..]
+            .iter()
+            .try_fold(document, |document, migrate| migrate(document))?;
+        let file: MapFile = serde_json::from_value(document)?;
+        file.into_grid()
+    }
+}
+
+impl MapFile {
+    fn into_grid(self) -> Result<Grid, MapFileError> {
+        let [size_x, size_y] = self.layout.size;
+        if !(size_x.is_finite() && size_y.is_finite() && size_x > 0.0 && size_y > 0.0) {
+            return Err(MapFileError::Corrupt("hex size must be positive".into()));
+        }
+        let layout = Layout {
+            orientation: match self.layout.orientation {
+                OrientationRecord::Pointy => LAYOUT_ORIENTATION_POINTY,
+                OrientationRecord::Flat => LAYOUT_ORIENTATION_FLAT,
+            },
+            size: Point { x: size_x, y: size_y },
+            origin: self.layout.origin.into(),
+        };
+
+        let mut terrains = TerrainRegistry::default();
+        for record in self.terrains {
+            let (id, terrain) = record.into_terrain()?;
+            if terrains.insert(id, terrain).is_some() {
+                return Err(MapFileError::Corrupt(format!("terrain {} is listed twice", id.0)));
+            }
+        }
+
+        if self.layers.is_empty() {
+            return Err(MapFileError::Corrupt("the map has no layers".into()));
+        }
+        let mut layers = Vec::with_capacity(self.layers.len());
+        for (id, record) in (0..).zip(self.layers) {
+            if !(0.0..=1.0).contains(&record.opacity) {
+                return Err(MapFileError::Corrupt(format!("layer {:?} has an invalid opacity", record.name)));
+            }
+            let mut layer = Layer::new(LayerId(id), record.name);
+            layer.visible = record.visible;
+            layer.locked = record.locked;
+            layer.opacity = record.opacity;
+            layer.data.reserve(record.cells.len());
+            for CellRecord { q, r, terrain } in record.cells {
+                if !terrains.contains(TerrainId(terrain)) {
+                    return Err(MapFileError::Corrupt(format!("cell ({q}, {r}) uses unknown terrain {terrain}")));
+                }
+                if layer.set_cell(checked_cell(q, r)?, Some(TerrainId(terrain))).is_some() {
+                    return Err(MapFileError::Corrupt(format!("cell ({q}, {r}) is listed twice in layer {:?}", layer.name)));
+                }
+            }
+            for edge in record.edges {
+                let direction = edge.direction()?;
+                let EdgeRecord { q, r, side, terrain } = edge;
+                if !terrains.contains(TerrainId(terrain)) {
+                    return Err(MapFileError::Corrupt(format!("{side} edge of cell ({q}, {r}) uses unknown terrain {terrain}")));
+                }
+                if layer.set_edge(HexEdge::new(checked_cell(q, r)?, direction), Some(TerrainId(terrain))).is_some() {
+                    return Err(MapFileError::Corrupt(format!("{side} edge of cell ({q}, {r}) is listed twice in layer {:?}", layer.name)));
+                }
+            }
+            for corner in record.corners {
+                let diagonal = corner.diagonal()?;
+                let CornerRecord { q, r, corner, terrain } = corner;
+                if !terrains.contains(TerrainId(terrain)) {
+                    return Err(MapFileError::Corrupt(format!("{corner} corner of cell ({q}, {r}) uses unknown terrain {terrain}")));
+                }
+                if layer.set_corner(HexCorner::new(checked_cell(q, r)?, diagonal), Some(TerrainId(terrain))).is_some() {
+                    return Err(MapFileError::Corrupt(format!("{corner} corner of cell ({q}, {r}) is listed twice in layer {:?}", layer.name)));
+                }
+            }
+            layers.push(layer);
+        }
+        Ok(Grid { layout, next_layer_id: layers.len() as u32, layers, active_layer: 0, terrains })
+    }
+}
+
+/// Cell `(q, r)` read from a file. Cells whose cube coordinates, or those of their
+/// neighbors, do not fit in an `i32` are refused rather than overflowing later.
+pub(super) fn checked_cell(q: i32, r: i32) -> Result<Hex, MapFileError> {
+    Hex::checked_new(q, r)
+        .filter(|hex| Direction::iter().all(|direction| hex.checked_add(direction.offset()).is_some()))
+        .ok_or_else(|| MapFileError::Corrupt(format!("cell ({q}, {r}) is out of range")))
+}
+
+/// Version 1 to 2: cell colors become references to generated terrains.
+fn migrate_colors_to_terrains(mut document: Value) -> Result<Value, MapFileError> {
+    let corrupt = |reason: &str| MapFileError::Corrupt(reason.into());
+    let cells = document.get_mut("cells")
+        .and_then(Value::as_array_mut)
+        .ok_or_else(|| corrupt("missing cells"))?;
+
+    let mut terrain_ids: Vec<String> = Vec::new();
+    for cell in cells.iter_mut() {
+        let cell = cell.as_object_mut().ok_or_else(|| corrupt("cell is not an object"))?;
+        let color = match cell.remove("color") {
+            Some(Value::String(color)) => color,
+            _ => return Err(corrupt("cell has no color")),
+        };
+        let id = match terrain_ids.iter().position(|known| *known == color) {
+            Some(id) => id,
+            None => {
+                terrain_ids.push(color);
+                terrain_ids.len() - 1
+            }
+        };
+        cell.insert("terrain".into(), id.into());
+    }
+    if terrain_ids.len() > usize::from(u16::MAX) + 1 {
+        return Err(corrupt("too many distinct colors"));
+    }
+
+    let terrains: Vec<Value> = terrain_ids.into_iter()
+        .enumerate()
+        .map(|(id, color)| serde_json::json!({
+            "id": id,
+            "name": format!("Color {color}"),
+            "color": color,
+            "movement_cost": 1,
+            "passable": true,
+        }))
+        .collect();
+    document["terrains"] = terrains.into();
+    document["version"] = 2.into();
+    Ok(document)
+}
+
+/// Version 2 to 3: the cell list becomes the only layer.
+fn migrate_cells_to_layers(mut document: Value) -> Result<Value, MapFileError> {
+    let object = document.as_object_mut().ok_or_else(|| MapFileError::Corrupt("map is not an object".into()))?;
+    let cells = object.remove("cells").ok_or_else(|| MapFileError::Corrupt("missing cells".into()))?;
+    let layer = serde_json::json!({
+        "name": "Terrain",
+        "visible": true,
+        "locked": false,
+        "opacity": 1.0,
+        "cells": cells,
+    });
+    object.insert("layers".into(), Value::Array(vec![layer]));
+    object.insert("version".into(), 3.into());
+    Ok(document)
+}
+
+/// Version 3 to 4: layers may list edges and corners, which older maps have none of.
+fn migrate_add_edges(mut document: Value) -> Result<Value, MapFileError> {
+    let object = document.as_object_mut().ok_or_else(|| MapFileError::Corrupt("map is not an object".into()))?;
+    object.insert("version".into(), 4.into());
+    Ok(document)
+}
+
+fn color_to_string(color: Color32) -> String {
+    let [r, g, b, a] = color.to_srgba_unmultiplied();
+    format!("#{r:02x}{g:02x}{b:02x}{a:02x}")
+}
+
+pub fn color_from_str(text: &str) -> Option<Color32> {
+    let digits = text.strip_prefix('#')?;
+    if digits.len() != 8 || !digits.is_ascii() {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(&digits[2 * i..2 * i + 2], 16).ok();
+    Some(Color32::from_rgba_unmultiplied(channel(0)?, channel(1)?, channel(2)?, channel(3)?))
+}
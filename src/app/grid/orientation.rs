@@ -0,0 +1,64 @@
+use super::{Grid, Hex, HexCorner, HexEdge, Layout, Parity, Point, LAYOUT_ORIENTATION_FLAT, LAYOUT_ORIENTATION_POINTY};
+
+/// Which way the cells of a map point.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum HexOrientation {
+    /// A corner at the top, cells form rows.
+    Pointy,
+    /// An edge at the top, cells form columns.
+    Flat,
+}
+
+impl Grid {
+    pub fn layout(&self) -> Layout {
+        self.layout
+    }
+
+    pub fn orientation(&self) -> HexOrientation {
+        if self.layout.orientation == LAYOUT_ORIENTATION_FLAT {
+            HexOrientation::Flat
+        } else {
+            HexOrientation::Pointy
+        }
+    }
+
+    /// Sets the width and height of a cell, as the distance from its center to its
+    /// corners on each axis. Sizes that are not positive are ignored.
+    pub fn set_hex_size(&mut self, size: Point) {
+        if size.x > 0.0 && size.y > 0.0 && size.x.is_finite() && size.y.is_finite() {
+            self.layout.size = size;
+        }
+    }
+
+    /// Switches the orientation of the map. Cells keep their coordinates, which turns
+    /// the picture by 30°, unless `relayout` is set: then every cell moves to keep its
+    /// column and row in offset coordinates, odd-r on pointy maps and odd-q on flat
+    /// ones, so rectangular maps stay rectangular. Edges and corners then follow the
+    /// cell owning them and keep their side of it.
+    pub fn set_orientation(&mut self, orientation: HexOrientation, relayout: bool) {
+        let current = self.orientation();
+        if orientation == current {
+            return;
+        }
+        self.layout.orientation = match orientation {
+            HexOrientation::Pointy => LAYOUT_ORIENTATION_POINTY,
+            HexOrientation::Flat => LAYOUT_ORIENTATION_FLAT,
+        };
+        if !relayout {
+            return;
+        }
+        let convert = |hex: Hex| match current {
+            HexOrientation::Pointy => Parity::Odd.q_to_cube(Parity::Odd.r_from_cube(hex)),
+            HexOrientation::Flat => Parity::Odd.r_to_cube(Parity::Odd.q_from_cube(hex)),
+        };
+        for layer in &mut self.layers {
+            layer.data = layer.data.drain().map(|(hex, terrain)| (convert(hex), terrain)).collect();
+            layer.edges = layer.edges.drain()
+                .map(|(edge, terrain)| (HexEdge::new(convert(edge.hex()), edge.direction()), terrain))
+                .collect();
+            layer.corners = layer.corners.drain()
+                .map(|(corner, terrain)| (HexCorner::new(convert(corner.hex()), corner.diagonal()), terrain))
+                .collect();
+        }
+    }
+}
@@ -0,0 +1,121 @@
+use std::{
+    cmp::Reverse, collections::{BinaryHeap, HashMap}
+};
+
+use super::{Grid, Hex, HexIterators, HexUtility};
+
+/// A route between two cells and the total cost of walking it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Path {
+    /// Both endpoints included.
+    pub cells: Vec<Hex>,
+    /// Sum of the costs of entering every cell after the first.
+    pub cost: u32,
+}
+
+/// Open list entry ordered by priority, ties broken by coordinates so runs are repeatable.
+type Frontier = BinaryHeap<Reverse<(u32, i32, i32)>>;
+
+impl Grid {
+    /// Cost of entering `hex`, `None` when it cannot be entered.
+    ///
+    /// Every visible layer with a cell at `hex` counts, so a marker painted over a
+    /// wall does not open it: the cell cannot be entered if any of them is impassable,
+    /// and costs the most any of them does otherwise. Cells that are empty on every
+    /// visible layer are off the map and cannot be entered either.
+    pub fn movement_cost(&self, hex: Hex) -> Option<u32> {
+        self.layers.iter()
+            .filter(|layer| layer.visible)
+            .filter_map(|layer| layer.cell(hex))
+            .map(|terrain| self.terrains.get(terrain)
+                .filter(|terrain| terrain.passable)
+                .map(|terrain| terrain.movement_cost))
+            .reduce(|cost, other| Some(cost?.max(other?)))?
+    }
+
+    /// Cheapest route from `from` to `to` (A*), `None` if `to` cannot be reached.
+    pub fn find_path(&self, from: Hex, to: Hex) -> Option<Path> {
+        if from == to {
+            return Some(Path { cells: vec![from], cost: 0 });
+        }
+        self.movement_cost(to)?;
+        //Scaling the hex distance by the cheapest terrain keeps the heuristic admissible
+        let min_cost = self.terrains.iter()
+            .filter(|(_, terrain)| terrain.passable)
+            .map(|(_, terrain)| terrain.movement_cost)
+            .min()?;
+        let heuristic = |hex: Hex| hex.distance(to) as u32 * min_cost;
+
+        let mut costs = HashMap::from([(from, 0)]);
+        let mut came_from = HashMap::new();
+        let mut frontier = Frontier::new();
+        frontier.push(Reverse((heuristic(from), from.q(), from.r())));
+        while let Some(Reverse((priority, q, r))) = frontier.pop() {
+            let hex = Hex::new(q, r);
+            let cost = costs[&hex];
+            if priority > cost + heuristic(hex) {
+                //Stale entry, the cell was reached cheaper since it was queued
+                continue;
+            }
+            if hex == to {
+                return Some(Path { cells: walk_back(&came_from, from, to), cost });
+            }
+            for neighbor in hex.neighbors() {
+                let Some(step) = self.movement_cost(neighbor) else {
+                    continue;
+                };
+                let next = cost + step;
+                if costs.get(&neighbor).is_none_or(|known| next < *known) {
+                    costs.insert(neighbor, next);
+                    came_from.insert(neighbor, hex);
+                    frontier.push(Reverse((next + heuristic(neighbor), neighbor.q(), neighbor.r())));
+                }
+            }
+        }
+        None
+    }
+
+    /// Cost of the cheapest route from `from` to every cell it can reach (Dijkstra),
+    /// `from` included. Cells costing more than `budget` are left out.
+    pub fn cost_field(&self, from: Hex, budget: Option<u32>) -> HashMap<Hex, u32> {
+        let mut costs = HashMap::from([(from, 0)]);
+        let mut frontier = Frontier::new();
+        frontier.push(Reverse((0, from.q(), from.r())));
+        while let Some(Reverse((cost, q, r))) = frontier.pop() {
+            let hex = Hex::new(q, r);
+            if cost > costs[&hex] {
+                continue;
+            }
+            for neighbor in hex.neighbors() {
+                let Some(step) = self.movement_cost(neighbor) else {
+                    continue;
+                };
+                let next = cost + step;
+                if budget.is_some_and(|budget| next > budget) {
+                    continue;
+                }
+                if costs.get(&neighbor).is_none_or(|known| next < *known) {
+                    costs.insert(neighbor, next);
+                    frontier.push(Reverse((next, neighbor.q(), neighbor.r())));
+                }
+            }
+        }
+        costs
+    }
+
+    /// Cells a unit standing on `from` can move to spending at most `budget`.
+    pub fn reachable(&self, from: Hex, budget: u32) -> Vec<Hex> {
+        self.cost_field(from, Some(budget)).into_keys().collect()
+    }
+}
+
+fn walk_back(came_from: &HashMap<Hex, Hex>, from: Hex, to: Hex) -> Vec<Hex> {
+    let mut cells = vec![to];
+    let mut hex = to;
+    while hex != from {
+        hex = came_from[&hex];
+        cells.push(hex);
+    }
+    cells.reverse();
+    cells
+}
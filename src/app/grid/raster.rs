@@ -0,0 +1,242 @@
+//! Maps traced from pictures, like sketches or scanned boards.
+//!
+//! The image is laid over the map with its top left corner at `origin` and every
+//! pixel `scale` world units wide. Each cell whose center falls on the image takes
+//! a color from the pixels inside it, then becomes the terrain of that color.
+
+use std::{collections::HashMap, fmt, io, path::Path};
+
+use egui::Color32;
+use image::{ImageReader, RgbaImage};
+
+use super::{point_in_polygon, Grid, Layout, LayoutTool, Point, Terrain, TerrainId, TerrainRegistry};
+
+/// How a cell picks its color from the pixels under it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Sampling {
+    /// The pixel under the center of the cell.
+    Center,
+    /// The mean of the pixels inside the cell.
+    Average,
+    /// The most frequent color inside the cell, after quantizing. Best with a
+    /// palette, as scans and photos rarely repeat exact colors.
+    Majority,
+}
+
+impl Sampling {
+    pub const ALL: [Sampling; 3] = [Sampling::Center, Sampling::Average, Sampling::Majority];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Sampling::Center => "Center",
+            Sampling::Average => "Average",
+            Sampling::Majority => "Majority",
+        }
+    }
+}
+
+/// How [`Grid::from_image`] lays an image over the map and reads it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RasterImport {
+    /// World position of the top left corner of the image.
+    pub origin: Point,
+    /// World units per pixel.
+    pub scale: f64,
+    pub sampling: Sampling,
+    /// Terrains cells snap to, the one with the nearest color. When empty, every
+    /// color found becomes a terrain of its own.
+    pub palette: Vec<Terrain>,
+    /// Cells whose color is more transparent than this stay empty.
+    pub min_alpha: u8,
+}
+
+impl Default for RasterImport {
+    fn default() -> Self {
+        Self {
+            origin: Point { x: 0.0, y: 0.0 },
+            scale: 0.01,
+            sampling: Sampling::Average,
+            palette: Vec::new(),
+            min_alpha: 128,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum RasterError {
+    Io(io::Error),
+    /// The file is not an image in a supported format.
+    Decode(String),
+    /// Without a palette, the image has more colors than a map can have terrains.
+    TooManyColors,
+}
+
+impl fmt::Display for RasterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "could not read the image: {error}"),
+            Self::Decode(reason) => write!(f, "could not decode the image: {reason}"),
+            Self::TooManyColors => write!(f, "the image has too many colors, import it with a palette"),
+        }
+    }
+}
+
+impl std::error::Error for RasterError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for RasterError {
+    fn from(value: io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+/// Reads an image in any format the `image` crate was built with, going by its
+/// contents rather than its extension.
+pub fn load_image(path: impl AsRef<Path>) -> Result<RgbaImage, RasterError> {
+    let reader = ImageReader::open(path)?.with_guessed_format()?;
+    let image = reader.decode().map_err(|error| RasterError::Decode(error.to_string()))?;
+    Ok(image.into_rgba8())
+}
+
+impl RasterImport {
+    /// Sets `scale` so the image spans `cells` cells of `layout` horizontally.
+    pub fn fit_width(&mut self, image: &RgbaImage, layout: Layout, cells: f64) {
+        let cell_width = LayoutTool::hex_to_pixel(layout, (1, 0).into()).x - LayoutTool::hex_to_pixel(layout, (0, 0).into()).x;
+        if image.width() > 0 && cells > 0.0 {
+            self.scale = cells * cell_width.abs() / image.width() as f64;
+        }
+    }
+
+    fn to_image(&self, Point { x, y }: Point) -> Point {
+        Point { x: (x - self.origin.x) / self.scale, y: (y - self.origin.y) / self.scale }
+    }
+
+    /// The palette color nearest to `color`, by distance in sRGB.
+    fn quantize(&self, color: Color32) -> Option<usize> {
+        let distance = |other: Color32| {
+            [color.r() as i32 - other.r() as i32, color.g() as i32 - other.g() as i32, color.b() as i32 - other.b() as i32]
+                .iter()
+                .map(|channel| channel * channel)
+                .sum::<i32>()
+        };
+        (0..self.palette.len()).min_by_key(|index| distance(self.palette[*index].color))
+    }
+}
+
+impl Grid {
+    /// Builds a map with `layout` by sampling the cells `image` covers.
+    pub fn from_image(layout: Layout, image: &RgbaImage, options: &RasterImport) -> Result<Grid, RasterError> {
+        let mut grid = Grid { layout, terrains: TerrainRegistry::default(), ..Default::default() };
+        if !(options.scale > 0.0 && options.scale.is_finite()) || image.width() == 0 || image.height() == 0 {
+            return Ok(grid);
+        }
+        let size = Point { x: image.width() as f64 * options.scale, y: image.height() as f64 * options.scale };
+        let max = Point { x: options.origin.x + size.x, y: options.origin.y + size.y };
+        let pixel = |Point { x, y }: Point| {
+            (x >= 0.0 && y >= 0.0 && x < image.width() as f64 && y < image.height() as f64).then(|| {
+                let [r, g, b, a] = image.get_pixel(x as u32, y as u32).0;
+                Color32::from_rgba_unmultiplied(r, g, b, a)
+            })
+        };
+
+        //Terrains made so far, by sampled color or by palette entry
+        let mut colors: HashMap<Color32, TerrainId> = HashMap::new();
+        let mut terrains: HashMap<usize, TerrainId> = HashMap::new();
+        for hex in grid.positions_near_rect(options.origin, max) {
+            let Some(center) = pixel(options.to_image(LayoutTool::hex_to_pixel(layout, hex))) else {
+                continue;
+            };
+            let inside = || {
+                let corners: Vec<_> = LayoutTool::polygon_corners(layout, hex).into_iter()
+                    .map(|corner| options.to_image(corner))
+                    .collect();
+                let x_range = corners.iter().fold((f64::MAX, f64::MIN), |(min, max), corner| (min.min(corner.x), max.max(corner.x)));
+                let y_range = corners.iter().fold((f64::MAX, f64::MIN), |(min, max), corner| (min.min(corner.y), max.max(corner.y)));
+                let mut pixels = Vec::new();
+                for y in (y_range.0.floor().max(0.0) as u32)..(y_range.1.ceil().min(image.height() as f64) as u32) {
+                    for x in (x_range.0.floor().max(0.0) as u32)..(x_range.1.ceil().min(image.width() as f64) as u32) {
+                        let middle = Point { x: x as f64 + 0.5, y: y as f64 + 0.5 };
+                        if point_in_polygon(middle, &corners) {
+                            pixels.extend(pixel(middle));
+                        }
+                    }
+                }
+                //Cells smaller than a pixel hold no pixel centers
+                if pixels.is_empty() {
+                    pixels.push(center);
+                }
+                pixels
+            };
+            let color = match options.sampling {
+                Sampling::Center => center,
+                Sampling::Average => average(&inside()),
+                Sampling::Majority => {
+                    let mut counts: HashMap<Color32, usize> = HashMap::new();
+                    for color in inside() {
+                        let color = match options.quantize(color) {
+                            //Transparent pixels vote for leaving the cell empty
+                            _ if color.a() < options.min_alpha => Color32::TRANSPARENT,
+                            Some(index) => options.palette[index].color,
+                            None => color,
+                        };
+                        *counts.entry(color).or_default() += 1;
+                    }
+                    //Ties go to the lowest color value so the result does not depend on hashing
+                    counts.into_iter()
+                        .max_by_key(|(color, count)| (*count, std::cmp::Reverse(color.to_array())))
+                        .map(|(color, _)| color)
+                        .unwrap_or(center)
+                }
+            };
+            if color.a() < options.min_alpha {
+                continue;
+            }
+            let terrain = match options.quantize(color) {
+                Some(index) => match terrains.get(&index) {
+                    Some(terrain) => *terrain,
+                    None => {
+                        let terrain = grid.terrains.add(options.palette[index].clone()).ok_or(RasterError::TooManyColors)?;
+                        terrains.insert(index, terrain);
+                        terrain
+                    }
+                },
+                None => match colors.get(&color) {
+                    Some(terrain) => *terrain,
+                    None => {
+                        let [r, g, b, _] = color.to_srgba_unmultiplied();
+                        let terrain = grid.terrains.add(Terrain::new(format!("#{r:02x}{g:02x}{b:02x}"), color.to_opaque()))
+                            .ok_or(RasterError::TooManyColors)?;
+                        colors.insert(color, terrain);
+                        terrain
+                    }
+                },
+            };
+            grid.paint_cell(hex, terrain);
+        }
+        Ok(grid)
+    }
+}
+
+/// Mean color, weighing each pixel by its opacity.
+fn average(pixels: &[Color32]) -> Color32 {
+    let mut sums = [0u64; 4];
+    for pixel in pixels {
+        let [r, g, b, a] = pixel.to_srgba_unmultiplied().map(u64::from);
+        sums[0] += r * a;
+        sums[1] += g * a;
+        sums[2] += b * a;
+        sums[3] += a;
+    }
+    if sums[3] == 0 {
+        return Color32::TRANSPARENT;
+    }
+    let channel = |sum: u64| (sum as f64 / sums[3] as f64).round() as u8;
+    let alpha = (sums[3] as f64 / pixels.len() as f64).round() as u8;
+    Color32::from_rgba_unmultiplied(channel(sums[0]), channel(sums[1]), channel(sums[2]), alpha)
+}
@@ -0,0 +1,139 @@
+use std::collections::HashSet;
+
+use super::{FillOptions, Grid, Hex, HexIterators, HexMath, HexRange, HexRound, LayoutTool, Point};
+
+/// How newly picked cells combine with the current selection.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SelectionOp {
+    Replace,
+    Add,
+    Subtract,
+}
+
+/// A set of cell positions the editing commands act on.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Selection {
+    cells: HashSet<Hex>,
+}
+
+impl Selection {
+    pub fn apply(&mut self, op: SelectionOp, cells: impl IntoIterator<Item = Hex>) {
+        match op {
+            SelectionOp::Replace => self.cells = cells.into_iter().collect(),
+            SelectionOp::Add => self.cells.extend(cells),
+            SelectionOp::Subtract => for hex in cells {
+                self.cells.remove(&hex);
+            },
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.cells.clear();
+    }
+
+    pub fn contains(&self, hex: Hex) -> bool {
+        self.cells.contains(&hex)
+    }
+
+    pub fn cells(&self) -> impl Iterator<Item = Hex> + '_ {
+        self.cells.iter().copied()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+
+    /// Shifts every selected position by `offset`.
+    pub fn translate(&mut self, offset: Hex) {
+        self.cells = self.cells.iter().map(|hex| hex.add(offset)).collect();
+    }
+}
+
+impl Grid {
+    /// Cells of the active layer whose centers lie inside the world space rectangle
+    /// spanned by `a` and `b`.
+    pub fn cells_in_rect(&self, a: Point, b: Point) -> Vec<Hex> {
+        let (min, max) = (Point { x: a.x.min(b.x), y: a.y.min(b.y) }, Point { x: a.x.max(b.x), y: a.y.max(b.y) });
+        self.cells_near_rect(min, max).into_iter()
+            .filter(|hex| {
+                let Point { x, y } = self.cell_center(*hex);
+                min.x <= x && x <= max.x && min.y <= y && y <= max.y
+            })
+            .collect()
+    }
+
+    /// Cells of the active layer whose centers lie inside the world space polygon `lasso`.
+    pub fn cells_in_lasso(&self, lasso: &[Point]) -> Vec<Hex> {
+        let Some(first) = lasso.first() else {
+            return Vec::new();
+        };
+        let (min, max) = lasso.iter().fold((*first, *first), |(min, max), point| (
+            Point { x: min.x.min(point.x), y: min.y.min(point.y) },
+            Point { x: max.x.max(point.x), y: max.y.max(point.y) },
+        ));
+        self.cells_near_rect(min, max).into_iter()
+            .filter(|hex| point_in_polygon(self.cell_center(*hex), lasso))
+            .collect()
+    }
+
+    /// Cells of the active layer at most `radius` steps from `center`.
+    pub fn cells_in_range(&self, center: Hex, radius: i32) -> Vec<Hex> {
+        let layer = self.active_layer();
+        center.range(radius).filter(|hex| layer.cell(*hex).is_some()).collect()
+    }
+
+    /// Cells of the active layer matching the one at `start`: the connected region
+    /// like the bucket fills, or every match on the layer when `contiguous` is off.
+    pub fn magic_wand(&self, start: Hex, options: &FillOptions, contiguous: bool) -> Vec<Hex> {
+        let layer = self.active_layer();
+        let Some(target) = layer.cell(start) else {
+            return Vec::new();
+        };
+        if contiguous {
+            return self.flood_fill(start, options);
+        }
+        layer.cells()
+            .filter(|(_, terrain)| self.fill_matches(target, *terrain, options))
+            .map(|(hex, _)| hex)
+            .collect()
+    }
+
+    /// Every position, painted or not, in the cube coordinate box around the world
+    /// space rectangle spanned by `min` and `max`, a superset of those centered inside it.
+    pub fn positions_near_rect(&self, min: Point, max: Point) -> HexRange {
+        let corners = [min, Point { x: max.x, y: min.y }, max, Point { x: min.x, y: max.y }]
+            .map(|corner| LayoutTool::pixel_to_hex(self.layout, corner).round());
+        let bound = |coord: fn(&Hex) -> i32| {
+            let values = corners.map(|hex| coord(&hex));
+            (values.iter().min().unwrap() - 1, values.iter().max().unwrap() + 1)
+        };
+        let (q, r, s) = (bound(Hex::q), bound(Hex::r), bound(Hex::s));
+        HexRange::new([q.0, r.0, s.0], [q.1, r.1, s.1])
+    }
+
+    /// Stored cells of the active layer among [`Grid::positions_near_rect`].
+    fn cells_near_rect(&self, min: Point, max: Point) -> Vec<Hex> {
+        let range = self.positions_near_rect(min, max);
+        let layer = self.active_layer();
+        //Zoomed out, the box can hold far more positions than the layer has cells
+        if range.span_area() > layer.data.len() as i64 {
+            return layer.cells().map(|(hex, _)| hex).filter(|hex| range.contains(*hex)).collect();
+        }
+        range.filter(|hex| layer.cell(*hex).is_some()).collect()
+    }
+}
+
+/// Even-odd rule.
+pub(super) fn point_in_polygon(point: Point, polygon: &[Point]) -> bool {
+    let mut inside = false;
+    let mut previous = polygon[polygon.len() - 1];
+    for current in polygon {
+        if (current.y > point.y) != (previous.y > point.y)
+            && point.x < (previous.x - current.x) * (point.y - current.y) / (previous.y - current.y) + current.x
+        {
+            inside = !inside;
+        }
+        previous = *current;
+    }
+    inside
+}
@@ -0,0 +1,18 @@
+//! Cells covered by the shapes that are not plain hex ranges, shared by the shape
+//! constructors of [`Grid`](super::Grid) and the shape drawing tools.
+
+use super::Hex;
+
+/// Every cell with `min.q <= q <= max.q` and `min.r <= r <= max.r`.
+pub fn rhombus(min: Hex, max: Hex) -> Vec<Hex> {
+    (min.q() ..= max.q())
+        .flat_map(|q| (min.r() ..= max.r()).map(move |r| Hex::new(q, r)))
+        .collect()
+}
+
+/// Triangle with its corner on `min` and `size + 1` cells per side.
+pub fn triangle(min: Hex, size: i32) -> Vec<Hex> {
+    (min.q() ..= min.q() + size)
+        .flat_map(|q| (min.r() ..= min.r() + size - (q - min.q())).map(move |r| Hex::new(q, r)))
+        .collect()
+}
@@ -0,0 +1,456 @@
+//! Maps in the style of an example, by wave function collapse.
+//!
+//! [`Rules`] say which terrains may lie on each of the six sides of each other and
+//! how often each one is picked. They are learned from painted cells or read from
+//! a table. [`Grid::synthesize`] then fills a region one cell at a time: the cell
+//! with the fewest options left takes one of them at random, weighted by
+//! frequency, and its neighbors drop the options that no longer fit. When a cell
+//! runs out of options, the last choice is undone and another one tried.
+//!
+//! Rule tables are text with one entry per line, `#` starting a comment:
+//!
+//! ```text
+//! # TERRAIN, SIDE, NEIGHBOR: NEIGHBOR may lie on SIDE of TERRAIN, SIDE being a
+//! # direction like north-east, or any
+//! Grass, any, Grass
+//! Grass, east, Water
+//! # TERRAIN, WEIGHT: how often TERRAIN is picked, 1 unless given
+//! Grass, 4
+//! ```
+
+use std::{collections::{HashMap, HashSet}, fmt, fs, io, path::Path};
+
+use super::{Direction, Grid, Hex, Rng, TerrainId, TerrainRegistry};
+
+/// Which terrains may be neighbors, and how common each one is.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Rules {
+    /// Terrains the rules know, in the order the tables below use.
+    terrains: Vec<TerrainId>,
+    weights: Vec<f64>,
+    /// `allowed[direction][a][b]` when terrain `b` may lie on that side of terrain `a`.
+    allowed: [Vec<Vec<bool>>; 6],
+}
+
+impl Rules {
+    /// Learns from the painted `cells` of the active layer of `grid`. Every pair
+    /// of them side by side becomes a rule, and every cell adds one to the weight
+    /// of its terrain.
+    pub fn learn(grid: &Grid, cells: impl IntoIterator<Item = Hex>) -> Rules {
+        let mut cells: Vec<_> = cells.into_iter().filter(|hex| grid.cell(*hex).is_some()).collect();
+        cells.sort_by_key(|hex| (hex.r(), hex.q()));
+        cells.dedup();
+        let example: HashSet<_> = cells.iter().copied().collect();
+        let mut rules = Rules::default();
+        for hex in cells {
+            let terrain = grid.cell(hex).expect("Unpainted cell in the example!");
+            let index = rules.index(terrain);
+            rules.weights[index] += 1.0;
+            for direction in Direction::iter() {
+                let neighbor = hex.neighbor(direction);
+                if let Some(other) = grid.cell(neighbor).filter(|_| example.contains(&neighbor)) {
+                    rules.allow(terrain, direction, other);
+                }
+            }
+        }
+        rules
+    }
+
+    /// Reads a rule table, naming terrains of `terrains` regardless of case.
+    pub fn parse(text: &str, terrains: &TerrainRegistry) -> Result<Rules, RulesError> {
+        let mut rules = Rules::default();
+        let mut weights = HashMap::new();
+        for (number, line) in text.lines().enumerate() {
+            let invalid = |reason: String| RulesError::Invalid { line: number + 1, reason };
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let terrain = |name: &str| terrains.iter()
+                .find(|(_, terrain)| terrain.name.eq_ignore_ascii_case(name))
+                .map(|(id, _)| id)
+                .ok_or_else(|| invalid(format!("unknown terrain \"{name}\"")));
+            let fields: Vec<_> = line.split(',').map(str::trim).collect();
+            match fields[..] {
+                [name, weight] => {
+                    let weight: f64 = weight.parse()
+                        .ok()
+                        .filter(|weight: &f64| *weight >= 0.0 && weight.is_finite())
+                        .ok_or_else(|| invalid(format!("weights must be numbers from 0 up, found \"{weight}\"")))?;
+                    let terrain = terrain(name)?;
+                    rules.index(terrain);
+                    weights.insert(terrain, weight);
+                }
+                [name, side, neighbor] => {
+                    let (terrain, neighbor) = (terrain(name)?, terrain(neighbor)?);
+                    if side.eq_ignore_ascii_case("any") {
+                        for direction in Direction::iter() {
+                            rules.allow(terrain, direction, neighbor);
+                        }
+                    } else {
+                        let direction = Direction::iter()
+                            .find(|direction| direction.name().eq_ignore_ascii_case(side))
+                            .ok_or_else(|| invalid(format!("unknown side \"{side}\"")))?;
+                        rules.allow(terrain, direction, neighbor);
+                    }
+                }
+                _ => return Err(invalid("expected TERRAIN, WEIGHT or TERRAIN, SIDE, NEIGHBOR".into())),
+            }
+        }
+        for (index, terrain) in rules.terrains.iter().enumerate() {
+            rules.weights[index] = weights.get(terrain).copied().unwrap_or(1.0);
+        }
+        Ok(rules)
+    }
+
+    pub fn load(path: impl AsRef<Path>, terrains: &TerrainRegistry) -> Result<Rules, RulesError> {
+        Self::parse(&fs::read_to_string(path)?, terrains)
+    }
+
+    /// Lets `neighbor` lie on the `direction` side of `terrain`, and so `terrain`
+    /// on the opposite side of `neighbor`.
+    pub fn allow(&mut self, terrain: TerrainId, direction: Direction, neighbor: TerrainId) {
+        let (a, b) = (self.index(terrain), self.index(neighbor));
+        self.allowed[direction.index()][a][b] = true;
+        self.allowed[direction.opposite().index()][b][a] = true;
+    }
+
+    /// Number of terrains the rules know.
+    pub fn len(&self) -> usize {
+        self.terrains.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.terrains.is_empty()
+    }
+
+    /// Number of allowed neighbors, counting each pair once per side.
+    pub fn adjacencies(&self) -> usize {
+        self.allowed.iter().flatten().flatten().filter(|allowed| **allowed).count() / 2
+    }
+
+    /// Position of `terrain` in the tables, adding it if new.
+    fn index(&mut self, terrain: TerrainId) -> usize {
+        if let Some(index) = self.terrains.iter().position(|known| *known == terrain) {
+            return index;
+        }
+        self.terrains.push(terrain);
+        self.weights.push(0.0);
+        for table in &mut self.allowed {
+            for row in table.iter_mut() {
+                row.push(false);
+            }
+            table.push(vec![false; self.terrains.len()]);
+        }
+        self.terrains.len() - 1
+    }
+}
+
+#[derive(Debug)]
+pub enum RulesError {
+    Io(io::Error),
+    Invalid { line: usize, reason: String },
+}
+
+impl fmt::Display for RulesError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "could not read the rules: {error}"),
+            Self::Invalid { line, reason } => write!(f, "line {line}: {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for RulesError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for RulesError {
+    fn from(value: io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+/// Settings of [`Grid::synthesize`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct Synthesis {
+    pub seed: u64,
+    /// Painted cells of the region keep their terrain. Otherwise they are
+    /// repainted, and only painted cells around the region hold it back.
+    pub keep_painted: bool,
+    /// Choices undone before giving up.
+    pub max_backtracks: usize,
+}
+
+impl Default for Synthesis {
+    fn default() -> Self {
+        Self { seed: 0, keep_painted: true, max_backtracks: 1000 }
+    }
+}
+
+/// Why a region could not be filled.
+#[derive(Debug, PartialEq)]
+pub enum SynthesisError {
+    /// The rules know no terrain.
+    NoRules,
+    /// A painted cell the region touches has a terrain the rules do not know.
+    UnknownTerrain { cell: Hex, terrain: String },
+    /// No terrain fits at `cell`, next to the painted neighbors listed.
+    Conflict { cell: Hex, painted: Vec<(Direction, String)> },
+    /// Every way of filling the region was tried, the last ran out of options at `cell`.
+    NoSolution { cell: Hex },
+    /// `max_backtracks` choices were undone, the last one ran out of options at `cell`.
+    GaveUp { cell: Hex, backtracks: usize },
+}
+
+impl fmt::Display for SynthesisError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoRules => write!(f, "the rules know no terrain, learn them from painted cells first"),
+            Self::UnknownTerrain { cell, terrain } => write!(
+                f, "\"{terrain}\" at {}, {} is not in the rules", cell.q(), cell.r()
+            ),
+            Self::Conflict { cell, painted } if painted.is_empty() => write!(
+                f, "no terrain the rules know fits at {}, {}", cell.q(), cell.r()
+            ),
+            Self::Conflict { cell, painted } => {
+                let neighbors: Vec<_> = painted.iter()
+                    .map(|(direction, terrain)| format!("{terrain} to the {}", direction.name()))
+                    .collect();
+                write!(f, "no terrain fits at {}, {} next to {}", cell.q(), cell.r(), neighbors.join(", "))
+            }
+            Self::NoSolution { cell } => write!(
+                f, "the rules cannot fill the region, every attempt ran out of options at {}, {}", cell.q(), cell.r()
+            ),
+            Self::GaveUp { cell, backtracks } => write!(
+                f, "gave up after undoing {backtracks} choices, the last at {}, {}", cell.q(), cell.r()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SynthesisError {}
+
+/// Terrains each cell may still take, with what it takes to undo changes.
+struct Wave<'a> {
+    rules: &'a Rules,
+    /// `neighbors[cell][direction]`, for neighbors inside the wave.
+    neighbors: Vec<[Option<usize>; 6]>,
+    /// Pinned cells keep their single option whatever their neighbors say.
+    pinned: Vec<bool>,
+    /// `options[cell][terrain]`, by index into the rules.
+    options: Vec<Vec<bool>>,
+    counts: Vec<usize>,
+    /// Sums of `w` and `w ln w` over the options left, for the entropy.
+    sums: Vec<(f64, f64)>,
+    /// Options removed, oldest first.
+    trail: Vec<(usize, usize)>,
+}
+
+/// A choice that can be undone.
+struct Decision {
+    cell: usize,
+    option: usize,
+    /// Length of the trail before the choice.
+    mark: usize,
+}
+
+impl Wave<'_> {
+    fn remove(&mut self, cell: usize, option: usize) {
+        self.options[cell][option] = false;
+        self.counts[cell] -= 1;
+        let weight = self.rules.weights[option];
+        self.sums[cell].0 -= weight;
+        self.sums[cell].1 -= weight_log(weight);
+        self.trail.push((cell, option));
+    }
+
+    /// Puts back the options removed since the trail was `mark` long.
+    fn undo(&mut self, mark: usize) {
+        for (cell, option) in self.trail.drain(mark..).rev() {
+            self.options[cell][option] = true;
+            self.counts[cell] += 1;
+            let weight = self.rules.weights[option];
+            self.sums[cell].0 += weight;
+            self.sums[cell].1 += weight_log(weight);
+        }
+    }
+
+    /// Drops the options of the neighbors of `stack` that nothing left in them
+    /// allows, and so on outwards. Fails with the first cell left without options.
+    fn propagate(&mut self, mut stack: Vec<usize>) -> Result<(), usize> {
+        while let Some(cell) = stack.pop() {
+            for direction in Direction::iter() {
+                let Some(neighbor) = self.neighbors[cell][direction.index()] else {
+                    continue;
+                };
+                if self.pinned[neighbor] {
+                    continue;
+                }
+                let allowed = &self.rules.allowed[direction.index()];
+                let unsupported: Vec<_> = (0..self.rules.len())
+                    .filter(|other| self.options[neighbor][*other])
+                    .filter(|other| !(0..self.rules.len()).any(|option| self.options[cell][option] && allowed[option][*other]))
+                    .collect();
+                if unsupported.is_empty() {
+                    continue;
+                }
+                for option in unsupported {
+                    self.remove(neighbor, option);
+                }
+                if self.counts[neighbor] == 0 {
+                    return Err(neighbor);
+                }
+                stack.push(neighbor);
+            }
+        }
+        Ok(())
+    }
+
+    /// Settles `cell` on `option`.
+    fn collapse(&mut self, cell: usize, option: usize) -> Result<(), usize> {
+        let others: Vec<_> = (0..self.rules.len()).filter(|other| *other != option && self.options[cell][*other]).collect();
+        for other in others {
+            self.remove(cell, other);
+        }
+        self.propagate(vec![cell])
+    }
+
+    /// Rules `option` out for `cell`.
+    fn ban(&mut self, cell: usize, option: usize) -> Result<(), usize> {
+        self.remove(cell, option);
+        if self.counts[cell] == 0 {
+            return Err(cell);
+        }
+        self.propagate(vec![cell])
+    }
+
+    /// The unsettled cell with the least entropy, ties broken by `jitter`.
+    fn most_constrained(&self, jitter: &[f64]) -> Option<usize> {
+        (0..self.counts.len())
+            .filter(|cell| self.counts[*cell] > 1)
+            .map(|cell| {
+                let (sum, sum_log) = self.sums[cell];
+                let entropy = if sum > 0.0 { sum.ln() - sum_log / sum } else { 0.0 };
+                (cell, entropy + jitter[cell])
+            })
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(cell, _)| cell)
+    }
+
+    /// One of the options of `cell`, picked by weight.
+    fn choose(&self, cell: usize, rng: &mut Rng) -> usize {
+        let options: Vec<_> = (0..self.rules.len()).filter(|option| self.options[cell][*option]).collect();
+        let total: f64 = options.iter().map(|option| self.rules.weights[*option]).sum();
+        if total <= 0.0 {
+            return options[rng.below(options.len())];
+        }
+        let mut target = rng.next_f64() * total;
+        for option in &options {
+            target -= self.rules.weights[*option];
+            if target < 0.0 {
+                return *option;
+            }
+        }
+        *options.last().expect("Choosing for a cell without options!")
+    }
+}
+
+fn weight_log(weight: f64) -> f64 {
+    if weight > 0.0 { weight * weight.ln() } else { 0.0 }
+}
+
+impl Grid {
+    /// Picks terrains for the cells of `region` on the active layer that follow
+    /// `rules`, returning the cells to paint. The painted cells around the region,
+    /// and inside it unless they are to be repainted, are left as they are and
+    /// the rest has to fit them. The same seed always gives the same cells.
+    pub fn synthesize(&self, region: impl IntoIterator<Item = Hex>, rules: &Rules, options: &Synthesis) -> Result<Vec<(Hex, TerrainId)>, SynthesisError> {
+        if rules.is_empty() {
+            return Err(SynthesisError::NoRules);
+        }
+        //Sorted so the result only depends on the seed
+        let mut free: Vec<_> = region.into_iter()
+            .filter(|hex| !(options.keep_painted && self.cell(*hex).is_some()))
+            .collect();
+        free.sort_by_key(|hex| (hex.r(), hex.q()));
+        free.dedup();
+        let free_set: HashSet<_> = free.iter().copied().collect();
+        let mut pinned: Vec<_> = free.iter()
+            .flat_map(|hex| Direction::iter().map(|direction| hex.neighbor(direction)))
+            .filter(|hex| !free_set.contains(hex) && self.cell(*hex).is_some())
+            .collect();
+        pinned.sort_by_key(|hex| (hex.r(), hex.q()));
+        pinned.dedup();
+
+        let cells: Vec<_> = free.iter().chain(&pinned).copied().collect();
+        let index: HashMap<_, _> = cells.iter().enumerate().map(|(index, hex)| (*hex, index)).collect();
+        let name = |terrain: TerrainId| self.terrains.get(terrain)
+            .map_or_else(|| format!("terrain {}", terrain.0), |terrain| terrain.name.clone());
+        let mut wave = Wave {
+            rules,
+            neighbors: cells.iter()
+                .map(|hex| Direction::ALL.map(|direction| index.get(&hex.neighbor(direction)).copied()))
+                .collect(),
+            pinned: cells.iter().map(|hex| !free_set.contains(hex)).collect(),
+            options: vec![vec![true; rules.len()]; cells.len()],
+            counts: vec![rules.len(); cells.len()],
+            sums: vec![rules.weights.iter().fold((0.0, 0.0), |(sum, sum_log), weight| (sum + weight, sum_log + weight_log(*weight))); cells.len()],
+            trail: Vec::new(),
+        };
+        for (cell, hex) in cells.iter().enumerate().skip(free.len()) {
+            let terrain = self.cell(*hex).expect("Unpainted cell pinned!");
+            let option = rules.terrains.iter()
+                .position(|known| *known == terrain)
+                .ok_or_else(|| SynthesisError::UnknownTerrain { cell: *hex, terrain: name(terrain) })?;
+            for other in (0..rules.len()).filter(|other| *other != option) {
+                wave.remove(cell, other);
+            }
+        }
+        if let Err(cell) = wave.propagate((0..cells.len()).collect()) {
+            let hex = cells[cell];
+            let painted = Direction::iter()
+                .filter_map(|direction| {
+                    let neighbor = index.get(&hex.neighbor(direction)).filter(|neighbor| wave.pinned[**neighbor])?;
+                    Some((direction, name(self.cell(cells[*neighbor]).expect("Unpainted cell pinned!"))))
+                })
+                .collect();
+            return Err(SynthesisError::Conflict { cell: hex, painted });
+        }
+        wave.trail.clear();
+
+        let mut rng = Rng::new(options.seed);
+        let jitter: Vec<_> = cells.iter().map(|_| rng.next_f64() * 1e-6).collect();
+        let mut decisions: Vec<Decision> = Vec::new();
+        let mut backtracks = 0;
+        while let Some(cell) = wave.most_constrained(&jitter) {
+            let option = wave.choose(cell, &mut rng);
+            decisions.push(Decision { cell, option, mark: wave.trail.len() });
+            let mut result = wave.collapse(cell, option);
+            while let Err(failed) = result {
+                let Some(decision) = decisions.pop() else {
+                    return Err(SynthesisError::NoSolution { cell: cells[failed] });
+                };
+                if backtracks == options.max_backtracks {
+                    return Err(SynthesisError::GaveUp { cell: cells[failed], backtracks });
+                }
+                backtracks += 1;
+                wave.undo(decision.mark);
+                result = wave.ban(decision.cell, decision.option);
+            }
+        }
+
+        Ok(free.iter()
+            .enumerate()
+            .map(|(cell, hex)| {
+                let option = (0..rules.len()).find(|option| wave.options[cell][*option]).expect("Cell left without options!");
+                (*hex, rules.terrains[option])
+            })
+            .collect())
+    }
+}
@@ -0,0 +1,128 @@
+use std::collections::BTreeMap;
+
+use egui::Color32;
+
+/// Drawn for cells whose terrain is missing from the registry.
+pub const MISSING_TERRAIN_COLOR: Color32 = Color32::from_rgb(255, 0, 255);
+
+/// Key of a [`Terrain`] inside a [`TerrainRegistry`]. This is what cells store.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct TerrainId(pub u16);
+
+/// A tile type game code can reason about.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Terrain {
+    pub name: String,
+    pub color: Color32,
+    /// Cost of entering a cell of this terrain.
+    pub movement_cost: u32,
+    pub passable: bool,
+    /// Blocks line of sight.
+    pub opaque: bool,
+    /// Free form key/value data for game code.
+    pub properties: BTreeMap<String, String>,
+}
+
+impl Terrain {
+    pub fn new(name: impl Into<String>, color: Color32) -> Self {
+        Self {
+            name: name.into(),
+            color,
+            movement_cost: 1,
+            passable: true,
+            opaque: false,
+            properties: BTreeMap::new(),
+        }
+    }
+
+    pub fn impassable(mut self) -> Self {
+        self.passable = false;
+        self
+    }
+
+    pub fn opaque(mut self) -> Self {
+        self.opaque = true;
+        self
+    }
+
+    pub fn with_cost(mut self, movement_cost: u32) -> Self {
+        self.movement_cost = movement_cost;
+        self
+    }
+}
+
+/// The terrain types a map can be painted with.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TerrainRegistry {
+    terrains: BTreeMap<TerrainId, Terrain>,
+}
+
+impl TerrainRegistry {
+    /// Terrain types new maps start with.
+    pub fn builtin() -> Self {
+        let terrains = [
+            Terrain::new("Grass", Color32::from_rgb(25, 200, 100)),
+            Terrain::new("Forest", Color32::from_rgb(20, 110, 50)).with_cost(2).opaque(),
+            Terrain::new("Water", Color32::from_rgb(40, 110, 210)).impassable(),
+            Terrain::new("Sand", Color32::from_rgb(230, 210, 140)).with_cost(2),
+            Terrain::new("Mountain", Color32::from_rgb(130, 120, 110)).with_cost(3).opaque(),
+            Terrain::new("Snow", Color32::from_rgb(240, 245, 250)).with_cost(2),
+        ];
+        Self { terrains: (0..).map(TerrainId).zip(terrains).collect() }
+    }
+
+    /// Registers `terrain` under a fresh id, the one after the highest taken or else
+    /// the lowest free one. Returns `None` when every id is taken.
+    pub fn add(&mut self, terrain: Terrain) -> Option<TerrainId> {
+        let id = match self.terrains.keys().next_back() {
+            None => TerrainId(0),
+            Some(TerrainId(last)) => match last.checked_add(1) {
+                Some(next) => TerrainId(next),
+                None => (0..=u16::MAX).map(TerrainId).find(|id| !self.terrains.contains_key(id))?,
+            },
+        };
+        self.terrains.insert(id, terrain);
+        Some(id)
+    }
+
+    /// Registers `terrain` under `id`, returning the terrain it replaced.
+    pub fn insert(&mut self, id: TerrainId, terrain: Terrain) -> Option<Terrain> {
+        self.terrains.insert(id, terrain)
+    }
+
+    pub fn remove(&mut self, id: TerrainId) -> Option<Terrain> {
+        self.terrains.remove(&id)
+    }
+
+    pub fn get(&self, id: TerrainId) -> Option<&Terrain> {
+        self.terrains.get(&id)
+    }
+
+    pub fn get_mut(&mut self, id: TerrainId) -> Option<&mut Terrain> {
+        self.terrains.get_mut(&id)
+    }
+
+    pub fn contains(&self, id: TerrainId) -> bool {
+        self.terrains.contains_key(&id)
+    }
+
+    pub fn color(&self, id: TerrainId) -> Color32 {
+        self.get(id).map_or(MISSING_TERRAIN_COLOR, |terrain| terrain.color)
+    }
+
+    pub fn ids(&self) -> impl Iterator<Item = TerrainId> + '_ {
+        self.terrains.keys().copied()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (TerrainId, &Terrain)> {
+        self.terrains.iter().map(|(id, terrain)| (*id, terrain))
+    }
+
+    pub fn len(&self) -> usize {
+        self.terrains.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.terrains.is_empty()
+    }
+}
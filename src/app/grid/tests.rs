@@ -0,0 +1,1130 @@
+use std::collections::HashSet;
+
+use egui::Color32;
+
+use crate::app::coordinates::CoordSystem;
+
+use super::map_file::{MapFileError, CURRENT_VERSION, FORMAT_TAG};
+use super::tiled::TiledError;
+use super::{
+    center_of, shapes, Biome, Diagonal, Direction, ExportError, FillMatch, FillOptions, FovMode, Generator, Grid, Hex, HexAxis, HexCorner, HexEdge, HexIterators, HexOrientation, HexUtility, ImageOptions, Layout, Noise, Point, RasterImport, Rng, Rules, RulesError, Synthesis, SynthesisError, Sampling, Selection, SelectionOp, Site, Terrain, TerrainId, TerrainRegistry, Transform, LAYOUT_ORIENTATION_FLAT
+};
+
+/// Layer ids are only unique for a session, the rest must survive saving.
+fn assert_same_layers(expected: &Grid, actual: &Grid) {
+    let strip = |grid: &Grid| grid.layers().iter()
+        .map(|layer| (
+            (layer.name.clone(), layer.visible, layer.locked, layer.opacity),
+            (layer.data.clone(), layer.edges.clone(), layer.corners.clone()),
+        ))
+        .collect::<Vec<_>>();
+    assert_eq!(strip(expected), strip(actual));
+}
+
+fn round_trip(grid: &Grid) -> Grid {
+    let mut buffer = Vec::new();
+    grid.write_to(&mut buffer).expect("Failed to write map!");
+    Grid::read_from(buffer.as_slice()).expect("Failed to read map back!")
+}
+
+#[test]
+fn test_map_file_roundtrip() {
+    let mut grid = Grid::make_hex(Hex::new(2, -1), 2);
+    let mut lava = Terrain::new("Lava", Color32::from_rgb(10, 20, 30)).impassable().with_cost(7);
+    lava.properties.insert("damage".into(), "5".into());
+    let lava = grid.terrains_mut().add(lava).unwrap();
+    grid.paint_cell(Hex::new(2, -1), TerrainId(1));
+    grid.paint_cell(Hex::new(-7, 3), lava);
+
+    let actual = round_trip(&grid);
+    assert_eq!(grid.layout, actual.layout);
+    assert_same_layers(&grid, &actual);
+    assert_eq!(grid.terrains, actual.terrains);
+}
+
+#[test]
+fn test_map_file_roundtrip_flat_layout() {
+    let mut grid = Grid {
+        layout: Layout {
+            orientation: LAYOUT_ORIENTATION_FLAT,
+            size: Point { x: 10.0, y: 15.0 },
+            origin: Point { x: 35.0, y: 71.0 },
+        },
+        ..Default::default()
+    };
+    grid.paint_cell(Hex::new(3, 4), TerrainId(2));
+
+    let actual = round_trip(&grid);
+    assert_eq!(grid.layout, actual.layout);
+    assert_same_layers(&grid, &actual);
+}
+
+#[test]
+fn test_map_file_roundtrip_layers() {
+    let mut grid = Grid::default();
+    grid.paint_cell(Hex::new(0, 0), TerrainId(1));
+    let roads = grid.add_layer("Roads");
+    grid.paint_cell(Hex::new(0, 0), TerrainId(3));
+    let layer = grid.layer_mut(roads).unwrap();
+    layer.visible = false;
+    layer.locked = true;
+    layer.opacity = 0.25;
+
+    let actual = round_trip(&grid);
+    assert_same_layers(&grid, &actual);
+}
+
+#[test]
+fn test_map_file_roundtrip_edges_and_corners() {
+    let mut grid = Grid::make_hex(Hex::new(0, 0), 1);
+    let wall = HexEdge::new(Hex::new(0, 0), Direction::SouthWest);
+    let tower = HexCorner::new(Hex::new(1, 0), Diagonal::South);
+    let layer = grid.active_layer().id();
+    grid.set_layer_site(layer, wall, Some(TerrainId(4)));
+    grid.set_layer_site(layer, tower, Some(TerrainId(5)));
+    grid.set_layer_site(layer, HexEdge::new(Hex::new(9, 9), Direction::East), Some(TerrainId(2)));
+    assert!(grid.uses_terrain(TerrainId(5)));
+
+    let actual = round_trip(&grid);
+    assert_same_layers(&grid, &actual);
+    assert_eq!(Some(TerrainId(4)), actual.active_layer().edge(HexEdge::new(Hex::new(-1, 1), Direction::NorthEast)));
+    assert_eq!(Some(TerrainId(5)), actual.active_layer().site(tower));
+
+    //Empty lists are left out, and edges may be named from either side
+    let mut buffer = Vec::new();
+    Grid::default().write_to(&mut buffer).unwrap();
+    assert!(!String::from_utf8(buffer).unwrap().contains("edges"));
+    let map = |sites: &str| format!(
+        r##"{{"format": "{FORMAT_TAG}", "version": {CURRENT_VERSION},
+            "layout": {{"orientation": "pointy", "size": [1.0, 1.0], "origin": [0.0, 0.0]}},
+            "terrains": [{{"id": 0, "name": "Grass", "color": "#00ff00ff", "movement_cost": 1, "passable": true}}],
+            "layers": [{{"name": "Walls", "visible": true, "locked": false, "opacity": 1.0, "cells": [], {sites}}}]}}"##
+    );
+    let grid = Grid::read_from(map(r#""edges": [{"q": 1, "r": 0, "side": "west", "terrain": 0}]"#).as_bytes()).unwrap();
+    assert_eq!(Some(TerrainId(0)), grid.active_layer().edge(HexEdge::new(Hex::new(0, 0), Direction::East)));
+
+    for sites in [
+        r#""edges": [{"q": 0, "r": 0, "side": "up", "terrain": 0}]"#,
+        r#""edges": [{"q": 0, "r": 0, "side": "east", "terrain": 3}]"#,
+        r#""edges": [{"q": 0, "r": 0, "side": "east", "terrain": 0}, {"q": 1, "r": 0, "side": "west", "terrain": 0}]"#,
+        r#""corners": [{"q": 0, "r": 0, "corner": "east", "terrain": 0}]"#,
+        r#""corners": [{"q": 0, "r": 0, "corner": "south", "terrain": 0}, {"q": 0, "r": 1, "corner": "west-north-west", "terrain": 0}]"#,
+    ] {
+        assert!(matches!(Grid::read_from(map(sites).as_bytes()), Err(MapFileError::Corrupt(_))), "{sites}");
+    }
+}
+
+#[test]
+fn test_map_file_migrates_version_2() {
+    let contents = format!(
+        r##"{{"format": "{FORMAT_TAG}", "version": 2,
+            "layout": {{"orientation": "pointy", "size": [1.0, 1.0], "origin": [0.0, 0.0]}},
+            "terrains": [{{"id": 0, "name": "Grass", "color": "#00ff00ff", "movement_cost": 1, "passable": true}}],
+            "cells": [{{"q": 4, "r": 2, "terrain": 0}}]}}"##
+    );
+    let grid = Grid::read_from(contents.as_bytes()).expect("Failed to migrate map!");
+
+    assert_eq!(1, grid.layers().len());
+    assert_eq!("Terrain", grid.active_layer().name);
+    assert_eq!(Some(TerrainId(0)), grid.cell(Hex::new(4, 2)));
+}
+
+#[test]
+fn test_map_file_migrates_version_1() {
+    let contents = format!(
+        r##"{{"format": "{FORMAT_TAG}", "version": 1,
+            "layout": {{"orientation": "pointy", "size": [1.0, 1.0], "origin": [0.0, 0.0]}},
+            "cells": [
+                {{"q": 0, "r": 0, "color": "#ff0000ff"}},
+                {{"q": 1, "r": 0, "color": "#00ff00ff"}},
+                {{"q": 2, "r": 0, "color": "#ff0000ff"}}
+            ]}}"##
+    );
+    let grid = Grid::read_from(contents.as_bytes()).expect("Failed to migrate map!");
+
+    assert_eq!(2, grid.terrains.len());
+    let red = grid.cell(Hex::new(0, 0)).unwrap();
+    let green = grid.cell(Hex::new(1, 0)).unwrap();
+    assert_eq!(Some(red), grid.cell(Hex::new(2, 0)));
+    assert_ne!(red, green);
+    assert_eq!(Color32::RED, grid.terrains.color(red));
+    assert_eq!(Color32::GREEN, grid.terrains.color(green));
+}
+
+#[test]
+fn test_map_file_future_version() {
+    let contents = format!(
+        r#"{{"format": "{FORMAT_TAG}", "version": {}, "layout": {{}}, "cells": []}}"#,
+        CURRENT_VERSION + 1
+    );
+    let error = Grid::read_from(contents.as_bytes()).err();
+    assert!(matches!(
+        error,
+        Some(MapFileError::FutureVersion { found, supported }) if found == CURRENT_VERSION + 1 && supported == CURRENT_VERSION
+    ));
+}
+
+#[test]
+fn test_map_file_corrupt() {
+    let truncated = br#"{"format": "hex-editor-map", "version": 1, "layout": {"#;
+    assert!(matches!(Grid::read_from(&truncated[..]), Err(MapFileError::Corrupt(_))));
+
+    let map = |terrains: &str, cells: &str| format!(
+        r##"{{"format": "{FORMAT_TAG}", "version": 2,
+            "layout": {{"orientation": "pointy", "size": [1.0, 1.0], "origin": [0.0, 0.0]}},
+            "terrains": [{terrains}], "cells": [{cells}]}}"##
+    );
+    let grass = r##"{"id": 0, "name": "Grass", "color": "#00ff00ff", "movement_cost": 1, "passable": true}"##;
+
+    let bad_color = map(r##"{"id": 0, "name": "Grass", "color": "#zz0000ff", "movement_cost": 1, "passable": true}"##, "");
+    assert!(matches!(Grid::read_from(bad_color.as_bytes()), Err(MapFileError::Corrupt(_))));
+
+    let duplicate = map(grass, r#"{"q": 1, "r": 0, "terrain": 0}, {"q": 1, "r": 0, "terrain": 0}"#);
+    assert!(matches!(Grid::read_from(duplicate.as_bytes()), Err(MapFileError::Corrupt(_))));
+
+    let unknown_terrain = map(grass, r#"{"q": 1, "r": 0, "terrain": 4}"#);
+    assert!(matches!(Grid::read_from(unknown_terrain.as_bytes()), Err(MapFileError::Corrupt(_))));
+
+    for cell in [r#"{"q": -2147483648, "r": 0, "terrain": 0}"#, r#"{"q": 5, "r": -2147483648, "terrain": 0}"#] {
+        let out_of_range = map(grass, cell);
+        assert!(matches!(Grid::read_from(out_of_range.as_bytes()), Err(MapFileError::Corrupt(_))), "{cell}");
+    }
+}
+
+#[test]
+fn test_layer_stack() {
+    let mut grid = Grid::default();
+    let bottom = grid.active_layer().id();
+    let count = grid.layers().len();
+
+    let top = grid.add_layer("Top");
+    assert_eq!(count + 1, grid.layers().len());
+    assert_eq!(top, grid.active_layer().id());
+    assert_eq!(top, grid.layers()[1].id());
+
+    grid.paint_cell(Hex::new(1, 1), TerrainId(2));
+    assert_eq!(None, grid.layer(bottom).unwrap().cell(Hex::new(1, 1)));
+
+    grid.move_layer(top, -5);
+    assert_eq!(top, grid.layers()[0].id());
+    assert_eq!(top, grid.active_layer().id());
+    grid.move_layer(top, 100);
+    assert_eq!(top, grid.layers()[count].id());
+
+    assert!(grid.remove_layer(top).is_some());
+    assert_eq!(count, grid.layers().len());
+    assert!(grid.layer(top).is_none());
+    assert!(!grid.uses_terrain(TerrainId(2)));
+}
+
+#[test]
+fn test_last_layer_is_kept() {
+    let mut grid = Grid::default();
+    let ids: Vec<_> = grid.layers().iter().map(|layer| layer.id()).collect();
+    for id in &ids[1..] {
+        assert!(grid.remove_layer(*id).is_some());
+    }
+    assert!(grid.remove_layer(ids[0]).is_none());
+    assert_eq!(ids[0], grid.active_layer().id());
+}
+
+#[test]
+fn test_map_file_not_a_map() {
+    let contents = br#"{"name": "something else"}"#;
+    assert!(matches!(Grid::read_from(&contents[..]), Err(MapFileError::NotAMap)));
+}
+
+#[test]
+fn test_map_file_refused_save_keeps_file() {
+    let mut grid = Grid::make_hex(Hex::new(0, 0), 1);
+    grid.layout.orientation.start_angle = 0.25;
+    let path = std::env::temp_dir().join(format!("hex-editor-grid-{}-refused.json", std::process::id()));
+    std::fs::write(&path, "previous").unwrap();
+    assert!(matches!(grid.save(&path), Err(MapFileError::Unsupported(_))));
+    assert_eq!("previous", std::fs::read_to_string(&path).unwrap());
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_flood_fill_same_terrain() {
+    let mut grid = Grid::make_hex(Hex::new(0, 0), 2);
+    //A wall of another terrain splits the hexagon in two
+    for r in -2..=2 {
+        grid.set_cell(Hex::new(0, r), Some(TerrainId(2)));
+    }
+    let region = grid.flood_fill(Hex::new(-1, 0), &FillOptions::default());
+    assert_eq!(7, region.len());
+    assert!(region.iter().all(|hex| hex.q() < 0));
+
+    let bounded = FillOptions { radius: Some(1), ..Default::default() };
+    let region = grid.flood_fill(Hex::new(-1, 0), &bounded);
+    assert!(region.iter().all(|hex| hex.distance(Hex::new(-1, 0)) <= 1));
+    assert_eq!(5, region.len());
+}
+
+#[test]
+fn test_flood_fill_similar_color() {
+    let mut grid = Grid::default();
+    let light = grid.terrains_mut().add(Terrain::new("Light", Color32::from_rgb(100, 100, 100))).unwrap();
+    let dark = grid.terrains_mut().add(Terrain::new("Dark", Color32::from_rgb(110, 100, 100))).unwrap();
+    grid.paint_cell(Hex::new(0, 0), light);
+    grid.paint_cell(Hex::new(1, 0), dark);
+
+    let exact = grid.flood_fill(Hex::new(0, 0), &FillOptions::default());
+    assert_eq!(vec![Hex::new(0, 0)], exact);
+
+    let similar = FillOptions { matching: FillMatch::SimilarColor { tolerance: 10 }, ..Default::default() };
+    assert_eq!(2, grid.flood_fill(Hex::new(0, 0), &similar).len());
+}
+
+#[test]
+fn test_flood_fill_empty_space() {
+    let mut grid = Grid::default();
+    //A ring around the origin encloses a single empty cell
+    for hex in Hex::new(0, 0).neighbors() {
+        grid.paint_cell(hex, TerrainId(0));
+    }
+    assert_eq!(vec![Hex::new(0, 0)], grid.flood_fill(Hex::new(0, 0), &FillOptions::default()));
+
+    //Outside the ring, empty space is clipped to the map bounds
+    grid.paint_cell(Hex::new(3, 0), TerrainId(0));
+    assert!(grid.flood_fill(Hex::new(5, 0), &FillOptions::default()).is_empty());
+    let outside = grid.flood_fill(Hex::new(2, 0), &FillOptions::default());
+    assert!(!outside.contains(&Hex::new(0, 0)));
+    assert!(outside.contains(&Hex::new(2, -1)));
+    assert!(outside.iter().all(|hex| (-1..=3).contains(&hex.q()) && (-1..=1).contains(&hex.r())));
+
+    let no_empty = FillOptions { fill_empty: false, ..Default::default() };
+    assert!(grid.flood_fill(Hex::new(0, 0), &no_empty).is_empty());
+}
+
+#[test]
+fn test_shapes() {
+    let grid = Grid::make_hex(Hex::new(2, -3), 3);
+    assert_eq!(37, grid.cells().count());
+    assert!(grid.cells().all(|hex| hex.distance(Hex::new(2, -3)) <= 3));
+    assert_eq!(12, shapes::rhombus(Hex::new(0, 0), Hex::new(2, 3)).len());
+    assert_eq!(10, shapes::triangle(Hex::new(0, 0), 3).len());
+}
+
+const GRASS: TerrainId = TerrainId(0);
+const FOREST: TerrainId = TerrainId(1);
+const WATER: TerrainId = TerrainId(2);
+
+#[test]
+fn test_find_path_avoids_obstacles() {
+    let mut grid = Grid::make_hex(Hex::new(0, 0), 3);
+    //A lake between the endpoints, open only at the rim
+    for hex in Hex::new(0, 0).range(2) {
+        if hex.q() == 0 {
+            grid.paint_cell(hex, WATER);
+        }
+    }
+    let (from, to) = (Hex::new(-2, 1), Hex::new(2, -1));
+    let path = grid.find_path(from, to).expect("The lake can be walked around");
+    assert_eq!(Some(&from), path.cells.first());
+    assert_eq!(Some(&to), path.cells.last());
+    assert!(path.cells.windows(2).all(|pair| pair[0].distance(pair[1]) == 1));
+    assert!(path.cells.iter().all(|hex| grid.cell(*hex) == Some(GRASS)));
+    assert_eq!(path.cells.len() as u32 - 1, path.cost);
+    assert!(path.cells.len() as i32 - 1 > from.distance(to));
+
+    assert!(grid.find_path(from, Hex::new(0, 0)).is_none());
+    assert!(grid.find_path(from, Hex::new(9, 0)).is_none());
+}
+
+#[test]
+fn test_find_path_prefers_cheap_terrain() {
+    let mut grid = Grid::make_rhombus(Hex::new(0, 0), Hex::new(4, 1));
+    //The straight row is forest, the parallel row is grass
+    for q in 1..=3 {
+        grid.paint_cell(Hex::new(q, 0), FOREST);
+    }
+    let path = grid.find_path(Hex::new(0, 0), Hex::new(4, 0)).unwrap();
+    assert_eq!(grid.cost_field(Hex::new(0, 0), None)[&Hex::new(4, 0)], path.cost);
+    assert!(path.cost < 1 + 2 * 3);
+}
+
+#[test]
+fn test_cost_field_and_reachable() {
+    let mut grid = Grid::make_hex(Hex::new(0, 0), 3);
+    grid.paint_cell(Hex::new(1, 0), FOREST);
+    grid.paint_cell(Hex::new(-1, 0), WATER);
+
+    let field = grid.cost_field(Hex::new(0, 0), None);
+    assert_eq!(36, field.len());
+    assert_eq!(0, field[&Hex::new(0, 0)]);
+    assert_eq!(2, field[&Hex::new(1, 0)]);
+    assert!(!field.contains_key(&Hex::new(-1, 0)));
+    for (hex, cost) in &field {
+        assert_eq!(*cost, grid.find_path(Hex::new(0, 0), *hex).unwrap().cost);
+    }
+
+    let mut reachable = grid.reachable(Hex::new(0, 0), 1);
+    reachable.sort_by_key(|hex| (hex.q(), hex.r()));
+    let mut expected: Vec<_> = Hex::new(0, 0).range(1)
+        .filter(|hex| *hex != Hex::new(1, 0) && *hex != Hex::new(-1, 0))
+        .collect();
+    expected.sort_by_key(|hex| (hex.q(), hex.r()));
+    assert_eq!(expected, reachable);
+}
+
+#[test]
+fn test_movement_cost_combines_visible_layers() {
+    let mut grid = Grid::make_hex(Hex::new(0, 0), 1);
+    let top = grid.add_layer("Bridges");
+    grid.paint_cell(Hex::new(0, 0), WATER);
+    assert_eq!(None, grid.movement_cost(Hex::new(0, 0)));
+
+    grid.layer_mut(top).unwrap().visible = false;
+    assert_eq!(Some(1), grid.movement_cost(Hex::new(0, 0)));
+    assert_eq!(None, grid.movement_cost(Hex::new(5, 0)));
+}
+
+#[test]
+fn test_annotation_over_wall_keeps_it_closed() {
+    let mut grid = Grid::make_hex(Hex::new(0, 0), 1);
+    grid.paint_cell(Hex::new(1, 0), WATER);
+    grid.paint_cell(Hex::new(-1, 0), MOUNTAIN);
+    grid.add_layer("Annotations");
+    grid.paint_cell(Hex::new(1, 0), GRASS);
+    grid.paint_cell(Hex::new(-1, 0), GRASS);
+    grid.paint_cell(Hex::new(0, 1), MOUNTAIN);
+
+    assert_eq!(None, grid.movement_cost(Hex::new(1, 0)));
+    assert_eq!(Some(3), grid.movement_cost(Hex::new(-1, 0)));
+    assert_eq!(Some(3), grid.movement_cost(Hex::new(0, 1)));
+    assert_eq!(Some(1), grid.movement_cost(Hex::new(0, 0)));
+}
+
+#[test]
+fn test_annotation_over_wall_keeps_it_opaque() {
+    let mut grid = Grid::make_hex(Hex::new(0, 0), 3);
+    grid.paint_cell(Hex::new(1, 0), MOUNTAIN);
+    let annotations = grid.add_layer("Annotations");
+    grid.paint_cell(Hex::new(1, 0), GRASS);
+    assert!(grid.is_opaque(Hex::new(1, 0)));
+    assert!(!grid.line_of_sight(Hex::new(0, 0), Hex::new(2, 0)));
+
+    //Only hiding the wall's own layer clears the view
+    grid.layer_mut(annotations).unwrap().visible = false;
+    assert!(grid.is_opaque(Hex::new(1, 0)));
+    let bottom = grid.layers()[0].id();
+    grid.layer_mut(bottom).unwrap().visible = false;
+    assert!(!grid.is_opaque(Hex::new(1, 0)));
+}
+
+const MOUNTAIN: TerrainId = TerrainId(4);
+
+#[test]
+fn test_field_of_view_walls() {
+    let mut grid = Grid::make_hex(Hex::new(0, 0), 6);
+    for mode in [FovMode::Shadowcast, FovMode::Symmetric] {
+        assert_eq!(127, grid.field_of_view(Hex::new(0, 0), 6, mode).len());
+    }
+
+    //Closed in by a ring of mountains, only the ring itself is seen
+    for hex in Hex::new(0, 0).ring(1) {
+        grid.paint_cell(hex, MOUNTAIN);
+    }
+    for mode in [FovMode::Shadowcast, FovMode::Symmetric] {
+        let visible = grid.field_of_view(Hex::new(0, 0), 6, mode);
+        assert_eq!(Hex::new(0, 0).range(1).collect::<HashSet<_>>(), visible);
+    }
+}
+
+#[test]
+fn test_field_of_view_single_wall() {
+    let mut grid = Grid::make_hex(Hex::new(0, 0), 6);
+    grid.paint_cell(Hex::new(2, 0), MOUNTAIN);
+    for mode in [FovMode::Shadowcast, FovMode::Symmetric] {
+        let visible = grid.field_of_view(Hex::new(0, 0), 6, mode);
+        assert!(visible.contains(&Hex::new(2, 0)));
+        assert!(!visible.contains(&Hex::new(3, 0)));
+        assert!(!visible.contains(&Hex::new(5, 0)));
+        //Cells beside the wall stay in sight
+        assert!(visible.contains(&Hex::new(2, -1)));
+        assert!(visible.contains(&Hex::new(0, 5)));
+    }
+}
+
+#[test]
+fn test_symmetric_line_of_sight() {
+    let mut grid = Grid::make_hex(Hex::new(0, 0), 5);
+    for hex in [Hex::new(1, 0), Hex::new(-2, 3), Hex::new(0, -2), Hex::new(2, 1), Hex::new(-1, -1)] {
+        grid.paint_cell(hex, MOUNTAIN);
+    }
+    let cells: Vec<_> = Hex::new(0, 0).range(3).collect();
+    let seen: Vec<_> = cells.iter().map(|hex| grid.field_of_view(*hex, 6, FovMode::Symmetric)).collect();
+    for (i, a) in cells.iter().enumerate() {
+        for (j, b) in cells.iter().enumerate() {
+            assert_eq!(grid.line_of_sight(*a, *b), grid.line_of_sight(*b, *a));
+            assert_eq!(seen[i].contains(b), seen[j].contains(a));
+        }
+    }
+}
+
+#[test]
+fn test_selection_ops() {
+    let mut selection = Selection::default();
+    selection.apply(SelectionOp::Replace, Hex::new(0, 0).range(1));
+    assert_eq!(7, selection.cells().count());
+    selection.apply(SelectionOp::Subtract, [Hex::new(0, 0), Hex::new(9, 9)]);
+    assert!(!selection.contains(Hex::new(0, 0)));
+    selection.apply(SelectionOp::Add, [Hex::new(9, 9)]);
+    assert_eq!(7, selection.cells().count());
+
+    selection.translate(Hex::new(1, 0));
+    assert!(selection.contains(Hex::new(2, 0)));
+    assert!(!selection.contains(Hex::new(1, 0)));
+    assert!(selection.contains(Hex::new(10, 9)));
+    selection.apply(SelectionOp::Replace, [Hex::new(2, 2)]);
+    assert_eq!(vec![Hex::new(2, 2)], selection.cells().collect::<Vec<_>>());
+    selection.clear();
+    assert!(selection.is_empty());
+}
+
+#[test]
+fn test_select_rect_and_lasso() {
+    let grid = Grid::make_hex(Hex::new(0, 0), 3);
+    let everything = grid.cells_in_rect(Point { x: -1000.0, y: -1000.0 }, Point { x: 1000.0, y: 1000.0 });
+    assert_eq!(37, everything.len());
+
+    let Point { x, y } = grid.cell_center(Hex::new(1, 1));
+    let corner = |dx: f64, dy: f64| Point { x: x + dx, y: y + dy };
+    assert_eq!(vec![Hex::new(1, 1)], grid.cells_in_rect(corner(0.01, 0.01), corner(-0.01, -0.01)));
+
+    //A triangle around the center cell and its right neighbor
+    let Point { x: right, .. } = grid.cell_center(Hex::new(1, 0));
+    let lasso = [Point { x: -0.01, y: -0.05 }, Point { x: right + 0.01, y: 0.0 }, Point { x: -0.01, y: 0.05 }];
+    let mut picked = grid.cells_in_lasso(&lasso);
+    picked.sort_by_key(|hex| hex.q());
+    assert_eq!(vec![Hex::new(0, 0), Hex::new(1, 0)], picked);
+    assert!(grid.cells_in_lasso(&[]).is_empty());
+}
+
+#[test]
+fn test_select_range_and_wand() {
+    let mut grid = Grid::make_hex(Hex::new(0, 0), 2);
+    //Cells outside the map are not picked
+    assert_eq!(4, grid.cells_in_range(Hex::new(2, 0), 1).len());
+
+    for r in -2..=2 {
+        grid.set_cell(Hex::new(0, r), Some(TerrainId(2)));
+    }
+    let options = FillOptions { fill_empty: false, ..Default::default() };
+    assert_eq!(7, grid.magic_wand(Hex::new(-1, 0), &options, true).len());
+    assert_eq!(14, grid.magic_wand(Hex::new(-1, 0), &options, false).len());
+    assert!(grid.magic_wand(Hex::new(9, 0), &options, false).is_empty());
+}
+
+#[test]
+fn test_clipboard_round_trip() {
+    let mut source = Grid::default();
+    let lava = source.terrains_mut().add(Terrain::new("Lava", Color32::from_rgb(255, 80, 0))).unwrap();
+    source.paint_cell(Hex::new(3, -2), TerrainId(0));
+    source.paint_cell(Hex::new(4, -2), lava);
+    let text = source.copy_cells([Hex::new(3, -2), Hex::new(4, -2), Hex::new(7, 7)]).unwrap();
+    assert!(source.copy_cells([Hex::new(7, 7)]).is_none());
+
+    //The target already uses Lava's id for something else
+    let mut target = Grid::default();
+    let ice = target.terrains_mut().add(Terrain::new("Ice", Color32::WHITE)).unwrap();
+    assert_eq!(ice, lava);
+    let pasted = target.paste_cells(&text, Some(Hex::new(0, 0))).unwrap();
+    let new_lava = pasted[1].1;
+    assert_eq!(vec![(Hex::new(0, 0).into(), TerrainId(0)), (Hex::new(1, 0).into(), new_lava)], pasted);
+    assert_ne!(ice, new_lava);
+    assert_eq!("Lava", target.terrains().get(new_lava).unwrap().name);
+
+    //Pasting again reuses the added terrain and lands where the cells were copied from
+    let count = target.terrains().iter().count();
+    let again = target.paste_cells(&text, None).unwrap();
+    assert!(again.contains(&(Hex::new(4, -2).into(), new_lava)));
+    assert_eq!(count, target.terrains().iter().count());
+
+    assert!(matches!(target.paste_cells("hello", None), Err(MapFileError::NotAMap)));
+
+    //Pasting far away must not overflow
+    assert!(matches!(target.paste_cells(&text, Some(Hex::new(i32::MAX - 1, 0))), Err(MapFileError::Corrupt(_))));
+    let mut far: serde_json::Value = serde_json::from_str(&text).unwrap();
+    far["origin"] = serde_json::json!([i32::MIN, 0]);
+    assert!(matches!(target.paste_cells(&far.to_string(), None), Err(MapFileError::Corrupt(_))));
+}
+
+#[test]
+fn test_clipboard_edges_and_corners() {
+    let mut source = Grid::default();
+    let layer = source.active_layer().id();
+    let wall = HexEdge::new(Hex::new(0, 0), Direction::East);
+    let tower = HexCorner::new(Hex::new(0, 0), Diagonal::North);
+    let outside = HexEdge::new(Hex::new(0, 0), Direction::West);
+    for (site, terrain) in [(Site::from(wall), 1), (tower.into(), 2), (outside.into(), 3)] {
+        source.set_layer_site(layer, site, Some(TerrainId(terrain)));
+    }
+    //Only the edges and corners lying wholly inside the copied cells come along
+    let cells = [Hex::new(0, 0), Hex::new(1, 0), Hex::new(1, -1), Hex::new(0, -1)];
+    let text = source.copy_cells(cells).unwrap();
+    assert!(source.copy_cells([Hex::new(0, 0)]).is_none());
+
+    let mut target = Grid::default();
+    let pasted = target.paste_cells(&text, Some(Hex::new(5, 5))).unwrap();
+    assert_eq!(2, pasted.len());
+    //Both are written from their owning cell, which becomes the origin
+    let moved = |site| Transform::Translate(Hex::new(5, 5)).apply_site(site);
+    assert!(pasted.contains(&(moved(wall.into()), TerrainId(1))));
+    assert!(pasted.contains(&(moved(tower.into()), TerrainId(2))));
+    assert_eq!(pasted, target.paste_cells(&text.replace("\"version\":2", "\"version\":1"), Some(Hex::new(5, 5))).unwrap());
+
+    let mut clipping: serde_json::Value = serde_json::from_str(&text).unwrap();
+    clipping["edges"][0]["side"] = "up".into();
+    assert!(matches!(target.paste_cells(&clipping.to_string(), None), Err(MapFileError::Corrupt(_))));
+}
+
+fn apply_changes(grid: &mut Grid, transform: Transform) {
+    for (layer, changes) in grid.transform_map(transform) {
+        for (site, value) in changes {
+            grid.set_layer_site(layer, site, value);
+        }
+    }
+}
+
+#[test]
+fn test_transform_overlapping_cells() {
+    //Shifting a row by one overwrites cells of the row itself
+    let mut grid = Grid::default();
+    for q in 0..3 {
+        grid.paint_cell(Hex::new(q, 0), TerrainId(q as u16));
+    }
+    apply_changes(&mut grid, Transform::Translate(Hex::new(1, 0)));
+    let mut cells: Vec<_> = grid.active_layer().cells().collect();
+    cells.sort_by_key(|(hex, _)| hex.q());
+    assert_eq!(vec![(Hex::new(1, 0), TerrainId(0)), (Hex::new(2, 0), TerrainId(1)), (Hex::new(3, 0), TerrainId(2))], cells);
+
+    //Unchanged cells are left out
+    let layer = grid.active_layer().id();
+    let pivot = Hex::new(2, 0);
+    let changes = grid.transform_changes(layer, [pivot], Transform::Rotate { pivot, steps: 2 });
+    assert!(changes.is_empty());
+}
+
+#[test]
+fn test_transform_whole_map() {
+    let mut grid = Grid::make_rhombus((0, 0), (3, 1));
+    let top = grid.add_layer("Marks");
+    grid.paint_cell(Hex::new(3, 1), TerrainId(4));
+    let cells = |grid: &Grid| grid.layers().iter().map(|layer| layer.data.clone()).collect::<Vec<_>>();
+    let original = cells(&grid);
+    //Only the active layer's cells count
+    let pivot = center_of(grid.active_layer().cells().map(|(hex, _)| hex)).unwrap();
+    assert_eq!(Hex::new(3, 1), pivot);
+
+    for steps in [1, 2, 3] {
+        apply_changes(&mut grid, Transform::Rotate { pivot: Hex::new(1, 0), steps });
+    }
+    assert_eq!(original, cells(&grid));
+    for axis in [HexAxis::Q, HexAxis::R, HexAxis::S] {
+        let mirror = Transform::Reflect { pivot: Hex::new(2, 1), axis };
+        apply_changes(&mut grid, mirror);
+        assert_eq!(8, grid.layers()[0].cells().count());
+        apply_changes(&mut grid, mirror);
+        assert_eq!(original, cells(&grid));
+    }
+
+    apply_changes(&mut grid, Transform::Rotate { pivot: Hex::new(0, 0), steps: 3 });
+    assert_eq!(Some(TerrainId(4)), grid.layer(top).unwrap().cell(Hex::new(-3, -1)));
+    assert_eq!(None, grid.layer(top).unwrap().cell(Hex::new(3, 1)));
+
+    //Locked layers stay put
+    grid.layer_mut(top).unwrap().locked = true;
+    apply_changes(&mut grid, Transform::Rotate { pivot: Hex::new(0, 0), steps: 3 });
+    assert_eq!(Some(TerrainId(4)), grid.layer(top).unwrap().cell(Hex::new(-3, -1)));
+    assert_eq!(original[0], grid.layers()[0].data);
+}
+
+#[test]
+fn test_transform_edges_and_corners() {
+    let mut grid = Grid::default();
+    let layer = grid.active_layer().id();
+    let river = HexEdge::new(Hex::new(0, 0), Direction::East);
+    let well = HexCorner::new(Hex::new(0, 0), Diagonal::North);
+    grid.set_layer_site(layer, river, Some(TerrainId(2)));
+    grid.set_layer_site(layer, well, Some(TerrainId(3)));
+
+    //Nothing is painted on the cells, edges and corners still move
+    apply_changes(&mut grid, Transform::Translate(Hex::new(2, -1)));
+    assert_eq!(None, grid.active_layer().edge(river));
+    assert_eq!(Some(TerrainId(2)), grid.active_layer().edge(HexEdge::new(Hex::new(2, -1), Direction::East)));
+    assert_eq!(Some(TerrainId(3)), grid.active_layer().corner(HexCorner::new(Hex::new(2, -1), Diagonal::North)));
+
+    apply_changes(&mut grid, Transform::Rotate { pivot: Hex::new(2, -1), steps: 1 });
+    assert_eq!(Some(TerrainId(2)), grid.active_layer().edge(HexEdge::new(Hex::new(2, -1), Direction::SouthEast)));
+    assert_eq!(Some(TerrainId(3)), grid.active_layer().corner(HexCorner::new(Hex::new(2, -1), Diagonal::EastNorthEast)));
+    let mirror = Transform::Reflect { pivot: Hex::new(2, -1), axis: HexAxis::R };
+    apply_changes(&mut grid, mirror);
+    apply_changes(&mut grid, mirror);
+    assert_eq!(1, grid.active_layer().edges().count());
+
+    //Within a selection, only edges wholly inside move
+    let changes = grid.transform_changes(layer, [Hex::new(2, -1)], Transform::Translate(Hex::new(0, 5)));
+    assert_eq!(0, changes.len());
+    let cells = HexEdge::new(Hex::new(2, -1), Direction::SouthEast).cells();
+    let changes = grid.transform_changes(layer, cells, Transform::Translate(Hex::new(0, 5)));
+    assert_eq!(2, changes.len());
+}
+
+#[test]
+fn test_mesh_of_edges_and_corners() {
+    let mut grid = Grid::make_hex(Hex::new(0, 0), 1);
+    let layer = grid.active_layer().id();
+    grid.set_layer_site(layer, HexEdge::new(Hex::new(0, 0), Direction::West), Some(TerrainId(1)));
+    grid.set_layer_site(layer, HexCorner::new(Hex::new(0, 0), Diagonal::South), Some(TerrainId(2)));
+    //Eight vertices per fan, edges and corners drawn over their cells
+    let mesh = grid.build_mesh();
+    assert_eq!(8 * 9, mesh.len());
+    let forest = grid.terrains().color(TerrainId(1)).to_array();
+    assert!(mesh[8 * 7..8 * 8].iter().all(|vertex| vertex.color == forest));
+    grid.layer_mut(layer).unwrap().visible = false;
+    assert!(grid.build_mesh().is_empty());
+}
+
+#[test]
+fn test_orientation_relayout() {
+    //Three rows of four cells in odd-r offset coordinates
+    let rows: Vec<_> = (0..3)
+        .flat_map(|row| (0..4).map(move |col| Hex::new(col - (row - (row & 1)) / 2, row)))
+        .collect();
+    let mut grid = Grid::from_cells(rows.clone());
+    let (min, max) = grid.bounds().unwrap();
+
+    grid.set_orientation(HexOrientation::Flat, true);
+    assert_eq!(HexOrientation::Flat, grid.orientation());
+    assert_eq!(12, grid.cells().count());
+    //Still four columns and three rows: wider than tall
+    let (flat_min, flat_max) = grid.bounds().unwrap();
+    assert!(flat_max.x - flat_min.x > flat_max.y - flat_min.y);
+    assert!(grid.cells().any(|hex| hex == Hex::new(3, -1)));
+
+    grid.set_orientation(HexOrientation::Pointy, true);
+    let mut cells: Vec<_> = grid.cells().collect();
+    cells.sort_by_key(|hex| (hex.r(), hex.q()));
+    assert_eq!(rows, cells);
+    assert_eq!(Some((min, max)), grid.bounds());
+
+    //Without relayout cells keep their coordinates
+    grid.set_orientation(HexOrientation::Flat, false);
+    assert!(rows.iter().all(|hex| grid.cell(*hex).is_some()));
+}
+
+#[test]
+fn test_hex_size() {
+    let mut grid = Grid::default();
+    grid.set_hex_size(Point { x: 2.0, y: 1.0 });
+    assert_eq!(Point { x: 2.0, y: 1.0 }, grid.layout().size);
+    grid.set_hex_size(Point { x: 0.0, y: 1.0 });
+    grid.set_hex_size(Point { x: f64::NAN, y: 1.0 });
+    assert_eq!(Point { x: 2.0, y: 1.0 }, grid.layout().size);
+
+    let round_tripped = round_trip(&grid);
+    assert_eq!(grid.layout(), round_tripped.layout());
+}
+
+#[test]
+fn test_render_png() {
+    assert!(matches!(Grid::default().render(&ImageOptions::default()), Err(ExportError::Empty)));
+
+    let grid = Grid::make_hex((0, 0), 1);
+    let options = ImageOptions { cell_size: 20.0, margin: 5, background: Color32::from_rgb(1, 2, 3), ..Default::default() };
+    let image = grid.render(&options).unwrap();
+    let (min, max) = grid.bounds().unwrap();
+    assert_eq!(((max.x - min.x) * 200.0 + 10.0).ceil() as u32, image.width());
+    assert_eq!(((max.y - min.y) * 200.0 + 10.0).ceil() as u32, image.height());
+
+    let terrain = grid.terrains().color(TerrainId::default()).to_srgba_unmultiplied();
+    let center = (image.width() / 2, image.height() / 2);
+    assert_eq!(terrain, image.get_pixel(center.0, center.1).0);
+    assert_eq!([1, 2, 3, 255], image.get_pixel(0, 0).0);
+
+    //Outlines run along the edge between the center cell and its east neighbor
+    let edge = (center.0 + 17, center.1);
+    let outlined = grid.render(&ImageOptions { outline_width: 3.0, outline_color: Color32::RED, ..options.clone() }).unwrap();
+    assert_eq!(terrain, image.get_pixel(edge.0, edge.1).0);
+    assert_eq!([255, 0, 0, 255], outlined.get_pixel(edge.0, edge.1).0);
+
+    let labeled = grid.render(&ImageOptions { labels: Some(CoordSystem::Axial), ..options.clone() }).unwrap();
+    assert_ne!(image, labeled);
+    assert_eq!(image.get_pixel(0, 0), labeled.get_pixel(0, 0));
+
+    let huge = ImageOptions { cell_size: 1e6, ..options };
+    assert!(matches!(grid.render(&huge), Err(ExportError::TooLarge { .. })));
+}
+
+#[test]
+fn test_write_svg() {
+    let mut grid = Grid::make_hex((0, 0), 1);
+    grid.layer_mut(grid.active_layer().id()).unwrap().name = "<Ground & sea>".into();
+    let options = ImageOptions { outline_width: 1.5, labels: Some(CoordSystem::Cube), ..Default::default() };
+    let mut buffer = Vec::new();
+    grid.write_svg(&mut buffer, &options).unwrap();
+    let svg = String::from_utf8(buffer).unwrap();
+
+    let image = grid.render(&options).unwrap();
+    assert!(svg.contains(&format!(r#"width="{}" height="{}""#, image.width(), image.height())), "{svg}");
+    assert_eq!(7 * 2, svg.matches("<polygon").count());
+    assert_eq!(7, svg.matches("<text").count());
+    assert!(svg.contains("&lt;Ground &amp; sea&gt;"));
+    assert!(svg.contains(r#"stroke-width="1.5""#));
+    assert!(svg.contains(">0, 0, 0</text>"));
+    assert!(svg.trim_end().ends_with("</svg>"));
+}
+
+#[test]
+fn test_export_edges_and_corners() {
+    let mut grid = Grid::make_hex((0, 0), 1);
+    let layer = grid.active_layer().id();
+    let wall = HexEdge::new(Hex::new(0, 0), Direction::East);
+    let tower = HexCorner::new(Hex::new(0, 0), Diagonal::South);
+    grid.set_layer_site(layer, wall, Some(TerrainId(1)));
+    grid.set_layer_site(layer, tower, Some(TerrainId(2)));
+
+    let options = ImageOptions { cell_size: 20.0, margin: 5, ..Default::default() };
+    let image = grid.render(&options).unwrap();
+    let min = grid.bounds().unwrap().0;
+    let pixel = |point: Point| {
+        let (x, y) = ((point.x - min.x) * 200.0 + 5.0, (point.y - min.y) * 200.0 + 5.0);
+        image.get_pixel(x as u32, y as u32).0
+    };
+    assert_eq!(grid.terrains().color(TerrainId(1)).to_srgba_unmultiplied(), pixel(wall.center(grid.layout())));
+    assert_eq!(grid.terrains().color(TerrainId(2)).to_srgba_unmultiplied(), pixel(tower.to_pixel(grid.layout())));
+
+    let mut buffer = Vec::new();
+    grid.write_svg(&mut buffer, &options).unwrap();
+    let svg = String::from_utf8(buffer).unwrap();
+    assert_eq!(1, svg.matches("<line").count());
+    assert_eq!(7 + 1, svg.matches("<polygon").count());
+}
+
+fn tiled_test_grid() -> Grid {
+    let mut grid = Grid::make_rhombus((-3, -3), (1, 2));
+    let mut forest = Terrain::new("Forest & <trees>", Color32::from_rgba_unmultiplied(10, 80, 20, 200)).with_cost(3).opaque();
+    forest.properties.insert("biome".into(), "temperate".into());
+    let forest = grid.terrains_mut().add(forest).unwrap();
+    for hex in [Hex::new(-3, -3), Hex::new(0, -1), Hex::new(1, 2)] {
+        grid.paint_cell(hex, forest);
+    }
+    let roads = grid.add_layer("Roads");
+    grid.set_layer_site(roads, Hex::new(-2, -1), Some(TerrainId(2)));
+    let layer = grid.layer_mut(roads).unwrap();
+    layer.visible = false;
+    layer.locked = true;
+    layer.opacity = 0.5;
+    grid
+}
+
+#[test]
+fn test_tiled_round_trip() {
+    for orientation in [HexOrientation::Pointy, HexOrientation::Flat] {
+        let mut grid = tiled_test_grid();
+        grid.set_orientation(orientation, false);
+        for tmx in [true, false] {
+            let mut buffer = Vec::new();
+            let read = if tmx {
+                grid.write_tmx(&mut buffer).unwrap();
+                Grid::read_tmx(buffer.as_slice()).unwrap()
+            } else {
+                grid.write_tmj(&mut buffer).unwrap();
+                Grid::read_tmj(buffer.as_slice()).unwrap()
+            };
+            let text = String::from_utf8(buffer).unwrap();
+            assert!(text.contains("hexagonal"), "{text}");
+            assert_same_layers(&grid, &read);
+            assert_eq!(orientation, read.orientation());
+            assert_eq!(grid.layout().size, read.layout().size);
+            let terrains = |grid: &Grid| grid.terrains().iter()
+                .map(|(id, terrain)| (id, terrain.clone()))
+                .collect::<Vec<_>>();
+            assert_eq!(terrains(&grid), terrains(&read));
+        }
+    }
+}
+
+#[test]
+fn test_tiled_stagger() {
+    //The first row is odd, moving it to 0 makes the shifted rows even ones
+    let grid = Grid::make_rhombus((0, 1), (2, 1));
+    let mut buffer = Vec::new();
+    grid.write_tmx(&mut buffer).unwrap();
+    let tmx = String::from_utf8(buffer).unwrap();
+    assert!(tmx.contains(r#"width="3" height="1""#), "{tmx}");
+    assert!(tmx.contains(r#"staggeraxis="y" staggerindex="even""#), "{tmx}");
+
+    let mut grid = Grid::make_rhombus((0, 0), (1, 1));
+    grid.set_orientation(HexOrientation::Flat, false);
+    let mut buffer = Vec::new();
+    grid.write_tmj(&mut buffer).unwrap();
+    let tmj = String::from_utf8(buffer).unwrap();
+    assert!(tmj.contains(r#""staggeraxis": "x""#), "{tmj}");
+    assert!(tmj.contains(r#""staggerindex": "odd""#), "{tmj}");
+}
+
+#[test]
+fn test_tiled_import() {
+    let tmx = r##"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.10" orientation="hexagonal" renderorder="right-down" width="2" height="2" tilewidth="28" tileheight="32" hexsidelength="16" staggeraxis="y" staggerindex="even" infinite="0">
+ <tileset firstgid="1" source="hexes.tsx"/>
+ <tileset firstgid="10" name="extra" tilewidth="28" tileheight="32" tilecount="2" columns="2">
+  <tile id="1"><properties><property name="name" value="Lava"/><property name="color" type="color" value="#ffff4000"/></properties></tile>
+ </tileset>
+ <objectgroup id="3" name="Objects"><object id="1" x="0" y="0"/></objectgroup>
+ <layer id="1" name="Ground" width="2" height="2">
+  <data encoding="csv">
+1,0,
+2,11
+</data>
+ </layer>
+</map>"##;
+    let grid = Grid::read_tmx(tmx.as_bytes()).unwrap();
+    assert_eq!(1, grid.layers().len());
+    assert_eq!(HexOrientation::Pointy, grid.orientation());
+    let mut cells: Vec<_> = grid.cells().collect();
+    cells.sort_by_key(|hex| (hex.r(), hex.q()));
+    //Even rows are shifted, so the second row starts half a cell to the left
+    assert_eq!(vec![Hex::new(0, 0), Hex::new(-1, 1), Hex::new(0, 1)], cells);
+    let lava = grid.cell(Hex::new(0, 1)).unwrap();
+    assert_eq!("Lava", grid.terrains().get(lava).unwrap().name);
+    assert_eq!(Color32::from_rgb(255, 64, 0), grid.terrains().color(lava));
+    assert_ne!(grid.cell(Hex::new(0, 0)), grid.cell(Hex::new(-1, 1)));
+
+    let base64 = tmx.replace(r#"encoding="csv""#, r#"encoding="base64""#);
+    assert!(matches!(Grid::read_tmx(base64.as_bytes()), Err(TiledError::Unsupported(_))));
+    let orthogonal = tmx.replace("hexagonal", "orthogonal");
+    assert!(matches!(Grid::read_tmx(orthogonal.as_bytes()), Err(TiledError::Unsupported(_))));
+    assert!(matches!(Grid::read_tmx("<svg/>".as_bytes()), Err(TiledError::Corrupt(_))));
+    assert!(matches!(Grid::read_tmj("{}".as_bytes()), Err(TiledError::Corrupt(_))));
+
+    //Sizes and origins that overflow are refused
+    let huge = tmx.replace(r#"width="2" height="2" tilewidth"#, r#"width="65536" height="65536" tilewidth"#);
+    assert!(matches!(Grid::read_tmx(huge.as_bytes()), Err(TiledError::Corrupt(_))));
+    let origin = r#" <properties><property name="hex-editor:origin-row" type="int" value="2147483647"/></properties>
+ <tileset firstgid="1""#;
+    let far = tmx.replacen(r#" <tileset firstgid="1""#, origin, 1);
+    assert!(matches!(Grid::read_tmx(far.as_bytes()), Err(TiledError::Corrupt(_))));
+}
+
+#[test]
+fn test_tiled_too_large() {
+    let mut grid = Grid::default();
+    for hex in [Hex::new(-1_500_000_000, 0), Hex::new(1_500_000_000, 0), Hex::new(0, 5)] {
+        grid.paint_cell(hex, TerrainId(0));
+    }
+    assert!(matches!(grid.write_tmj(&mut Vec::new()), Err(TiledError::Unsupported(_))));
+}
+
+#[test]
+fn test_tiled_refuses_edges_and_corners() {
+    let mut grid = tiled_test_grid();
+    let layer = grid.active_layer().id();
+    grid.set_layer_site(layer, HexCorner::new(Hex::new(0, 0), Diagonal::North), Some(TerrainId(1)));
+    assert!(matches!(grid.write_tmx(&mut Vec::new()), Err(TiledError::Unsupported(_))));
+    assert!(matches!(grid.write_tmj(&mut Vec::new()), Err(TiledError::Unsupported(_))));
+
+    //Refusing leaves a file already there untouched
+    let path = std::env::temp_dir().join(format!("hex-editor-grid-{}-refused.tmx", std::process::id()));
+    std::fs::write(&path, "previous").unwrap();
+    assert!(matches!(grid.save_tiled(&path), Err(TiledError::Unsupported(_))));
+    assert_eq!("previous", std::fs::read_to_string(&path).unwrap());
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_image_round_trip() {
+    let mut grid = Grid::make_hex((0, 0), 3);
+    let terrains: Vec<_> = grid.terrains().ids().collect();
+    let cells: Vec<_> = grid.cells().collect();
+    for (index, hex) in cells.iter().enumerate() {
+        grid.paint_cell(*hex, terrains[index % terrains.len()]);
+    }
+    let image = grid.render(&ImageOptions { cell_size: 20.0, margin: 0, ..Default::default() }).unwrap();
+
+    let palette: Vec<_> = grid.terrains().iter().map(|(_, terrain)| terrain.clone()).collect();
+    for sampling in Sampling::ALL {
+        let options = RasterImport {
+            origin: grid.bounds().unwrap().0,
+            scale: 0.1 / 20.0,
+            sampling,
+            palette: palette.clone(),
+            ..Default::default()
+        };
+        let traced = Grid::from_image(grid.layout(), &image, &options).unwrap();
+        assert_eq!(cells.len(), traced.cells().count(), "{sampling:?}");
+        for hex in &cells {
+            let name = |grid: &Grid| grid.terrains().get(grid.cell(*hex).unwrap()).unwrap().name.clone();
+            assert_eq!(name(&grid), name(&traced), "{sampling:?} {hex:?}");
+        }
+    }
+}
+
+#[test]
+fn test_image_sampling() {
+    //Left half red, right half blue with a transparent bottom row of pixels
+    let image = image::RgbaImage::from_fn(40, 20, |x, y| match (x < 20, y < 19) {
+        (_, false) => image::Rgba([0, 0, 0, 0]),
+        (true, true) => image::Rgba([250, 10, 10, 255]),
+        (false, true) => image::Rgba([10, 10, 250, 255]),
+    });
+    let layout = Grid::default().layout();
+    let mut options = RasterImport { origin: Point { x: 0.0, y: 0.0 }, sampling: Sampling::Center, ..Default::default() };
+    options.fit_width(&image, layout, 8.0);
+    assert!((options.scale * 40.0 - 8.0 * 3f64.sqrt() * 0.1).abs() < 1e-9);
+
+    let traced = Grid::from_image(layout, &image, &options).unwrap();
+    let mut names: Vec<_> = traced.terrains().iter().map(|(_, terrain)| terrain.name.clone()).collect();
+    names.sort();
+    assert_eq!(vec!["#0a0afa", "#fa0a0a"], names);
+    let red = traced.cell(traced.sample_cell(Point { x: 0.1, y: 0.1 })).unwrap();
+    let blue = traced.cell(traced.sample_cell(Point { x: 1.2, y: 0.1 })).unwrap();
+    assert_eq!(Color32::from_rgb(250, 10, 10), traced.terrains().color(red));
+    assert_eq!(Color32::from_rgb(10, 10, 250), traced.terrains().color(blue));
+
+    //Cells straddling both halves average to purple, which snaps to the nearest swatch
+    options.sampling = Sampling::Average;
+    options.palette = vec![Terrain::new("Red", Color32::RED), Terrain::new("Purple", Color32::from_rgb(128, 0, 128))];
+    let traced = Grid::from_image(layout, &image, &options).unwrap();
+    let names: HashSet<_> = traced.cells()
+        .map(|hex| traced.terrains().get(traced.cell(hex).unwrap()).unwrap().name.clone())
+        .collect();
+    assert_eq!(HashSet::from(["Red".to_string(), "Purple".to_string()]), names);
+
+    let empty = Grid::from_image(layout, &image, &RasterImport { scale: 0.0, ..options }).unwrap();
+    assert_eq!(0, empty.cells().count());
+}
+
+
+#[test]
+fn test_noise() {
+    let noise = Noise::new(5);
+    assert_eq!(0.0, noise.sample(3.0, -7.0));
+    let mut rng = Rng::new(1);
+    for _ in 0..1000 {
+        let (x, y) = (rng.next_f64() * 100.0 - 50.0, rng.next_f64() * 100.0 - 50.0);
+        assert!((-1.0..=1.0).contains(&noise.sample(x, y)));
+        assert!((0.0..=1.0).contains(&noise.fractal(x, y, 5, 0.5, 2.0)));
+    }
+    let samples = |noise: &Noise| (0..20).map(|step| noise.fractal(step as f64 * 0.3, 0.7, 3, 0.5, 2.0)).collect::<Vec<_>>();
+    assert_eq!(samples(&Noise::new(5)), samples(&noise));
+    assert_ne!(samples(&Noise::new(6)), samples(&noise));
+}
+
+#[test]
+fn test_generate() {
+    let layout = Grid::default().layout();
+    let generator = Generator { seed: 9, scale: 4.0, ..Default::default() };
+    let grid = Grid::generate(layout, Hex::new(0, 0).range(10), &generator);
+    assert_eq!(331, grid.cells().count());
+    assert_eq!(generator.biomes.len(), grid.terrains().len());
+    let again = Grid::generate(layout, Hex::new(0, 0).range(10), &generator);
+    assert_same_layers(&grid, &again);
+    let other = Grid::generate(layout, Hex::new(0, 0).range(10), &Generator { seed: 10, ..generator.clone() });
+    assert!(grid.cells().any(|hex| grid.cell(hex) != other.cell(hex)));
+
+    //Half the cells or so are low, half high
+    let split = Generator {
+        biomes: vec![
+            Biome::new(Terrain::new("Low", Color32::BLUE), 0.5, 1.0),
+            Biome::new(Terrain::new("High", Color32::RED), 0.0, 0.0),
+        ],
+        ..generator.clone()
+    };
+    assert_eq!(Some(1), split.biome(0.7, 0.2));
+    let grid = Grid::generate(layout, Hex::new(0, 0).range(10), &split);
+    let low = grid.cells().filter(|hex| grid.cell(*hex) == Some(TerrainId(0))).count();
+    assert!((50..280).contains(&low), "{low}");
+
+    let empty = Generator { biomes: Vec::new(), ..generator };
+    assert_eq!(None, empty.biome(0.5, 0.5));
+    assert_eq!(0, Grid::generate(layout, Hex::new(0, 0).range(3), &empty).cells().count());
+}
+
+#[test]
+fn test_synthesize_learned() {
+    //Stripes along the q axis: cells match their north-west and south-east neighbors only
+    let mut grid = Grid::default();
+    for hex in shapes::rhombus(Hex::new(20, 0), Hex::new(25, 5)) {
+        grid.paint_cell(hex, if hex.q() % 2 == 0 { GRASS } else { WATER });
+    }
+    let rules = Rules::learn(&grid, grid.cells().collect::<Vec<_>>());
+    assert_eq!(2, rules.len());
+    assert_eq!(6, rules.adjacencies());
+
+    let region: Vec<_> = Hex::new(0, 0).range(4).collect();
+    let options = Synthesis { seed: 3, ..Default::default() };
+    let cells = grid.synthesize(region.clone(), &rules, &options).unwrap();
+    assert_eq!(region.len(), cells.len());
+    let stripe = |hex: Hex| cells.iter().find(|(cell, _)| *cell == hex).unwrap().1;
+    let phase = stripe(Hex::new(0, 0));
+    for (hex, terrain) in &cells {
+        assert_eq!(hex.q().rem_euclid(2) == 0, *terrain == phase, "{hex:?}");
+    }
+    assert_eq!(cells, grid.synthesize(region, &rules, &options).unwrap());
+
+    //A painted cell pins its stripe
+    grid.paint_cell((1, 0), GRASS);
+    let cells = grid.synthesize(Hex::new(0, 0).range(2), &rules, &Synthesis { seed: 8, ..Default::default() }).unwrap();
+    assert_eq!(18, cells.len());
+    assert!(cells.iter().all(|(hex, terrain)| (hex.q().rem_euclid(2) == 1) == (*terrain == GRASS)));
+    let repainted = grid.synthesize(Hex::new(0, 0).range(2), &rules, &Synthesis { keep_painted: false, ..Default::default() }).unwrap();
+    assert_eq!(19, repainted.len());
+}
+
+#[test]
+fn test_synthesize_failures() {
+    let mut grid = Grid::default();
+    let apart = Rules::parse("Grass, any, Grass\nwater, any, WATER\n", grid.terrains()).unwrap();
+    grid.paint_cell((0, 0), GRASS);
+    grid.paint_cell((2, 0), WATER);
+    assert_eq!(
+        Err(SynthesisError::Conflict {
+            cell: Hex::new(1, 0),
+            painted: vec![(Direction::East, "Water".into()), (Direction::West, "Grass".into())],
+        }),
+        grid.synthesize([Hex::new(1, 0)], &apart, &Synthesis::default()),
+    );
+
+    grid.paint_cell((0, 1), TerrainId(5));
+    assert!(matches!(
+        grid.synthesize([Hex::new(-1, 1)], &apart, &Synthesis::default()),
+        Err(SynthesisError::UnknownTerrain { terrain, .. }) if terrain == "Snow"
+    ));
+    assert_eq!(Err(SynthesisError::NoRules), grid.synthesize([Hex::new(5, 5)], &Rules::default(), &Synthesis::default()));
+
+    //Two terrains cannot alternate around the three cells meeting at a corner
+    let alternate = Rules::parse("Grass, any, Water", grid.terrains()).unwrap();
+    let region: Vec<_> = Hex::new(10, 0).range(1).collect();
+    assert!(matches!(grid.synthesize(region.clone(), &alternate, &Synthesis::default()), Err(SynthesisError::NoSolution { .. })));
+    let hasty = Synthesis { max_backtracks: 0, ..Default::default() };
+    assert!(matches!(grid.synthesize(region, &alternate, &hasty), Err(SynthesisError::GaveUp { backtracks: 0, .. })));
+}
+
+#[test]
+fn test_rules_parse() {
+    let terrains = Grid::default().terrains().clone();
+    let rules = Rules::parse("# Coast\nSand, any, Water # wet\nSand, north-east, Grass\n\nSand, 3\n", &terrains).unwrap();
+    assert_eq!(3, rules.len());
+    assert_eq!(7, rules.adjacencies());
+    for (text, line) in [("Sand, any, Lava", 1), ("Sand, any, Sand\nSand, up, Sand", 2), ("Sand, -1", 1), ("Sand", 1)] {
+        assert!(matches!(Rules::parse(text, &terrains), Err(RulesError::Invalid { line: found, .. }) if found == line), "{text}");
+    }
+}
+
+#[test]
+fn test_terrain_ids_run_out() {
+    let mut terrains = TerrainRegistry::default();
+    terrains.insert(TerrainId(u16::MAX), Terrain::new("Last", Color32::BLACK));
+    //Past the highest id, gaps are reused
+    assert_eq!(Some(TerrainId(0)), terrains.add(Terrain::new("First", Color32::WHITE)));
+    for id in 1..u16::MAX {
+        terrains.insert(TerrainId(id), Terrain::new("Filler", Color32::WHITE));
+    }
+    assert_eq!(None, terrains.add(Terrain::new("Extra", Color32::WHITE)));
+    assert_eq!(usize::from(u16::MAX) + 1, terrains.len());
+}
@@ -0,0 +1,502 @@
+//! Exchange of maps with the [Tiled](https://www.mapeditor.org) editor, as
+//! `orientation="hexagonal"` maps in its XML (`.tmx`) or JSON (`.tmj`) format.
+//!
+//! Pointy maps become `staggeraxis="y"` maps, whose rows are shifted, and flat maps
+//! `staggeraxis="x"` ones, whose columns are. Cells go through odd offset
+//! coordinates, which are moved so the first row and column are 0. The move is
+//! kept in the `hex-editor:origin-col` and `hex-editor:origin-row` map properties
+//! so reading the file back puts every cell where it was; when it is odd along the
+//! staggered axis, `staggerindex` becomes `"even"` to keep the same lines shifted.
+//! Tiled sizes tiles in whole pixels, so the exact hex size is kept in the
+//! `hex-editor:hex-width` and `hex-editor:hex-height` properties.
+//!
+//! Terrains form a tileset without images whose tile ids are the terrain ids. Each
+//! tile lists the terrain in its properties: `name`, `color`, `movement_cost`,
+//! `passable` and `opaque`, then the terrain's own properties. Every layer becomes
+//! a tile layer with CSV data. Tiles cannot hold painted edges and corners, so
+//! maps with any are refused rather than saved without them.
+//!
+//! Reading also accepts maps made in Tiled. Tiles without those properties become
+//! new terrains with generated colors, and layers other than tile layers are
+//! skipped. Their cells get the proportions of their tiles.
+
+use std::{
+    collections::HashMap, fmt, fs::{self, File}, io::{self, BufReader, Read, Write}, path::Path
+};
+
+use egui::{ecolor::Hsva, Color32};
+
+use super::{
+    map_file::checked_cell, Grid, Hex, HexOrientation, Layer, LayerId, Layout, OffsetCoord, Parity, Point, Terrain, TerrainId,
+    TerrainRegistry, LAYOUT_ORIENTATION_FLAT, LAYOUT_ORIENTATION_POINTY
+};
+
+mod tmj;
+mod tmx;
+
+/// Tiled release whose format the files follow.
+const TILED_VERSION: &str = "1.10";
+const ORIGIN_COL: &str = "hex-editor:origin-col";
+const ORIGIN_ROW: &str = "hex-editor:origin-row";
+const HEX_WIDTH: &str = "hex-editor:hex-width";
+const HEX_HEIGHT: &str = "hex-editor:hex-height";
+/// Pixels from the center of a cell to its farthest corner in exported tiles.
+const TILE_SIZE: f64 = 32.0;
+/// Tile id bits Tiled uses for flipped and rotated tiles.
+const FLIP_FLAGS: u32 = 0xf000_0000;
+
+#[derive(Debug)]
+pub enum TiledError {
+    Io(io::Error),
+    /// The file is not a well formed Tiled map.
+    Corrupt(String),
+    /// The file uses a Tiled feature the editor has no counterpart for.
+    Unsupported(String),
+    /// The extension is neither `.tmx` nor `.tmj`.
+    UnknownFormat,
+}
+
+impl fmt::Display for TiledError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "could not access the Tiled map: {error}"),
+            Self::Corrupt(reason) => write!(f, "the Tiled map is corrupt: {reason}"),
+            Self::Unsupported(reason) => write!(f, "the Tiled map is not supported: {reason}"),
+            Self::UnknownFormat => write!(f, "Tiled maps end in .tmx or .tmj"),
+        }
+    }
+}
+
+impl std::error::Error for TiledError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for TiledError {
+    fn from(value: io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+/// What the TMX and TMJ formats share, with the names Tiled gives things.
+#[derive(Debug, Default, PartialEq)]
+struct TiledMap {
+    /// `None` for maps that are not hexagonal.
+    stagger: Option<Stagger>,
+    width: u32,
+    height: u32,
+    tile_width: u32,
+    tile_height: u32,
+    hex_side_length: u32,
+    infinite: bool,
+    properties: Vec<Property>,
+    tilesets: Vec<Tileset>,
+    layers: Vec<TileLayer>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Stagger {
+    /// `Pointy` for `staggeraxis="y"`, `Flat` for `"x"`.
+    axis: HexOrientation,
+    index: Parity,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Property {
+    name: String,
+    /// `string`, `int`, `float`, `bool` or `color`.
+    kind: String,
+    value: String,
+}
+
+#[derive(Debug, Default, PartialEq)]
+struct Tileset {
+    first_gid: u32,
+    name: String,
+    tile_count: u32,
+    tiles: Vec<Tile>,
+}
+
+#[derive(Debug, PartialEq)]
+struct Tile {
+    id: u32,
+    properties: Vec<Property>,
+}
+
+#[derive(Debug, PartialEq)]
+struct TileLayer {
+    id: u32,
+    name: String,
+    visible: bool,
+    locked: bool,
+    opacity: f32,
+    /// Global tile ids row by row, 0 for empty cells.
+    data: Vec<u32>,
+}
+
+impl Property {
+    fn new(name: &str, kind: &str, value: impl ToString) -> Self {
+        Self { name: name.into(), kind: kind.into(), value: value.to_string() }
+    }
+}
+
+impl Stagger {
+    fn axis_name(self) -> &'static str {
+        match self.axis {
+            HexOrientation::Pointy => "y",
+            HexOrientation::Flat => "x",
+        }
+    }
+
+    fn index_name(self) -> &'static str {
+        match self.index {
+            Parity::Even => "even",
+            Parity::Odd => "odd",
+        }
+    }
+
+    fn parse(axis: &str, index: &str) -> Result<Self, TiledError> {
+        let axis = match axis {
+            "y" => HexOrientation::Pointy,
+            "x" => HexOrientation::Flat,
+            _ => return Err(TiledError::Corrupt(format!("invalid stagger axis {axis:?}"))),
+        };
+        let index = match index {
+            "even" => Parity::Even,
+            "odd" => Parity::Odd,
+            _ => return Err(TiledError::Corrupt(format!("invalid stagger index {index:?}"))),
+        };
+        Ok(Self { axis, index })
+    }
+
+    fn to_offset(self, hex: Hex) -> OffsetCoord {
+        match self.axis {
+            HexOrientation::Pointy => self.index.r_from_cube(hex),
+            HexOrientation::Flat => self.index.q_from_cube(hex),
+        }
+    }
+
+    /// The cell at `coord`, if it fits in the coordinate range.
+    fn to_cube(self, coord: OffsetCoord) -> Option<Hex> {
+        match self.axis {
+            HexOrientation::Pointy => self.index.r_to_cube_checked(coord),
+            HexOrientation::Flat => self.index.q_to_cube_checked(coord),
+        }
+    }
+
+    /// The same grid with its offset coordinates moved by `(col, row)`.
+    fn moved(self, col: i32, row: i32) -> Self {
+        let step = match self.axis {
+            HexOrientation::Pointy => row,
+            HexOrientation::Flat => col,
+        };
+        let index = match (self.index, step % 2 == 0) {
+            (index, true) => index,
+            (Parity::Even, false) => Parity::Odd,
+            (Parity::Odd, false) => Parity::Even,
+        };
+        Self { index, ..self }
+    }
+}
+
+impl Grid {
+    /// Saves the map as a `.tmx` or `.tmj` file, by extension.
+    pub fn save_tiled(&self, path: impl AsRef<Path>) -> Result<(), TiledError> {
+        let path = path.as_ref();
+        let tmx = tiled_extension(path)?;
+        //Written out first, so a refused map leaves the file it would replace alone
+        let mut buffer = Vec::new();
+        if tmx {
+            self.write_tmx(&mut buffer)?;
+        } else {
+            self.write_tmj(&mut buffer)?;
+        }
+        fs::write(path, buffer)?;
+        Ok(())
+    }
+
+    /// Loads a `.tmx` or `.tmj` file, by extension.
+    pub fn load_tiled(path: impl AsRef<Path>) -> Result<Self, TiledError> {
+        let path = path.as_ref();
+        let tmx = tiled_extension(path)?;
+        let reader = BufReader::new(File::open(path)?);
+        if tmx {
+            Self::read_tmx(reader)
+        } else {
+            Self::read_tmj(reader)
+        }
+    }
+
+    pub fn write_tmx(&self, writer: impl Write) -> Result<(), TiledError> {
+        tmx::write(&self.to_tiled()?, writer)
+    }
+
+    pub fn read_tmx(reader: impl Read) -> Result<Self, TiledError> {
+        tmx::read(reader)?.into_grid()
+    }
+
+    pub fn write_tmj(&self, writer: impl Write) -> Result<(), TiledError> {
+        tmj::write(&self.to_tiled()?, writer)
+    }
+
+    pub fn read_tmj(reader: impl Read) -> Result<Self, TiledError> {
+        tmj::read(reader)?.into_grid()
+    }
+
+    fn to_tiled(&self) -> Result<TiledMap, TiledError> {
+        if self.layers.iter().any(|layer| layer.edges().next().is_some() || layer.corners().next().is_some()) {
+            return Err(TiledError::Unsupported("Tiled maps cannot hold painted edges and corners".into()));
+        }
+        let stagger = Stagger { axis: self.orientation(), index: Parity::Odd };
+        let coords: Vec<_> = self.layers.iter()
+            .flat_map(|layer| layer.data.keys())
+            .map(|hex| stagger.to_offset(*hex))
+            .collect();
+        let min_col = coords.iter().map(|coord| coord.col).min().unwrap_or(0);
+        let min_row = coords.iter().map(|coord| coord.row).min().unwrap_or(0);
+        let max_col = coords.iter().map(|coord| coord.col).max().unwrap_or(0);
+        let max_row = coords.iter().map(|coord| coord.row).max().unwrap_or(0);
+        let too_large = || TiledError::Unsupported("the map is too large for Tiled".into());
+        let width = max_col.abs_diff(min_col).checked_add(1).ok_or_else(too_large)?;
+        let height = max_row.abs_diff(min_row).checked_add(1).ok_or_else(too_large)?;
+        let tile_count = width.checked_mul(height).and_then(|count| usize::try_from(count).ok()).ok_or_else(too_large)?;
+
+        //Tiled measures the full width and height of a tile, and the straight side
+        //along the staggered axis
+        let Point { x, y } = self.layout.size;
+        let scale = TILE_SIZE / x.max(y);
+        let (tile_width, tile_height, hex_side_length) = match stagger.axis {
+            HexOrientation::Pointy => (3f64.sqrt() * x * scale, 2.0 * y * scale, y * scale),
+            HexOrientation::Flat => (2.0 * x * scale, 3f64.sqrt() * y * scale, x * scale),
+        };
+
+        let tiles = self.terrains.iter()
+            .map(|(TerrainId(id), terrain)| {
+                let [r, g, b, a] = terrain.color.to_srgba_unmultiplied();
+                let mut properties = vec![
+                    Property::new("name", "string", &terrain.name),
+                    Property::new("color", "color", format!("#{a:02x}{r:02x}{g:02x}{b:02x}")),
+                    Property::new("movement_cost", "int", terrain.movement_cost),
+                    Property::new("passable", "bool", terrain.passable),
+                    Property::new("opaque", "bool", terrain.opaque),
+                ];
+                properties.extend(terrain.properties.iter().map(|(name, value)| Property::new(name, "string", value)));
+                Tile { id: id as u32, properties }
+            })
+            .collect::<Vec<_>>();
+        let tileset = Tileset {
+            first_gid: 1,
+            name: "Terrains".into(),
+            tile_count: tiles.iter().map(|tile| tile.id + 1).max().unwrap_or(0),
+            tiles,
+        };
+
+        let layers = (1..).zip(&self.layers)
+            .map(|(id, layer)| {
+                let mut data = vec![0; tile_count];
+                for (hex, TerrainId(terrain)) in layer.cells() {
+                    let OffsetCoord { col, row } = stagger.to_offset(hex);
+                    data[(row.abs_diff(min_row) * width + col.abs_diff(min_col)) as usize] = terrain as u32 + tileset.first_gid;
+                }
+                TileLayer { id, name: layer.name.clone(), visible: layer.visible, locked: layer.locked, opacity: layer.opacity, data }
+            })
+            .collect();
+
+        Ok(TiledMap {
+            stagger: Some(stagger.moved(-min_col, -min_row)),
+            width,
+            height,
+            tile_width: tile_width.round().max(1.0) as u32,
+            tile_height: tile_height.round().max(1.0) as u32,
+            hex_side_length: hex_side_length.round() as u32,
+            infinite: false,
+            properties: vec![
+                Property::new(ORIGIN_COL, "int", min_col),
+                Property::new(ORIGIN_ROW, "int", min_row),
+                Property::new(HEX_WIDTH, "float", x),
+                Property::new(HEX_HEIGHT, "float", y),
+            ],
+            tilesets: vec![tileset],
+            layers,
+        })
+    }
+}
+
+impl TiledMap {
+    fn into_grid(self) -> Result<Grid, TiledError> {
+        let Some(stagger) = self.stagger else {
+            return Err(TiledError::Unsupported("only hexagonal maps can be opened".into()));
+        };
+        if self.infinite {
+            return Err(TiledError::Unsupported("infinite maps cannot be opened".into()));
+        }
+        if self.tile_width == 0 || self.tile_height == 0 {
+            return Err(TiledError::Corrupt("tiles must have a size".into()));
+        }
+        let property = |name: &str| -> Result<Option<f64>, TiledError> {
+            match self.properties.iter().find(|property| property.name == name) {
+                Some(property) => property.value.parse().map(Some)
+                    .map_err(|_| TiledError::Corrupt(format!("{name} must be a number"))),
+                None => Ok(None),
+            }
+        };
+        let origin = |name: &str| -> Result<i32, TiledError> {
+            match property(name)? {
+                Some(value) if value.fract() == 0.0 && value.abs() <= i32::MAX as f64 => Ok(value as i32),
+                Some(_) => Err(TiledError::Corrupt(format!("{name} must be a whole number"))),
+                None => Ok(0),
+            }
+        };
+        let (origin_col, origin_row) = (origin(ORIGIN_COL)?, origin(ORIGIN_ROW)?);
+        let stagger_at_origin = stagger.moved(origin_col, origin_row);
+
+        let (width, height) = (self.tile_width as f64, self.tile_height as f64);
+        let (x, y) = match stagger.axis {
+            HexOrientation::Pointy => (width / 3f64.sqrt(), height / 2.0),
+            HexOrientation::Flat => (width / 2.0, height / 3f64.sqrt()),
+        };
+        //Cells keep their proportions at the size new maps have
+        let scale = Grid::default().layout.size.x / x.max(y);
+        let size = match (property(HEX_WIDTH)?, property(HEX_HEIGHT)?) {
+            (Some(x), Some(y)) if x > 0.0 && y > 0.0 && x.is_finite() && y.is_finite() => Point { x, y },
+            _ => Point { x: x * scale, y: y * scale },
+        };
+        let layout = Layout {
+            orientation: match stagger.axis {
+                HexOrientation::Pointy => LAYOUT_ORIENTATION_POINTY,
+                HexOrientation::Flat => LAYOUT_ORIENTATION_FLAT,
+            },
+            size,
+            origin: Point { x: 0.0, y: 0.0 },
+        };
+
+        let mut terrains = TerrainRegistry::default();
+        let mut gids: HashMap<u32, TerrainId> = HashMap::new();
+        //Tiles of the first tileset keep their ids, the terrain ids they were saved from
+        if let Some(tileset) = self.tilesets.first() {
+            for tile in &tileset.tiles {
+                let id = TerrainId(u16::try_from(tile.id)
+                    .map_err(|_| TiledError::Unsupported(format!("tile {} has too high an id", tile.id)))?);
+                terrains.insert(id, tile_terrain(tileset, tile.id, &tile.properties)?);
+                gids.insert(tileset.first_gid + tile.id, id);
+            }
+        }
+
+        if self.layers.is_empty() {
+            return Err(TiledError::Unsupported("the map has no tile layers".into()));
+        }
+        let tile_count = self.width.checked_mul(self.height)
+            .and_then(|count| usize::try_from(count).ok())
+            .ok_or_else(|| TiledError::Corrupt(format!("{}×{} tiles are too many", self.width, self.height)))?;
+        let out_of_range = || TiledError::Corrupt("tiles lie beyond the coordinate range".into());
+        let mut layers = Vec::with_capacity(self.layers.len());
+        for (id, record) in (0..).zip(self.layers) {
+            if record.data.len() != tile_count {
+                return Err(TiledError::Corrupt(format!("layer {:?} does not have {}×{} tiles", record.name, self.width, self.height)));
+            }
+            let mut layer = Layer::new(LayerId(id), record.name);
+            layer.visible = record.visible;
+            layer.locked = record.locked;
+            layer.opacity = record.opacity.clamp(0.0, 1.0);
+            for (index, gid) in record.data.into_iter().enumerate() {
+                let gid = gid & !FLIP_FLAGS;
+                if gid == 0 {
+                    continue;
+                }
+                let terrain = match gids.get(&gid) {
+                    Some(terrain) => *terrain,
+                    None => {
+                        let terrain = foreign_terrain(&self.tilesets, gid, &terrains)?;
+                        let id = terrains.add(terrain)
+                            .ok_or_else(|| TiledError::Corrupt(format!("tile {gid} does not fit in the terrain ids left")))?;
+                        gids.insert(gid, id);
+                        id
+                    }
+                };
+                let col = i32::try_from(index as u32 % self.width).ok().and_then(|col| col.checked_add(origin_col));
+                let row = i32::try_from(index as u32 / self.width).ok().and_then(|row| row.checked_add(origin_row));
+                let (Some(col), Some(row)) = (col, row) else {
+                    return Err(out_of_range());
+                };
+                let hex = stagger_at_origin.to_cube(OffsetCoord { col, row })
+                    .and_then(|hex| checked_cell(hex.q(), hex.r()).ok())
+                    .ok_or_else(out_of_range)?;
+                layer.set_cell(hex, Some(terrain));
+            }
+            layers.push(layer);
+        }
+        Ok(Grid { layout, next_layer_id: layers.len() as u32, layers, active_layer: 0, terrains })
+    }
+}
+
+/// Whether `path` is a TMX file rather than a TMJ one.
+fn tiled_extension(path: &Path) -> Result<bool, TiledError> {
+    let extension = path.extension().and_then(|extension| extension.to_str()).unwrap_or_default();
+    match extension.to_ascii_lowercase().as_str() {
+        "tmx" => Ok(true),
+        "tmj" => Ok(false),
+        _ => Err(TiledError::UnknownFormat),
+    }
+}
+
+/// A terrain from the properties of a tile, with defaults for missing ones.
+fn tile_terrain(tileset: &Tileset, id: u32, properties: &[Property]) -> Result<Terrain, TiledError> {
+    let mut terrain = Terrain::new(format!("{} {id}", tileset.name), generated_color(id));
+    for Property { name, kind: _, value } in properties {
+        let invalid = || TiledError::Corrupt(format!("tile {id} has an invalid {name}"));
+        match name.as_str() {
+            "name" => terrain.name = value.clone(),
+            "color" => terrain.color = tiled_color(value).ok_or_else(invalid)?,
+            "movement_cost" => terrain.movement_cost = value.parse().map_err(|_| invalid())?,
+            "passable" => terrain.passable = value.parse().map_err(|_| invalid())?,
+            "opaque" => terrain.opaque = value.parse().map_err(|_| invalid())?,
+            _ => {
+                terrain.properties.insert(name.clone(), value.clone());
+            }
+        }
+    }
+    Ok(terrain)
+}
+
+/// A terrain for a tile the first tileset does not describe.
+fn foreign_terrain(tilesets: &[Tileset], gid: u32, terrains: &TerrainRegistry) -> Result<Terrain, TiledError> {
+    let tileset = tilesets.iter()
+        .filter(|tileset| tileset.first_gid <= gid)
+        .max_by_key(|tileset| tileset.first_gid)
+        .ok_or_else(|| TiledError::Corrupt(format!("no tileset has tile {gid}")))?;
+    let id = gid - tileset.first_gid;
+    let properties = tileset.tiles.iter()
+        .find(|tile| tile.id == id)
+        .map_or(&[][..], |tile| &tile.properties);
+    let mut terrain = tile_terrain(tileset, id, properties)?;
+    if !properties.iter().any(|property| property.name == "color") {
+        terrain.color = generated_color(terrains.len() as u32);
+    }
+    Ok(terrain)
+}
+
+/// Evenly spread hues, so generated terrains are told apart.
+fn generated_color(index: u32) -> Color32 {
+    let hue = (index as f32 * 0.618_034).fract();
+    Hsva::new(hue, 0.55, 0.8, 1.0).into()
+}
+
+/// Reads Tiled's `#aarrggbb` or `#rrggbb`.
+fn tiled_color(text: &str) -> Option<Color32> {
+    let digits = text.strip_prefix('#')?;
+    if !digits.is_ascii() || !(digits.len() == 6 || digits.len() == 8) {
+        return None;
+    }
+    let channels = (0..digits.len() / 2)
+        .map(|i| u8::from_str_radix(&digits[2 * i..2 * i + 2], 16).ok())
+        .collect::<Option<Vec<_>>>()?;
+    let (a, rgb) = match channels.len() {
+        4 => (channels[0], &channels[1..]),
+        _ => (255, &channels[..]),
+    };
+    Some(Color32::from_rgba_unmultiplied(rgb[0], rgb[1], rgb[2], a))
+}
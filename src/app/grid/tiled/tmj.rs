@@ -0,0 +1,258 @@
+//! The JSON flavour of Tiled maps.
+
+use std::io::{Read, Write};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::{Property, Stagger, Tile, TileLayer, TiledError, TiledMap, Tileset, TILED_VERSION};
+
+#[derive(Serialize, Deserialize)]
+struct MapRecord {
+    #[serde(rename = "type")]
+    kind: String,
+    version: Value,
+    orientation: String,
+    #[serde(default)]
+    renderorder: String,
+    width: u32,
+    height: u32,
+    tilewidth: u32,
+    tileheight: u32,
+    #[serde(default)]
+    hexsidelength: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    staggeraxis: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    staggerindex: Option<String>,
+    #[serde(default)]
+    infinite: bool,
+    #[serde(default)]
+    nextlayerid: u32,
+    #[serde(default)]
+    nextobjectid: u32,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    properties: Vec<PropertyRecord>,
+    #[serde(default)]
+    tilesets: Vec<TilesetRecord>,
+    #[serde(default)]
+    layers: Vec<LayerRecord>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct PropertyRecord {
+    name: String,
+    #[serde(rename = "type", default = "string_type")]
+    kind: String,
+    value: Value,
+}
+
+#[derive(Serialize, Deserialize)]
+struct TilesetRecord {
+    firstgid: u32,
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    tilecount: u32,
+    #[serde(default, skip_deserializing)]
+    columns: u32,
+    #[serde(default)]
+    tilewidth: u32,
+    #[serde(default)]
+    tileheight: u32,
+    #[serde(default)]
+    tiles: Vec<TileRecord>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct TileRecord {
+    id: u32,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    properties: Vec<PropertyRecord>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct LayerRecord {
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    id: u32,
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    width: u32,
+    #[serde(default)]
+    height: u32,
+    #[serde(default)]
+    x: i32,
+    #[serde(default)]
+    y: i32,
+    #[serde(default = "yes")]
+    visible: bool,
+    #[serde(default, skip_serializing_if = "is_false")]
+    locked: bool,
+    #[serde(default = "opaque")]
+    opacity: f32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    encoding: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    data: Option<Value>,
+}
+
+fn string_type() -> String {
+    "string".into()
+}
+
+fn yes() -> bool {
+    true
+}
+
+fn opaque() -> f32 {
+    1.0
+}
+
+fn is_false(value: &bool) -> bool {
+    !value
+}
+
+impl PropertyRecord {
+    fn new(property: &Property) -> Self {
+        let value = match property.kind.as_str() {
+            "int" => property.value.parse::<i64>().map(Value::from).ok(),
+            "float" => property.value.parse::<f64>().map(Value::from).ok(),
+            "bool" => property.value.parse::<bool>().map(Value::from).ok(),
+            _ => None,
+        };
+        Self {
+            name: property.name.clone(),
+            kind: property.kind.clone(),
+            value: value.unwrap_or_else(|| property.value.clone().into()),
+        }
+    }
+
+    fn into_property(self) -> Property {
+        let value = match self.value {
+            Value::String(value) => value,
+            value => value.to_string(),
+        };
+        Property { name: self.name, kind: self.kind, value }
+    }
+}
+
+pub(super) fn write(map: &TiledMap, writer: impl Write) -> Result<(), TiledError> {
+    let stagger = map.stagger.expect("Only hexagonal maps are written!");
+    let properties = |properties: &[Property]| properties.iter().map(PropertyRecord::new).collect();
+    let record = MapRecord {
+        kind: "map".into(),
+        version: TILED_VERSION.into(),
+        orientation: "hexagonal".into(),
+        renderorder: "right-down".into(),
+        width: map.width,
+        height: map.height,
+        tilewidth: map.tile_width,
+        tileheight: map.tile_height,
+        hexsidelength: map.hex_side_length,
+        staggeraxis: Some(stagger.axis_name().into()),
+        staggerindex: Some(stagger.index_name().into()),
+        infinite: map.infinite,
+        nextlayerid: map.layers.len() as u32 + 1,
+        nextobjectid: 1,
+        properties: properties(&map.properties),
+        tilesets: map.tilesets.iter()
+            .map(|tileset| TilesetRecord {
+                firstgid: tileset.first_gid,
+                name: tileset.name.clone(),
+                tilecount: tileset.tile_count,
+                columns: 0,
+                tilewidth: map.tile_width,
+                tileheight: map.tile_height,
+                tiles: tileset.tiles.iter()
+                    .map(|tile| TileRecord { id: tile.id, properties: properties(&tile.properties) })
+                    .collect(),
+            })
+            .collect(),
+        layers: map.layers.iter()
+            .map(|layer| LayerRecord {
+                kind: "tilelayer".into(),
+                id: layer.id,
+                name: layer.name.clone(),
+                width: map.width,
+                height: map.height,
+                x: 0,
+                y: 0,
+                visible: layer.visible,
+                locked: layer.locked,
+                opacity: layer.opacity,
+                encoding: None,
+                data: Some(layer.data.clone().into()),
+            })
+            .collect(),
+    };
+    serde_json::to_writer_pretty(writer, &record).map_err(|error| match error.is_io() {
+        true => TiledError::Io(error.into()),
+        false => TiledError::Corrupt(error.to_string()),
+    })
+}
+
+pub(super) fn read(reader: impl Read) -> Result<TiledMap, TiledError> {
+    let record: MapRecord = serde_json::from_reader(reader).map_err(|error| match error.is_io() {
+        true => TiledError::Io(error.into()),
+        false => TiledError::Corrupt(error.to_string()),
+    })?;
+    if record.kind != "map" {
+        return Err(TiledError::Corrupt("the file is not a Tiled map".into()));
+    }
+    let stagger = match record.orientation.as_str() {
+        "hexagonal" => Some(Stagger::parse(
+            record.staggeraxis.as_deref().unwrap_or("y"),
+            record.staggerindex.as_deref().unwrap_or("odd"),
+        )?),
+        _ => None,
+    };
+
+    let mut layers = Vec::new();
+    for layer in record.layers.into_iter().filter(|layer| layer.kind == "tilelayer") {
+        if let Some(encoding) = layer.encoding.as_deref().filter(|encoding| *encoding != "csv") {
+            return Err(TiledError::Unsupported(format!("layers encoded as {encoding}, use the CSV layer format")));
+        }
+        let data = match layer.data {
+            Some(data) => serde_json::from_value(data)
+                .map_err(|_| TiledError::Corrupt(format!("layer {:?} has invalid tiles", layer.name)))?,
+            None if record.infinite => Vec::new(),
+            None => return Err(TiledError::Corrupt(format!("layer {:?} has no tiles", layer.name))),
+        };
+        layers.push(TileLayer {
+            id: layer.id,
+            name: layer.name,
+            visible: layer.visible,
+            locked: layer.locked,
+            opacity: layer.opacity,
+            data,
+        });
+    }
+
+    Ok(TiledMap {
+        stagger,
+        width: record.width,
+        height: record.height,
+        tile_width: record.tilewidth,
+        tile_height: record.tileheight,
+        hex_side_length: record.hexsidelength,
+        infinite: record.infinite,
+        properties: record.properties.into_iter().map(PropertyRecord::into_property).collect(),
+        tilesets: record.tilesets.into_iter()
+            .map(|tileset| Tileset {
+                first_gid: tileset.firstgid,
+                name: tileset.name,
+                tile_count: tileset.tilecount,
+                tiles: tileset.tiles.into_iter()
+                    .map(|tile| Tile {
+                        id: tile.id,
+                        properties: tile.properties.into_iter().map(PropertyRecord::into_property).collect(),
+                    })
+                    .collect(),
+            })
+            .collect(),
+        layers,
+    })
+}
@@ -0,0 +1,250 @@
+//! The XML flavour of Tiled maps.
+
+use std::io::{BufReader, Read, Write};
+
+use quick_xml::{
+    escape::escape, events::{BytesStart, Event}, Reader
+};
+
+use super::{Property, Stagger, Tile, TileLayer, TiledError, TiledMap, Tileset, TILED_VERSION};
+
+pub(super) fn write(map: &TiledMap, mut writer: impl Write) -> Result<(), TiledError> {
+    let stagger = map.stagger.expect("Only hexagonal maps are written!");
+    writeln!(writer, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(
+        writer,
+        r#"<map version="{TILED_VERSION}" orientation="hexagonal" renderorder="right-down" width="{}" height="{}" tilewidth="{}" tileheight="{}" hexsidelength="{}" staggeraxis="{}" staggerindex="{}" infinite="0" nextlayerid="{}" nextobjectid="1">"#,
+        map.width,
+        map.height,
+        map.tile_width,
+        map.tile_height,
+        map.hex_side_length,
+        stagger.axis_name(),
+        stagger.index_name(),
+        map.layers.len() + 1,
+    )?;
+    write_properties(&mut writer, &map.properties, 1)?;
+    for tileset in &map.tilesets {
+        writeln!(
+            writer,
+            r#" <tileset firstgid="{}" name="{}" tilewidth="{}" tileheight="{}" tilecount="{}" columns="0">"#,
+            tileset.first_gid,
+            escape(&tileset.name),
+            map.tile_width,
+            map.tile_height,
+            tileset.tile_count,
+        )?;
+        writeln!(writer, r#"  <grid orientation="orthogonal" width="1" height="1"/>"#)?;
+        for tile in &tileset.tiles {
+            writeln!(writer, r#"  <tile id="{}">"#, tile.id)?;
+            write_properties(&mut writer, &tile.properties, 3)?;
+            writeln!(writer, "  </tile>")?;
+        }
+        writeln!(writer, " </tileset>")?;
+    }
+    for layer in &map.layers {
+        write!(writer, r#" <layer id="{}" name="{}" width="{}" height="{}""#, layer.id, escape(&layer.name), map.width, map.height)?;
+        if !layer.visible {
+            write!(writer, r#" visible="0""#)?;
+        }
+        if layer.locked {
+            write!(writer, r#" locked="1""#)?;
+        }
+        if layer.opacity < 1.0 {
+            write!(writer, r#" opacity="{}""#, layer.opacity)?;
+        }
+        writeln!(writer, ">")?;
+        writeln!(writer, r#"  <data encoding="csv">"#)?;
+        for (index, row) in layer.data.chunks(map.width.max(1) as usize).enumerate() {
+            let row = row.iter().map(u32::to_string).collect::<Vec<_>>().join(",");
+            let separator = if index + 1 < map.height as usize { "," } else { "" };
+            writeln!(writer, "{row}{separator}")?;
+        }
+        writeln!(writer, "</data>")?;
+        writeln!(writer, " </layer>")?;
+    }
+    writeln!(writer, "</map>")?;
+    Ok(())
+}
+
+fn write_properties(writer: &mut impl Write, properties: &[Property], depth: usize) -> Result<(), TiledError> {
+    if properties.is_empty() {
+        return Ok(());
+    }
+    let indent = " ".repeat(depth);
+    writeln!(writer, "{indent}<properties>")?;
+    for property in properties {
+        let kind = match property.kind.as_str() {
+            "string" => String::new(),
+            kind => format!(r#" type="{kind}""#),
+        };
+        writeln!(writer, r#"{indent} <property name="{}"{kind} value="{}"/>"#, escape(&property.name), escape(&property.value))?;
+    }
+    writeln!(writer, "{indent}</properties>")?;
+    Ok(())
+}
+
+/// Where in the document the reader is.
+#[derive(Clone, Copy, PartialEq)]
+enum Scope {
+    Map,
+    Tileset,
+    Tile,
+    Layer,
+    /// `<data>` of a layer, in CSV.
+    Data,
+    /// Elements whose content is skipped, like object groups.
+    Other,
+}
+
+pub(super) fn read(reader: impl Read) -> Result<TiledMap, TiledError> {
+    let mut reader = Reader::from_reader(BufReader::new(reader));
+    reader.config_mut().trim_text(true);
+    let mut map = TiledMap::default();
+    let mut found_map = false;
+    let mut scopes: Vec<Scope> = Vec::new();
+    let mut buffer = Vec::new();
+    loop {
+        let event = reader.read_event_into(&mut buffer).map_err(corrupt)?;
+        let empty = matches!(event, Event::Empty(_));
+        match event {
+            Event::Start(element) | Event::Empty(element) => {
+                let scope = start_element(&mut map, scopes.last().copied(), &element)?;
+                found_map |= scope == Scope::Map;
+                if !empty {
+                    scopes.push(scope);
+                }
+            }
+            Event::End(_) => {
+                scopes.pop();
+            }
+            Event::Text(text) if scopes.last() == Some(&Scope::Data) => {
+                let text = text.unescape().map_err(corrupt)?;
+                let layer = map.layers.last_mut().expect("Data outside of a layer!");
+                for gid in text.split(',').map(str::trim).filter(|gid| !gid.is_empty()) {
+                    layer.data.push(gid.parse().map_err(|_| TiledError::Corrupt(format!("invalid tile {gid:?}")))?);
+                }
+            }
+            Event::Eof => break,
+            _ => (),
+        }
+        buffer.clear();
+    }
+    if !found_map {
+        return Err(TiledError::Corrupt("the file has no <map>".into()));
+    }
+    Ok(map)
+}
+
+/// Records an element opened inside `parent`, returning its scope.
+fn start_element(map: &mut TiledMap, parent: Option<Scope>, element: &BytesStart) -> Result<Scope, TiledError> {
+    let name = element.local_name();
+    let attribute = |key: &str| attribute(element, key);
+    let number = |key: &str| -> Result<u32, TiledError> {
+        match attribute(key)? {
+            Some(value) => value.parse().map_err(|_| TiledError::Corrupt(format!("invalid {key} {value:?}"))),
+            None => Ok(0),
+        }
+    };
+    let scope = match (parent, name.as_ref()) {
+        (None, b"map") => {
+            if attribute("orientation")?.as_deref() == Some("hexagonal") {
+                let axis = attribute("staggeraxis")?.unwrap_or_else(|| "y".into());
+                let index = attribute("staggerindex")?.unwrap_or_else(|| "odd".into());
+                map.stagger = Some(Stagger::parse(&axis, &index)?);
+            }
+            map.width = number("width")?;
+            map.height = number("height")?;
+            map.tile_width = number("tilewidth")?;
+            map.tile_height = number("tileheight")?;
+            map.hex_side_length = number("hexsidelength")?;
+            map.infinite = attribute("infinite")?.as_deref() == Some("1");
+            Scope::Map
+        }
+        (Some(Scope::Map), b"tileset") => {
+            map.tilesets.push(Tileset {
+                first_gid: number("firstgid")?,
+                name: attribute("name")?.unwrap_or_default(),
+                tile_count: number("tilecount")?,
+                tiles: Vec::new(),
+            });
+            Scope::Tileset
+        }
+        (Some(Scope::Tileset), b"tile") => {
+            let tileset = map.tilesets.last_mut().expect("Tile outside of a tileset!");
+            tileset.tiles.push(Tile { id: number("id")?, properties: Vec::new() });
+            Scope::Tile
+        }
+        (Some(Scope::Map), b"layer") => {
+            let float = |key: &str, default: f32| -> Result<f32, TiledError> {
+                match attribute(key)? {
+                    Some(value) => value.parse().map_err(|_| TiledError::Corrupt(format!("invalid {key} {value:?}"))),
+                    None => Ok(default),
+                }
+            };
+            map.layers.push(TileLayer {
+                id: number("id")?,
+                name: attribute("name")?.unwrap_or_default(),
+                visible: attribute("visible")?.as_deref() != Some("0"),
+                locked: attribute("locked")?.as_deref() == Some("1"),
+                opacity: float("opacity", 1.0)?,
+                data: Vec::new(),
+            });
+            Scope::Layer
+        }
+        (Some(Scope::Layer), b"data") => {
+            match attribute("encoding")?.as_deref() {
+                Some("csv") | None => (),
+                Some(encoding) => return Err(TiledError::Unsupported(format!(
+                    "layers encoded as {encoding}, use the CSV layer format"
+                ))),
+            }
+            if attribute("compression")?.is_some() {
+                return Err(TiledError::Unsupported("compressed layers, use the CSV layer format".into()));
+            }
+            Scope::Data
+        }
+        (Some(Scope::Data), b"tile") => {
+            let layer = map.layers.last_mut().expect("Data outside of a layer!");
+            layer.data.push(number("gid")?);
+            Scope::Other
+        }
+        (Some(Scope::Data), b"chunk") => {
+            return Err(TiledError::Unsupported("infinite maps cannot be opened".into()));
+        }
+        (Some(scope @ (Scope::Map | Scope::Tile)), b"properties") => scope,
+        (Some(scope @ (Scope::Map | Scope::Tile)), b"property") => {
+            let property = Property {
+                name: attribute("name")?.unwrap_or_default(),
+                kind: attribute("type")?.unwrap_or_else(|| "string".into()),
+                value: attribute("value")?.unwrap_or_default(),
+            };
+            let properties = match scope {
+                Scope::Map => &mut map.properties,
+                _ => {
+                    let tileset = map.tilesets.last_mut().expect("Tile outside of a tileset!");
+                    &mut tileset.tiles.last_mut().expect("Property outside of a tile!").properties
+                }
+            };
+            properties.push(property);
+            Scope::Other
+        }
+        (None, _) => return Err(TiledError::Corrupt("the file is not a Tiled map".into())),
+        _ => Scope::Other,
+    };
+    Ok(scope)
+}
+
+fn attribute(element: &BytesStart, key: &str) -> Result<Option<String>, TiledError> {
+    for attribute in element.attributes() {
+        let attribute = attribute.map_err(corrupt)?;
+        if attribute.key.as_ref() == key.as_bytes() {
+            return Ok(Some(attribute.unescape_value().map_err(corrupt)?.into_owned()));
+        }
+    }
+    Ok(None)
+}
+
+fn corrupt(error: impl ToString) -> TiledError {
+    TiledError::Corrupt(error.to_string())
+}
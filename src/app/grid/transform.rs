@@ -0,0 +1,98 @@
+use std::collections::{HashMap, HashSet};
+
+use super::{FractionalHex, Grid, Hex, HexAxis, HexCorner, HexEdge, HexMath, HexReflection, HexRotation, HexRound, LayerId, Site, TerrainId};
+
+/// Cells, edges and corners to write, `None` erasing them.
+pub type SiteChanges = Vec<(Site, Option<TerrainId>)>;
+
+/// A rigid motion of cells over the grid.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Transform {
+    /// Sixths of a turn around `pivot`, rightwards when positive.
+    Rotate { pivot: Hex, steps: i32 },
+    /// Mirror across the line through `pivot` parallel to `axis`.
+    Reflect { pivot: Hex, axis: HexAxis },
+    Translate(Hex),
+}
+
+impl Transform {
+    /// The same transform, rotating or mirroring around `pivot` instead.
+    pub fn around(self, pivot: Hex) -> Self {
+        match self {
+            Transform::Rotate { steps, .. } => Transform::Rotate { pivot, steps },
+            Transform::Reflect { axis, .. } => Transform::Reflect { pivot, axis },
+            Transform::Translate(offset) => Transform::Translate(offset),
+        }
+    }
+
+    pub fn apply(&self, hex: Hex) -> Hex {
+        match *self {
+            Transform::Rotate { pivot, steps } => hex.rotate_around(pivot, steps),
+            Transform::Reflect { pivot, axis } => hex.reflect_around(pivot, axis),
+            Transform::Translate(offset) => hex.add(offset),
+        }
+    }
+
+    /// Moves a cell, edge or corner along with the cells around it.
+    pub fn apply_site(&self, site: Site) -> Site {
+        //Rigid motions keep neighbors together, so the moved cells still meet
+        match site {
+            Site::Cell(hex) => Site::Cell(self.apply(hex)),
+            Site::Edge(edge) => {
+                let [a, b] = edge.cells().map(|hex| self.apply(hex));
+                Site::Edge(HexEdge::between(a, b).expect("Moved neighbors stay neighbors"))
+            }
+            Site::Corner(corner) => {
+                let [a, b, c] = corner.cells().map(|hex| self.apply(hex));
+                Site::Corner(HexCorner::between(a, b, c).expect("Moved cells still meet"))
+            }
+        }
+    }
+}
+
+/// Cell nearest to the average position of `cells`, a pivot that keeps them in place
+/// as much as possible.
+pub fn center_of(cells: impl IntoIterator<Item = Hex>) -> Option<Hex> {
+    let (count, q, r) = cells.into_iter()
+        .fold((0, 0i64, 0i64), |(count, q, r), hex| (count + 1, q + hex.q() as i64, r + hex.r() as i64));
+    (count > 0).then(|| FractionalHex::new(q as f64 / count as f64, r as f64 / count as f64).round())
+}
+
+impl Grid {
+    /// Changes that move the stored cells of `layer` among `cells` by `transform`,
+    /// with the edges and corners lying wholly between them. Sites they leave are
+    /// erased, sites they land on overwritten.
+    pub fn transform_changes(
+        &self,
+        layer: LayerId,
+        cells: impl IntoIterator<Item = Hex>,
+        transform: Transform,
+    ) -> SiteChanges {
+        let Some(layer) = self.layer(layer) else {
+            return Vec::new();
+        };
+        let cells: HashSet<Hex> = cells.into_iter().collect();
+        let moved: Vec<(Site, TerrainId)> = cells.iter()
+            .filter_map(|hex| Some((Site::Cell(*hex), layer.cell(*hex)?)))
+            .chain(layer.edges()
+                .filter(|(edge, _)| edge.cells().iter().all(|hex| cells.contains(hex)))
+                .map(|(edge, terrain)| (Site::Edge(edge), terrain)))
+            .chain(layer.corners()
+                .filter(|(corner, _)| corner.cells().iter().all(|hex| cells.contains(hex)))
+                .map(|(corner, terrain)| (Site::Corner(corner), terrain)))
+            .collect();
+        let mut changes: HashMap<_, _> = moved.iter().map(|(site, _)| (*site, None)).collect();
+        changes.extend(moved.into_iter().map(|(site, terrain)| (transform.apply_site(site), Some(terrain))));
+        changes.into_iter()
+            .filter(|(site, value)| layer.site(*site) != *value)
+            .collect()
+    }
+
+    /// Changes that apply `transform` to every editable layer, keyed by layer.
+    pub fn transform_map(&self, transform: Transform) -> Vec<(LayerId, SiteChanges)> {
+        self.layers.iter()
+            .filter(|layer| layer.is_editable())
+            .map(|layer| (layer.id(), self.transform_changes(layer.id(), layer.occupied_cells(), transform)))
+            .collect()
+    }
+}
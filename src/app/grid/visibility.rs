@@ -0,0 +1,102 @@
+use std::collections::HashSet;
+
+use super::{Grid, Hex, HexIterators, HexUtility};
+
+/// How [`Grid::field_of_view`] decides what the origin sees.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FovMode {
+    /// Ring by ring shadowcasting. Fast, but a cell may see one that does not see it back.
+    Shadowcast,
+    /// A cell is visible when [`Grid::line_of_sight`] reaches it, so sight is always mutual.
+    Symmetric,
+}
+
+/// Angular span over a ring, in turns. Cell `i` of ring `k` is centered on `i / 6k`.
+type Span = (f64, f64);
+
+impl Grid {
+    /// Whether `hex` blocks sight, which it does when the cell of any visible layer
+    /// there is opaque, so a marker painted over a wall does not see through it.
+    pub fn is_opaque(&self, hex: Hex) -> bool {
+        self.layers.iter()
+            .filter(|layer| layer.visible)
+            .filter_map(|layer| layer.cell(hex))
+            .filter_map(|terrain| self.terrains.get(terrain))
+            .any(|terrain| terrain.opaque)
+    }
+
+    /// Whether no opaque cell lies strictly between `from` and `to`.
+    ///
+    /// Rounding can make `line` pick different cells on ties depending on the
+    /// direction it is drawn in, so both directions are tried. Sight is clear
+    /// when either is, which makes the answer the same both ways.
+    pub fn line_of_sight(&self, from: Hex, to: Hex) -> bool {
+        let clear = |line: Vec<Hex>| line.len() < 3 || line[1..line.len() - 1].iter().all(|hex| !self.is_opaque(*hex));
+        clear(from.line(to)) || clear(to.line(from))
+    }
+
+    /// Cells within `radius` of `origin` that it can see, `origin` included.
+    /// Opaque cells are seen but hide what lies behind them.
+    pub fn field_of_view(&self, origin: Hex, radius: i32, mode: FovMode) -> HashSet<Hex> {
+        match mode {
+            FovMode::Shadowcast => self.shadowcast(origin, radius),
+            FovMode::Symmetric => origin.range(radius.max(0))
+                .filter(|hex| self.line_of_sight(origin, *hex))
+                .collect(),
+        }
+    }
+
+    fn shadowcast(&self, origin: Hex, radius: i32) -> HashSet<Hex> {
+        let mut visible = HashSet::from([origin]);
+        let mut shadows: Vec<Span> = Vec::new();
+        for k in 1..=radius {
+            let cells = (6 * k) as f64;
+            let half = 0.5 / cells;
+            //Cells of one ring do not shadow each other, walls are added after the ring
+            let mut walls = Vec::new();
+            for (i, hex) in origin.ring(k).enumerate() {
+                let center = i as f64 / cells;
+                if in_shadow(&shadows, center) {
+                    continue;
+                }
+                visible.insert(hex);
+                if self.is_opaque(hex) {
+                    walls.push((center - half, center + half));
+                }
+            }
+            add_shadows(&mut shadows, walls);
+            if matches!(shadows[..], [(start, end)] if start <= 0.0 && end >= 1.0) {
+                break;
+            }
+        }
+        visible
+    }
+}
+
+fn in_shadow(shadows: &[Span], angle: f64) -> bool {
+    shadows.iter().any(|(start, end)| *start < angle && angle < *end)
+}
+
+/// Adds `spans` to `shadows`, keeping them sorted, inside `[0, 1]` and merged where they touch.
+fn add_shadows(shadows: &mut Vec<Span>, spans: Vec<Span>) {
+    //Spans crossing angle 0 are split, slightly widened so 0 itself stays covered
+    const WRAP: f64 = 1e-9;
+    for (start, end) in spans {
+        if start < 0.0 {
+            shadows.push((-WRAP, end));
+            shadows.push((start + 1.0, 1.0 + WRAP));
+        } else {
+            shadows.push((start, end));
+        }
+    }
+    shadows.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let mut merged: Vec<Span> = Vec::with_capacity(shadows.len());
+    for (start, end) in shadows.drain(..) {
+        match merged.last_mut() {
+            //Walls side by side leave no gap to peek through
+            Some(last) if start <= last.1 + 1e-9 => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+    *shadows = merged;
+}
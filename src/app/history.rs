@@ -0,0 +1,265 @@
+use std::{collections::{HashMap, VecDeque}, mem::size_of};
+
+use super::grid::{Grid, Layer, LayerId, Site, TerrainId};
+
+#[cfg(test)]
+mod tests;
+
+/// Default amount of memory the undo stack may hold before old steps are dropped.
+pub const HISTORY_BUDGET_BYTES: usize = 16 * 1024 * 1024;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct CellChange {
+    layer: LayerId,
+    site: Site,
+    before: Option<TerrainId>,
+    after: Option<TerrainId>,
+}
+
+enum LayerChange {
+    /// The layer is held here while it is out of the grid.
+    Removed { id: LayerId, index: usize, layer: Option<Layer> },
+    Moved { id: LayerId, offset: isize },
+}
+
+impl LayerChange {
+    fn cost(&self) -> usize {
+        match self {
+            LayerChange::Removed { layer: Some(layer), .. } => {
+                size_of::<Layer>() + layer.sites().count() * size_of::<(Site, TerrainId)>()
+            }
+            _ => 0,
+        }
+    }
+}
+
+/// One undoable step: every cell, edge and corner it touched with its value
+/// before and after, and the layers it removed or moved.
+#[derive(Default)]
+struct Edit {
+    changes: Vec<CellChange>,
+    index: HashMap<(LayerId, Site), usize>,
+    layers: Vec<LayerChange>,
+}
+
+impl Edit {
+    fn record(&mut self, layer: LayerId, site: Site, before: Option<TerrainId>, after: Option<TerrainId>) {
+        //A cell touched twice in the same step keeps its oldest `before`
+        match self.index.get(&(layer, site)) {
+            Some(&i) => self.changes[i].after = after,
+            None => {
+                self.index.insert((layer, site), self.changes.len());
+                self.changes.push(CellChange { layer, site, before, after });
+            }
+        }
+    }
+
+    fn finish(mut self) -> Option<Self> {
+        self.changes.retain(|change| change.before != change.after);
+        self.index.clear();
+        self.index.shrink_to_fit();
+        self.changes.shrink_to_fit();
+        (!self.changes.is_empty() || !self.layers.is_empty()).then_some(self)
+    }
+
+    fn cost(&self) -> usize {
+        size_of::<Self>()
+            + self.changes.capacity() * size_of::<CellChange>()
+            + self.layers.iter().map(LayerChange::cost).sum::<usize>()
+    }
+
+    /// Whether a layer the step touched is still there but hidden or locked.
+    fn is_blocked(&self, grid: &Grid) -> bool {
+        self.changes.iter().any(|change| grid.layer(change.layer).is_some_and(|layer| !layer.is_editable()))
+    }
+
+    fn undo(&mut self, grid: &mut Grid) {
+        for change in self.layers.iter_mut().rev() {
+            match change {
+                LayerChange::Removed { index, layer, .. } => {
+                    if let Some(layer) = layer.take() {
+                        grid.restore_layer(*index, layer);
+                    }
+                }
+                LayerChange::Moved { id, offset } => grid.move_layer(*id, -*offset),
+            }
+        }
+        for change in self.changes.iter().rev() {
+            grid.set_layer_site(change.layer, change.site, change.before);
+        }
+    }
+
+    fn redo(&mut self, grid: &mut Grid) {
+        for change in &self.changes {
+            grid.set_layer_site(change.layer, change.site, change.after);
+        }
+        for change in &mut self.layers {
+            match change {
+                LayerChange::Removed { id, layer, .. } => *layer = grid.remove_layer(*id),
+                LayerChange::Moved { id, offset } => grid.move_layer(*id, *offset),
+            }
+        }
+    }
+}
+
+/// Undo/redo stack for every mutation made to a [`Grid`] from the editor.
+///
+/// Changes remember the layer they were made on, steps touching a layer that
+/// was deleted since only restore the cells of the remaining layers. Steps
+/// touching a layer that is hidden or locked now are refused until it is
+/// editable again.
+///
+/// Edits made between [`History::begin`] and [`History::end`] are coalesced
+/// into a single step, so a whole drag stroke is undone at once.
+pub struct History {
+    undo: VecDeque<Edit>,
+    redo: Vec<Edit>,
+    pending: Option<Edit>,
+    budget: usize,
+    used: usize,
+}
+
+impl History {
+    pub fn new(budget: usize) -> Self {
+        Self {
+            undo: VecDeque::new(),
+            redo: Vec::new(),
+            pending: None,
+            budget,
+            used: 0,
+        }
+    }
+
+    /// Starts a step that collects every following change until [`History::end`].
+    pub fn begin(&mut self) {
+        if self.pending.is_none() {
+            self.pending = Some(Edit::default());
+        }
+    }
+
+    /// Closes the current step and pushes it to the undo stack, if it changed anything.
+    pub fn end(&mut self) {
+        let Some(edit) = self.pending.take().and_then(Edit::finish) else {
+            return;
+        };
+        self.redo.clear();
+        self.used += edit.cost();
+        self.undo.push_back(edit);
+        while self.used > self.budget && self.undo.len() > 1 {
+            if let Some(dropped) = self.undo.pop_front() {
+                self.used -= dropped.cost();
+            }
+        }
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    /// Sets cells, edges or corners of the active layer through the history as part
+    /// of the current step. Outside of a step they are recorded as their own step.
+    /// Hidden and locked layers are left untouched.
+    pub fn apply<S: Into<Site>>(&mut self, grid: &mut Grid, cells: impl IntoIterator<Item = (S, Option<TerrainId>)>) {
+        let layer = grid.active_layer().id();
+        self.apply_to_layer(grid, layer, cells);
+    }
+
+    /// Like [`History::apply`] on any layer.
+    pub fn apply_to_layer<S: Into<Site>>(&mut self, grid: &mut Grid, layer: LayerId, cells: impl IntoIterator<Item = (S, Option<TerrainId>)>) {
+        if !grid.layer(layer).is_some_and(|layer| layer.is_editable()) {
+            return;
+        }
+        let standalone = !self.is_recording();
+        self.begin();
+        let edit = self.pending.as_mut().expect("A step was just started");
+        for (site, value) in cells {
+            let site = site.into();
+            let before = grid.set_layer_site(layer, site, value);
+            edit.record(layer, site, before, value);
+        }
+        if standalone {
+            self.end();
+        }
+    }
+
+    /// Deletes a layer as a step of its own, so undoing brings it back.
+    pub fn remove_layer(&mut self, grid: &mut Grid, id: LayerId) -> bool {
+        self.end();
+        let Some(index) = grid.layers().iter().position(|layer| layer.id() == id) else {
+            return false;
+        };
+        let Some(layer) = grid.remove_layer(id) else {
+            return false;
+        };
+        self.push_layer_change(LayerChange::Removed { id, index, layer: Some(layer) });
+        true
+    }
+
+    /// Moves a layer up or down the stack as a step of its own.
+    pub fn move_layer(&mut self, grid: &mut Grid, id: LayerId, offset: isize) -> bool {
+        self.end();
+        let position = |grid: &Grid| grid.layers().iter().position(|layer| layer.id() == id);
+        let Some(before) = position(grid) else {
+            return false;
+        };
+        grid.move_layer(id, offset);
+        let after = position(grid).expect("Moved layers stay in the grid");
+        if before == after {
+            return false;
+        }
+        self.push_layer_change(LayerChange::Moved { id, offset: after as isize - before as isize });
+        true
+    }
+
+    fn push_layer_change(&mut self, change: LayerChange) {
+        self.pending = Some(Edit { layers: vec![change], ..Edit::default() });
+        self.end();
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    /// Reverts the latest step. Returns whether the grid changed.
+    pub fn undo(&mut self, grid: &mut Grid) -> bool {
+        self.end();
+        if self.undo.back().is_none_or(|edit| edit.is_blocked(grid)) {
+            return false;
+        }
+        let mut edit = self.undo.pop_back().expect("The stack was just checked");
+        self.used -= edit.cost();
+        edit.undo(grid);
+        self.redo.push(edit);
+        true
+    }
+
+    /// Reapplies the latest undone step. Returns whether the grid changed.
+    pub fn redo(&mut self, grid: &mut Grid) -> bool {
+        self.end();
+        if self.redo.last().is_none_or(|edit| edit.is_blocked(grid)) {
+            return false;
+        }
+        let mut edit = self.redo.pop().expect("The stack was just checked");
+        edit.redo(grid);
+        self.used += edit.cost();
+        self.undo.push_back(edit);
+        true
+    }
+
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+        self.pending = None;
+        self.used = 0;
+    }
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self::new(HISTORY_BUDGET_BYTES)
+    }
+}
@@ -0,0 +1,204 @@
+use super::History;
+use crate::app::grid::{Direction, Grid, Hex, HexEdge, TerrainId};
+
+const RED: TerrainId = TerrainId(1);
+const GREEN: TerrainId = TerrainId(2);
+const BLUE: TerrainId = TerrainId(3);
+
+#[test]
+fn test_undo_redo_single_edit() {
+    let mut grid = Grid::default();
+    let mut history = History::default();
+
+    history.apply(&mut grid, [(Hex::new(1, -1), Some(RED))]);
+    assert_eq!(Some(RED), grid.cell(Hex::new(1, -1)));
+
+    assert!(history.undo(&mut grid));
+    assert_eq!(None, grid.cell(Hex::new(1, -1)));
+    assert!(!history.undo(&mut grid));
+
+    assert!(history.redo(&mut grid));
+    assert_eq!(Some(RED), grid.cell(Hex::new(1, -1)));
+    assert!(!history.redo(&mut grid));
+}
+
+#[test]
+fn test_stroke_is_one_step() {
+    let mut grid = Grid::default();
+    grid.paint_cell(Hex::new(0, 0), BLUE);
+    let mut history = History::default();
+
+    history.begin();
+    history.apply(&mut grid, [(Hex::new(0, 0), Some(RED))]);
+    history.apply(&mut grid, [(Hex::new(1, 0), Some(RED))]);
+    history.apply(&mut grid, [(Hex::new(0, 0), Some(GREEN))]);
+    history.end();
+
+    assert!(history.undo(&mut grid));
+    assert_eq!(Some(BLUE), grid.cell(Hex::new(0, 0)));
+    assert_eq!(None, grid.cell(Hex::new(1, 0)));
+    assert!(!history.can_undo());
+
+    assert!(history.redo(&mut grid));
+    assert_eq!(Some(GREEN), grid.cell(Hex::new(0, 0)));
+    assert_eq!(Some(RED), grid.cell(Hex::new(1, 0)));
+}
+
+#[test]
+fn test_noop_stroke_is_dropped() {
+    let mut grid = Grid::default();
+    grid.paint_cell(Hex::new(0, 0), BLUE);
+    let mut history = History::default();
+
+    history.begin();
+    history.apply(&mut grid, [(Hex::new(0, 0), Some(BLUE))]);
+    history.apply(&mut grid, [(Hex::new(2, 0), Some(RED))]);
+    history.apply(&mut grid, [(Hex::new(2, 0), None)]);
+    history.end();
+
+    assert!(!history.can_undo());
+}
+
+#[test]
+fn test_new_edit_clears_redo() {
+    let mut grid = Grid::default();
+    let mut history = History::default();
+
+    history.apply(&mut grid, [(Hex::new(0, 0), Some(RED))]);
+    history.undo(&mut grid);
+    assert!(history.can_redo());
+
+    history.apply(&mut grid, [(Hex::new(0, 1), Some(RED))]);
+    assert!(!history.can_redo());
+}
+
+#[test]
+fn test_budget_drops_oldest_steps() {
+    let mut grid = Grid::default();
+    let mut history = History::new(0);
+
+    for q in 0..10 {
+        history.apply(&mut grid, [(Hex::new(q, 0), Some(RED))]);
+    }
+
+    //The newest step is always kept, even when it alone exceeds the budget
+    assert!(history.undo(&mut grid));
+    assert_eq!(None, grid.cell(Hex::new(9, 0)));
+    assert!(!history.undo(&mut grid));
+    assert_eq!(Some(RED), grid.cell(Hex::new(8, 0)));
+}
+
+#[test]
+fn test_undo_targets_layer_of_the_edit() {
+    let mut grid = Grid::default();
+    let mut history = History::default();
+    let bottom = grid.active_layer().id();
+
+    history.apply(&mut grid, [(Hex::new(0, 0), Some(RED))]);
+    let top = grid.add_layer("Top");
+    history.apply(&mut grid, [(Hex::new(0, 0), Some(GREEN))]);
+
+    assert!(history.undo(&mut grid));
+    assert_eq!(None, grid.cell(Hex::new(0, 0)));
+    assert!(history.undo(&mut grid));
+    assert_eq!(None, grid.layer(bottom).unwrap().cell(Hex::new(0, 0)));
+
+    grid.remove_layer(top);
+    assert!(history.redo(&mut grid));
+    assert_eq!(Some(RED), grid.layer(bottom).unwrap().cell(Hex::new(0, 0)));
+    //The top layer is gone, redoing its edit must not paint elsewhere
+    assert!(history.redo(&mut grid));
+    assert_eq!(Some(RED), grid.layer(bottom).unwrap().cell(Hex::new(0, 0)));
+    assert!(grid.layers().iter().all(|layer| layer.id() == bottom || layer.cell(Hex::new(0, 0)).is_none()));
+}
+
+#[test]
+fn test_locked_layers_are_skipped() {
+    let mut grid = Grid::default();
+    let mut history = History::default();
+    let bottom = grid.active_layer().id();
+    grid.layer_mut(bottom).unwrap().locked = true;
+    let top = grid.add_layer("Top");
+
+    history.begin();
+    history.apply_to_layer(&mut grid, bottom, [(Hex::new(0, 0), Some(RED))]);
+    history.apply_to_layer(&mut grid, top, [(Hex::new(0, 0), Some(GREEN))]);
+    history.end();
+    assert_eq!(None, grid.layer(bottom).unwrap().cell(Hex::new(0, 0)));
+    assert_eq!(Some(GREEN), grid.cell(Hex::new(0, 0)));
+
+    grid.layer_mut(top).unwrap().visible = false;
+    history.apply(&mut grid, [(Hex::new(0, 0), None)]);
+    assert_eq!(Some(GREEN), grid.cell(Hex::new(0, 0)));
+    //Undo stays out of the hidden layer too, until it is shown again
+    assert!(!history.undo(&mut grid));
+    assert_eq!(Some(GREEN), grid.layer(top).unwrap().cell(Hex::new(0, 0)));
+    grid.layer_mut(top).unwrap().visible = true;
+    assert!(history.undo(&mut grid));
+    assert_eq!(None, grid.cell(Hex::new(0, 0)));
+    assert!(!history.can_undo());
+
+    grid.layer_mut(top).unwrap().locked = true;
+    assert!(!history.redo(&mut grid));
+    assert_eq!(None, grid.layer(top).unwrap().cell(Hex::new(0, 0)));
+    assert!(history.can_redo());
+}
+
+#[test]
+fn test_undo_layer_removal() {
+    let mut grid = Grid::default();
+    let mut history = History::default();
+    let top = grid.add_layer("Top");
+    history.apply(&mut grid, [(Hex::new(0, 0), Some(RED))]);
+
+    assert!(history.remove_layer(&mut grid, top));
+    assert!(grid.layer(top).is_none());
+    assert!(history.undo(&mut grid));
+    assert_eq!(top, grid.active_layer().id());
+    assert_eq!(Some(RED), grid.cell(Hex::new(0, 0)));
+    //The restored layer is the one earlier steps remember
+    assert!(history.undo(&mut grid));
+    assert_eq!(None, grid.cell(Hex::new(0, 0)));
+
+    assert!(history.redo(&mut grid));
+    assert!(history.redo(&mut grid));
+    assert!(grid.layer(top).is_none());
+}
+
+#[test]
+fn test_undo_layer_move() {
+    let mut grid = Grid::default();
+    let mut history = History::default();
+    let top = grid.add_layer("Top");
+    let index = grid.layers().len() - 1;
+    grid.move_layer(top, 5);
+    let position = |grid: &Grid| grid.layers().iter().position(|layer| layer.id() == top);
+
+    assert!(!history.move_layer(&mut grid, top, 1));
+    assert!(!history.can_undo());
+    assert!(history.move_layer(&mut grid, top, -10));
+    assert_eq!(Some(0), position(&grid));
+    assert!(history.undo(&mut grid));
+    assert_eq!(Some(index), position(&grid));
+    assert!(history.redo(&mut grid));
+    assert_eq!(Some(0), position(&grid));
+}
+
+#[test]
+fn test_undo_edges() {
+    let mut grid = Grid::default();
+    let mut history = History::default();
+    let edge = HexEdge::new(Hex::new(0, 0), Direction::West);
+
+    history.begin();
+    history.apply(&mut grid, [(edge, Some(RED))]);
+    history.apply(&mut grid, [(Hex::new(0, 0), Some(GREEN))]);
+    history.end();
+    assert_eq!(Some(RED), grid.active_layer().edge(edge));
+
+    assert!(history.undo(&mut grid));
+    assert_eq!(None, grid.active_layer().edge(edge));
+    assert_eq!(None, grid.cell(Hex::new(0, 0)));
+    assert!(history.redo(&mut grid));
+    assert_eq!(Some(RED), grid.active_layer().site(HexEdge::new(Hex::new(-1, 0), Direction::East)));
+}
@@ -0,0 +1,60 @@
+use egui::{Slider, TextEdit, Ui};
+
+use super::{grid::Grid, history::History};
+
+/// Lists the layers of `grid` top to bottom and edits the stack.
+/// Deleting and reordering go through `history`.
+/// Returns whether anything that affects rendering changed.
+pub fn ui(ui: &mut Ui, grid: &mut Grid, history: &mut History) -> bool {
+    let mut changed = false;
+    ui.heading("Layers");
+
+    let active = grid.active_layer().id();
+    let mut activated = None;
+    let ids: Vec<_> = grid.layers().iter().rev().map(|layer| layer.id()).collect();
+    for id in ids {
+        let Some(layer) = grid.layer_mut(id) else {
+            continue;
+        };
+        ui.horizontal(|ui| {
+            changed |= ui.checkbox(&mut layer.visible, "").on_hover_text("Visible").changed();
+            let lock = if layer.locked { "🔒" } else { "🔓" };
+            if ui.selectable_label(layer.locked, lock).on_hover_text("Locked").clicked() {
+                layer.locked = !layer.locked;
+            }
+            if ui.selectable_label(id == active, &layer.name).clicked() {
+                activated = Some(id);
+            }
+        });
+    }
+    if let Some(id) = activated {
+        grid.set_active_layer(id);
+    }
+
+    ui.horizontal(|ui| {
+        if ui.button("Add").clicked() {
+            let name = format!("Layer {}", grid.layers().len() + 1);
+            grid.add_layer(name);
+        }
+        let active = grid.active_layer().id();
+        if ui.add_enabled(grid.layers().len() > 1, egui::Button::new("Delete")).clicked() {
+            changed |= history.remove_layer(grid, active);
+        }
+        if ui.button("Up").clicked() {
+            changed |= history.move_layer(grid, active, 1);
+        }
+        if ui.button("Down").clicked() {
+            changed |= history.move_layer(grid, active, -1);
+        }
+    });
+
+    let active = grid.active_layer().id();
+    if let Some(layer) = grid.layer_mut(active) {
+        ui.horizontal(|ui| {
+            ui.label("Name:");
+            ui.add(TextEdit::singleline(&mut layer.name).desired_width(f32::INFINITY));
+        });
+        changed |= ui.add(Slider::new(&mut layer.opacity, 0.0..=1.0).text("Opacity")).changed();
+    }
+    changed
+}
@@ -0,0 +1,256 @@
+use std::{fmt, fs, io, path::Path};
+
+use egui::{color_picker::{color_edit_button_srgba, Alpha}, Color32, Sense, Stroke, TextEdit, Ui};
+use emath::Vec2;
+
+#[cfg(test)]
+mod tests;
+
+const SWATCH_SIZE: Vec2 = Vec2::splat(18.0);
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Swatch {
+    pub name: String,
+    pub color: Color32,
+}
+
+impl Swatch {
+    pub fn new(name: impl Into<String>, color: Color32) -> Self {
+        Self { name: name.into(), color }
+    }
+}
+
+/// Palette file formats, picked from the file extension.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PaletteFormat {
+    /// GIMP `.gpl`: named RGB entries.
+    Gimp,
+    /// Paint.NET `.txt`: one `AARRGGBB` per line, `;` starts a comment.
+    PaintNet,
+    /// Plain `.hex` list: one `RRGGBB` per line.
+    HexList,
+}
+
+impl PaletteFormat {
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "gpl" => Some(Self::Gimp),
+            "txt" => Some(Self::PaintNet),
+            "hex" => Some(Self::HexList),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum PaletteError {
+    Io(io::Error),
+    UnknownFormat,
+    /// A line could not be parsed, numbered from 1.
+    Invalid { line: usize, reason: String },
+}
+
+impl fmt::Display for PaletteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "could not access the palette file: {error}"),
+            Self::UnknownFormat => write!(f, "unknown palette format, expected a .gpl, .txt or .hex file"),
+            Self::Invalid { line, reason } => write!(f, "invalid palette at line {line}: {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for PaletteError {}
+
+impl From<io::Error> for PaletteError {
+    fn from(value: io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+/// Named swatches plus the two colors new terrains are picked from.
+pub struct Palette {
+    pub name: String,
+    pub swatches: Vec<Swatch>,
+    /// Picked with the primary (left) mouse button.
+    pub primary: Color32,
+    /// Picked with the secondary (right) mouse button.
+    pub secondary: Color32,
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        let swatches = vec![
+            Swatch::new("Grass", Color32::from_rgb(25, 200, 100)),
+            Swatch::new("Forest", Color32::from_rgb(20, 110, 50)),
+            Swatch::new("Water", Color32::from_rgb(40, 110, 210)),
+            Swatch::new("Sand", Color32::from_rgb(230, 210, 140)),
+            Swatch::new("Mountain", Color32::from_rgb(130, 120, 110)),
+            Swatch::new("Snow", Color32::from_rgb(240, 245, 250)),
+        ];
+        Self {
+            name: "Default".into(),
+            primary: swatches[0].color,
+            secondary: swatches[2].color,
+            swatches,
+        }
+    }
+}
+
+impl Palette {
+    pub fn load(path: &Path) -> Result<Self, PaletteError> {
+        let format = PaletteFormat::from_path(path).ok_or(PaletteError::UnknownFormat)?;
+        let name = path.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default();
+        let mut palette = Self::parse(&fs::read_to_string(path)?, format)?;
+        if palette.name.is_empty() {
+            palette.name = name;
+        }
+        Ok(palette)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), PaletteError> {
+        let format = PaletteFormat::from_path(path).ok_or(PaletteError::UnknownFormat)?;
+        fs::write(path, self.serialize(format))?;
+        Ok(())
+    }
+
+    /// Reads swatches from `text`, keeping the default primary and secondary colors.
+    pub fn parse(text: &str, format: PaletteFormat) -> Result<Self, PaletteError> {
+        let mut palette = Self { name: String::new(), swatches: Vec::new(), ..Default::default() };
+        let mut lines = text.lines().enumerate().map(|(index, line)| (index + 1, line.trim()));
+
+        if format == PaletteFormat::Gimp {
+            match lines.next() {
+                Some((_, "GIMP Palette")) => (),
+                _ => return Err(PaletteError::Invalid { line: 1, reason: "missing \"GIMP Palette\" header".into() }),
+            }
+        }
+        for (line, content) in lines {
+            let invalid = |reason: &str| PaletteError::Invalid { line, reason: reason.into() };
+            match format {
+                PaletteFormat::Gimp => {
+                    if let Some(name) = content.strip_prefix("Name:") {
+                        palette.name = name.trim().into();
+                        continue;
+                    }
+                    if content.is_empty() || content.starts_with('#') || content.starts_with("Columns:") {
+                        continue;
+                    }
+                    let mut fields = content.split_whitespace();
+                    let mut channel = || fields.next()
+                        .and_then(|field| field.parse::<u8>().ok())
+                        .ok_or_else(|| invalid("expected three channels between 0 and 255"));
+                    let (r, g, b) = (channel()?, channel()?, channel()?);
+                    let name = fields.collect::<Vec<_>>().join(" ");
+                    palette.swatches.push(Swatch::new(name, Color32::from_rgb(r, g, b)));
+                }
+                PaletteFormat::PaintNet | PaletteFormat::HexList => {
+                    if content.is_empty() || content.starts_with(';') {
+                        continue;
+                    }
+                    let digits = content.trim_start_matches('#');
+                    let color = parse_hex_color(digits, format == PaletteFormat::PaintNet)
+                        .ok_or_else(|| invalid("expected a hexadecimal color"))?;
+                    palette.swatches.push(Swatch::new(format!("#{}", digits.to_ascii_lowercase()), color));
+                }
+            }
+        }
+        Ok(palette)
+    }
+
+    pub fn serialize(&self, format: PaletteFormat) -> String {
+        let mut text = String::new();
+        match format {
+            PaletteFormat::Gimp => {
+                text.push_str(&format!("GIMP Palette\nName: {}\nColumns: 0\n#\n", self.name));
+                for Swatch { name, color } in &self.swatches {
+                    let [r, g, b, _] = color.to_srgba_unmultiplied();
+                    text.push_str(&format!("{r:3} {g:3} {b:3}\t{name}\n"));
+                }
+            }
+            PaletteFormat::PaintNet => {
+                text.push_str(&format!("; Paint.NET Palette File\n; {}\n", self.name));
+                for Swatch { color, .. } in &self.swatches {
+                    let [r, g, b, a] = color.to_srgba_unmultiplied();
+                    text.push_str(&format!("{a:02X}{r:02X}{g:02X}{b:02X}\n"));
+                }
+            }
+            PaletteFormat::HexList => {
+                for Swatch { color, .. } in &self.swatches {
+                    let [r, g, b, _] = color.to_srgba_unmultiplied();
+                    text.push_str(&format!("{r:02x}{g:02x}{b:02x}\n"));
+                }
+            }
+        }
+        text
+    }
+
+    pub fn swap_colors(&mut self) {
+        std::mem::swap(&mut self.primary, &mut self.secondary);
+    }
+
+    pub fn ui(&mut self, ui: &mut Ui) {
+        ui.heading("Palette");
+        ui.horizontal(|ui| {
+            color_edit_button_srgba(ui, &mut self.primary, Alpha::OnlyBlend).on_hover_text("Primary (left click)");
+            color_edit_button_srgba(ui, &mut self.secondary, Alpha::OnlyBlend).on_hover_text("Secondary (right click)");
+            if ui.button("Swap").on_hover_text("Swap primary and secondary").clicked() {
+                self.swap_colors();
+            }
+        });
+        ui.label("Left click a swatch to pick it as primary, right click for secondary.");
+        ui.separator();
+
+        ui.horizontal(|ui| {
+            ui.label("Name:");
+            ui.add(TextEdit::singleline(&mut self.name).desired_width(f32::INFINITY));
+        });
+        let mut removed = None;
+        egui::ScrollArea::vertical().max_height((ui.available_height() - 64.0).max(0.0)).show(ui, |ui| {
+            for (index, swatch) in self.swatches.iter_mut().enumerate() {
+                ui.horizontal(|ui| {
+                    let (rect, response) = ui.allocate_exact_size(SWATCH_SIZE, Sense::click());
+                    let outline = if swatch.color == self.primary {
+                        Stroke::new(2.0, ui.visuals().strong_text_color())
+                    } else if swatch.color == self.secondary {
+                        Stroke::new(1.0, ui.visuals().text_color())
+                    } else {
+                        Stroke::NONE
+                    };
+                    ui.painter().rect(rect, 2.0, swatch.color, outline);
+                    if response.clicked() {
+                        self.primary = swatch.color;
+                    }
+                    if response.secondary_clicked() {
+                        self.secondary = swatch.color;
+                    }
+                    color_edit_button_srgba(ui, &mut swatch.color, Alpha::OnlyBlend);
+                    ui.add(TextEdit::singleline(&mut swatch.name).desired_width(100.0));
+                    if ui.small_button("x").on_hover_text("Remove swatch").clicked() {
+                        removed = Some(index);
+                    }
+                });
+            }
+        });
+        if let Some(index) = removed {
+            self.swatches.remove(index);
+        }
+        if ui.button("Add primary color").clicked() {
+            let name = format!("Color {}", self.swatches.len() + 1);
+            self.swatches.push(Swatch::new(name, self.primary));
+        }
+    }
+}
+
+fn parse_hex_color(digits: &str, with_alpha: bool) -> Option<Color32> {
+    let expected = if with_alpha { 8 } else { 6 };
+    if digits.len() != expected || !digits.is_ascii() {
+        return None;
+    }
+    let mut channels = (0..expected / 2).map(|i| u8::from_str_radix(&digits[2 * i..2 * i + 2], 16));
+    let mut next = || channels.next()?.ok();
+    let a = if with_alpha { next()? } else { 255 };
+    let (r, g, b) = (next()?, next()?, next()?);
+    Some(Color32::from_rgba_unmultiplied(r, g, b, a))
+}
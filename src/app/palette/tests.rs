@@ -0,0 +1,73 @@
+use egui::Color32;
+
+use super::{Palette, PaletteError, PaletteFormat, Swatch};
+
+fn sample() -> Palette {
+    Palette {
+        name: "Terrain".into(),
+        swatches: vec![
+            Swatch::new("Deep water", Color32::from_rgb(10, 40, 120)),
+            Swatch::new("Grass", Color32::from_rgb(25, 200, 100)),
+        ],
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_gimp_roundtrip() {
+    let palette = sample();
+    let text = palette.serialize(PaletteFormat::Gimp);
+    let actual = Palette::parse(&text, PaletteFormat::Gimp).unwrap();
+
+    assert_eq!(palette.name, actual.name);
+    assert_eq!(palette.swatches, actual.swatches);
+}
+
+#[test]
+fn test_gimp_parse() {
+    let text = "GIMP Palette\nName: Sample\nColumns: 4\n# comment\n255   0   0\tBright red\n  0 128 255\n";
+    let palette = Palette::parse(text, PaletteFormat::Gimp).unwrap();
+
+    assert_eq!("Sample", palette.name);
+    assert_eq!(
+        vec![
+            Swatch::new("Bright red", Color32::from_rgb(255, 0, 0)),
+            Swatch::new("", Color32::from_rgb(0, 128, 255)),
+        ],
+        palette.swatches
+    );
+}
+
+#[test]
+fn test_paint_net_roundtrip() {
+    let palette = sample();
+    let text = palette.serialize(PaletteFormat::PaintNet);
+    let actual = Palette::parse(&text, PaletteFormat::PaintNet).unwrap();
+
+    let colors: Vec<_> = actual.swatches.iter().map(|swatch| swatch.color).collect();
+    assert_eq!(vec![Color32::from_rgb(10, 40, 120), Color32::from_rgb(25, 200, 100)], colors);
+    assert_eq!("#ff0a2878", actual.swatches[0].name);
+}
+
+#[test]
+fn test_hex_list_parse() {
+    let palette = Palette::parse("19c864\n#0A2878\n\n", PaletteFormat::HexList).unwrap();
+    let colors: Vec<_> = palette.swatches.iter().map(|swatch| swatch.color).collect();
+    assert_eq!(vec![Color32::from_rgb(25, 200, 100), Color32::from_rgb(10, 40, 120)], colors);
+}
+
+#[test]
+fn test_invalid_palettes() {
+    assert!(matches!(
+        Palette::parse("Not a palette\n", PaletteFormat::Gimp),
+        Err(PaletteError::Invalid { line: 1, .. })
+    ));
+    assert!(matches!(
+        Palette::parse("GIMP Palette\n255 0\n", PaletteFormat::Gimp),
+        Err(PaletteError::Invalid { line: 2, .. })
+    ));
+    assert!(matches!(
+        Palette::parse("; comment\nFF00FF\n", PaletteFormat::PaintNet),
+        Err(PaletteError::Invalid { line: 2, .. })
+    ));
+}
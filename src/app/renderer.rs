@@ -2,13 +2,14 @@
 use eframe::glow;
 use eframe::egui_glow;
 use glow::HasContext;
-use emath::Vec2;
+
+use super::grid::Vertex;
+
 pub struct Renderer {
     program: glow::Program,
     vertex_array: glow::VertexArray,
     vertex_buffer: glow::Buffer,
     vertex_count: i32,
-    angle: f32,
 }
 impl Renderer {
     pub unsafe fn new(gl: &glow::Context) -> Self {
@@ -53,6 +54,8 @@ impl Renderer {
             .map(compile_shaders)
             .collect();
 
+        gl.bind_attrib_location(program, 0, "v_position");
+        gl.bind_attrib_location(program, 1, "v_fill");
         gl.link_program(program);
         assert!(
             gl.get_program_link_status(program),
@@ -70,15 +73,15 @@ impl Renderer {
             vertex_array,
             vertex_buffer,
             vertex_count:0,
-            angle: 0.0
         }
     }
 
-    pub unsafe fn draw(&self, gl: &glow::Context) {
+    pub unsafe fn draw(&self, gl: &glow::Context, view_projection: &[f32; 9]) {
         gl.use_program(Some(self.program));
-        gl.uniform_1_f32(
-            gl.get_uniform_location(self.program, "u_angle").as_ref(),
-            self.angle
+        gl.uniform_matrix_3_f32_slice(
+            gl.get_uniform_location(self.program, "u_view_projection").as_ref(),
+            false,
+            view_projection
         );
         gl.bind_vertex_array(Some(self.vertex_array));
         //gl.bind_vertex_buffer(0, Some(self.vertex_buffer), 0, 0);
@@ -87,9 +90,9 @@ impl Renderer {
         }
     }
 
-    pub unsafe fn update_mesh(&mut self, gl: &glow::Context, mesh: &[[f32;2]]) {
+    pub unsafe fn update_mesh(&mut self, gl: &glow::Context, mesh: &[Vertex]) {
         let ptr = mesh.as_ptr() as *const u8;
-        let len = mesh.len() * 2 * core::mem::size_of::<f32>();
+        let len = std::mem::size_of_val(mesh);
         let mesh_u8: &[u8] = core::slice::from_raw_parts(ptr, len);
 
         gl.bind_buffer(glow::ARRAY_BUFFER, Some(self.vertex_buffer));
@@ -98,24 +101,26 @@ impl Renderer {
         self.vertex_count = mesh.len() as i32;
     }
 
-    pub fn rotate(&mut self, angle: Vec2) {
-        self.angle += angle[0];
-    }
-
     pub unsafe fn clear_resources(&self, gl: &glow::Context) {
         gl.delete_program(self.program);
         gl.delete_vertex_array(self.vertex_array);
+        gl.delete_buffer(self.vertex_buffer);
     }
 }
 
-unsafe fn create_vertex_array(gl: &glow::Context, ) -> (glow::NativeBuffer, glow::NativeVertexArray) {
+unsafe fn create_vertex_array(gl: &glow::Context) -> (glow::NativeBuffer, glow::NativeVertexArray) {
     let vbo = gl.create_buffer().expect("Failed to create VBO!");
     gl.bind_buffer(glow::ARRAY_BUFFER, Some(vbo));
     gl.buffer_data_u8_slice(glow::ARRAY_BUFFER, &[], glow::DYNAMIC_DRAW);
 
+    let stride = core::mem::size_of::<Vertex>() as i32;
+    let color_offset = core::mem::offset_of!(Vertex, color) as i32;
+
     let vao = gl.create_vertex_array().expect("Failed to create VAO!");
-    gl.bind_vertex_array(Some((vao)));
+    gl.bind_vertex_array(Some(vao));
     gl.enable_vertex_attrib_array(0);
-    gl.vertex_attrib_pointer_f32(0, 2, glow::FLOAT, false, 8, 0);
+    gl.vertex_attrib_pointer_f32(0, 2, glow::FLOAT, false, stride, 0);
+    gl.enable_vertex_attrib_array(1);
+    gl.vertex_attrib_pointer_f32(1, 4, glow::UNSIGNED_BYTE, true, stride, color_offset);
     (vbo, vao)
 }
\ No newline at end of file
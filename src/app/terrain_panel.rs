@@ -0,0 +1,188 @@
+use egui::{color_picker::{color_edit_button_srgba, Alpha}, Color32, DragValue, Grid as UiGrid, Key, Modifiers, Sense, Stroke, TextEdit, Ui};
+use emath::Vec2;
+
+use super::grid::{Grid, Terrain, TerrainId};
+
+const SWATCH_SIZE: Vec2 = Vec2::splat(18.0);
+
+/// Lists the map's terrains, picks the ones tools paint with and edits them.
+pub struct TerrainPanel {
+    /// Painted with the primary (left) mouse button.
+    pub primary: TerrainId,
+    /// Painted with the secondary (right) mouse button.
+    pub secondary: TerrainId,
+    selected: Option<TerrainId>,
+    new_key: String,
+    new_value: String,
+}
+
+/// What the last frame of the panel changed.
+#[derive(Default)]
+pub struct TerrainPanelResponse {
+    /// A terrain color changed, the mesh has to be rebuilt.
+    pub recolored: bool,
+    /// A terrain was deleted, edits referring to it can no longer be undone.
+    pub removed: bool,
+}
+
+impl Default for TerrainPanel {
+    fn default() -> Self {
+        Self {
+            primary: TerrainId(0),
+            secondary: TerrainId(2),
+            selected: None,
+            new_key: String::new(),
+            new_value: String::new(),
+        }
+    }
+}
+
+impl TerrainPanel {
+    pub fn swap(&mut self) {
+        std::mem::swap(&mut self.primary, &mut self.secondary);
+    }
+
+    pub fn handle_shortcuts(&mut self, ctx: &egui::Context) {
+        if !ctx.wants_keyboard_input() && ctx.input_mut(|input| input.consume_key(Modifiers::NONE, Key::X)) {
+            self.swap();
+        }
+    }
+
+    /// Falls back to existing terrains when the selection points to deleted ones.
+    pub fn validate(&mut self, grid: &Grid) {
+        let registry = grid.terrains();
+        let fallback = registry.ids().next().unwrap_or_default();
+        for id in [&mut self.primary, &mut self.secondary] {
+            if !registry.contains(*id) {
+                *id = fallback;
+            }
+        }
+        if self.selected.is_some_and(|id| !registry.contains(id)) {
+            self.selected = None;
+        }
+    }
+
+    /// `new_color` is used for terrains created from the panel.
+    pub fn ui(&mut self, ui: &mut Ui, grid: &mut Grid, new_color: Color32) -> TerrainPanelResponse {
+        let mut response = TerrainPanelResponse::default();
+        let colors_before: Vec<_> = grid.terrains().iter().map(|(_, terrain)| terrain.color).collect();
+
+        ui.heading("Terrains");
+        ui.horizontal(|ui| {
+            let registry = grid.terrains();
+            let name = |id| registry.get(id).map_or("-", |terrain: &Terrain| terrain.name.as_str());
+            ui.label(format!("Left: {}  Right: {}", name(self.primary), name(self.secondary)));
+            if ui.button("Swap").on_hover_text("Swap left and right terrains (X)").clicked() {
+                self.swap();
+            }
+        });
+        ui.separator();
+
+        if grid.terrains().is_empty() {
+            ui.label("The map has no terrains, add one to start painting.");
+        }
+        egui::ScrollArea::vertical().id_source("terrain_list").max_height(200.0).show(ui, |ui| {
+            for (id, terrain) in grid.terrains().iter() {
+                ui.horizontal(|ui| {
+                    let (rect, swatch) = ui.allocate_exact_size(SWATCH_SIZE, Sense::click());
+                    let outline = if id == self.primary {
+                        Stroke::new(2.0, ui.visuals().strong_text_color())
+                    } else if id == self.secondary {
+                        Stroke::new(1.0, ui.visuals().text_color())
+                    } else {
+                        Stroke::NONE
+                    };
+                    ui.painter().rect(rect, 2.0, terrain.color, outline);
+                    let label = ui.selectable_label(self.selected == Some(id), &terrain.name);
+                    if swatch.clicked() || label.clicked() {
+                        self.primary = id;
+                        self.selected = Some(id);
+                    }
+                    if swatch.secondary_clicked() || label.secondary_clicked() {
+                        self.secondary = id;
+                        self.selected = Some(id);
+                    }
+                });
+            }
+        });
+        if ui.button("New terrain").clicked() {
+            let name = format!("Terrain {}", grid.terrains().len() + 1);
+            if let Some(id) = grid.terrains_mut().add(Terrain::new(name, new_color)) {
+                self.selected = Some(id);
+            }
+        }
+
+        if let Some(id) = self.selected {
+            ui.separator();
+            let in_use = grid.uses_terrain(id);
+            if let Some(terrain) = grid.terrains_mut().get_mut(id) {
+                self.edit_ui(ui, terrain, new_color);
+            }
+            let delete = ui.add_enabled(!in_use, egui::Button::new("Delete terrain"))
+                .on_disabled_hover_text("Cells are still painted with this terrain");
+            if delete.clicked() {
+                grid.terrains_mut().remove(id);
+                self.selected = None;
+                response.removed = true;
+            }
+        }
+
+        self.validate(grid);
+        let colors_after: Vec<_> = grid.terrains().iter().map(|(_, terrain)| terrain.color).collect();
+        response.recolored = response.removed || colors_before != colors_after;
+        response
+    }
+
+    fn edit_ui(&mut self, ui: &mut Ui, terrain: &mut Terrain, palette_color: Color32) {
+        UiGrid::new("terrain_editor").num_columns(2).show(ui, |ui| {
+            ui.label("Name");
+            ui.text_edit_singleline(&mut terrain.name);
+            ui.end_row();
+
+            ui.label("Color");
+            ui.horizontal(|ui| {
+                color_edit_button_srgba(ui, &mut terrain.color, Alpha::OnlyBlend);
+                if ui.button("From palette").on_hover_text("Use the palette's primary color").clicked() {
+                    terrain.color = palette_color;
+                }
+            });
+            ui.end_row();
+
+            ui.label("Movement cost");
+            ui.add(DragValue::new(&mut terrain.movement_cost).range(0..=1000));
+            ui.end_row();
+
+            ui.label("Passable");
+            ui.checkbox(&mut terrain.passable, "");
+            ui.end_row();
+
+            ui.label("Opaque");
+            ui.checkbox(&mut terrain.opaque, "").on_hover_text("Blocks line of sight");
+            ui.end_row();
+        });
+
+        ui.label("Properties");
+        let mut removed = None;
+        UiGrid::new("terrain_properties").num_columns(3).show(ui, |ui| {
+            for (key, value) in terrain.properties.iter_mut() {
+                ui.label(key);
+                ui.add(TextEdit::singleline(value).desired_width(80.0));
+                if ui.small_button("x").clicked() {
+                    removed = Some(key.clone());
+                }
+                ui.end_row();
+            }
+            ui.add(TextEdit::singleline(&mut self.new_key).hint_text("key").desired_width(80.0));
+            ui.add(TextEdit::singleline(&mut self.new_value).hint_text("value").desired_width(80.0));
+            let key = self.new_key.trim();
+            if ui.add_enabled(!key.is_empty(), egui::Button::new("Add")).clicked() {
+                terrain.properties.insert(key.to_owned(), std::mem::take(&mut self.new_value));
+                self.new_key.clear();
+            }
+            ui.end_row();
+        });
+        if let Some(key) = removed {
+            terrain.properties.remove(&key);
+        }
+    }
+}
@@ -0,0 +1,245 @@
+use egui::{Align2, Color32, CursorIcon, FontId, Key, Modifiers, Painter, PointerButton, Shape, Stroke, Ui};
+use emath::{Pos2, Rect};
+
+use super::{camera::Camera, grid::{Grid, Hex, HexCorner, HexEdge, Point, Selection, Site, TerrainId}, history::History};
+
+mod brush; use brush::Brush;
+mod bucket; use bucket::Bucket;
+mod shape; use shape::{ShapeKind, ShapeTool};
+mod route; use route::Route;
+mod sight; use sight::Sight;
+mod select; use select::Select;
+mod edge; use edge::EdgeBrush;
+
+/// Everything a tool may touch while handling a pointer event.
+pub struct ToolContext<'a> {
+    pub grid: &'a mut Grid,
+    history: &'a mut History,
+    pub selection: &'a mut Selection,
+    /// Primary and secondary terrains.
+    pub terrains: [TerrainId; 2],
+    /// Button that started the current stroke.
+    pub button: PointerButton,
+    /// Keys held during the event.
+    pub modifiers: Modifiers,
+    /// World position of the pointer.
+    pub pointer: Point,
+    changed: bool,
+}
+
+impl<'a> ToolContext<'a> {
+    pub fn new(
+        grid: &'a mut Grid,
+        history: &'a mut History,
+        selection: &'a mut Selection,
+        terrains: [TerrainId; 2],
+        button: PointerButton,
+        modifiers: Modifiers,
+        pointer: Point,
+    ) -> Self {
+        Self { grid, history, selection, terrains, button, modifiers, pointer, changed: false }
+    }
+
+    /// Terrain bound to the button that started the stroke.
+    pub fn terrain(&self) -> TerrainId {
+        match self.button {
+            PointerButton::Secondary => self.terrains[1],
+            _ => self.terrains[0],
+        }
+    }
+
+    /// Paints (`Some`) or erases (`None`) cells, edges or corners of the active layer
+    /// as part of the current undo step. Hidden and locked layers are left untouched.
+    pub fn set_cells<S: Into<Site>>(&mut self, cells: impl IntoIterator<Item = (S, Option<TerrainId>)>) {
+        let layer = self.grid.active_layer();
+        if !layer.is_editable() {
+            return;
+        }
+        let cells: Vec<(Site, _)> = cells.into_iter()
+            .map(|(site, value)| (site.into(), value))
+            .filter(|(site, value)| layer.site(*site) != *value)
+            .collect();
+        if !cells.is_empty() {
+            self.history.apply(self.grid, cells);
+            self.changed = true;
+        }
+    }
+
+    /// Whether any cell was modified through this context.
+    pub fn changed(&self) -> bool {
+        self.changed
+    }
+}
+
+/// Draws tool previews on top of the viewport, in world coordinates.
+pub struct Overlay<'a> {
+    painter: &'a Painter,
+    camera: &'a Camera,
+    viewport: Rect,
+    grid: &'a Grid,
+}
+
+impl<'a> Overlay<'a> {
+    pub fn new(painter: &'a Painter, camera: &'a Camera, viewport: Rect, grid: &'a Grid) -> Self {
+        Self { painter, camera, viewport, grid }
+    }
+
+    fn to_screen(&self, Point { x, y }: Point) -> Pos2 {
+        self.camera.world_to_screen(&self.viewport, [x as f32, y as f32].into())
+    }
+
+    fn corners(&self, hex: Hex) -> Vec<Pos2> {
+        self.grid.polygon_corners(hex)
+            .map(|corner| self.camera.world_to_screen(&self.viewport, corner.into()))
+            .collect()
+    }
+
+    fn center(&self, hex: Hex) -> Pos2 {
+        self.to_screen(self.grid.cell_center(hex))
+    }
+
+    pub fn outline_cell(&self, hex: Hex, stroke: impl Into<Stroke>) {
+        self.painter.add(Shape::closed_line(self.corners(hex), stroke.into()));
+    }
+
+    pub fn fill_cell(&self, hex: Hex, color: Color32) {
+        self.painter.add(Shape::convex_polygon(self.corners(hex), color, Stroke::NONE));
+    }
+
+    /// Outlines a polygon given in world coordinates.
+    pub fn outline_polygon(&self, points: &[Point], stroke: impl Into<Stroke>) {
+        let points = points.iter().map(|point| self.to_screen(*point)).collect();
+        self.painter.add(Shape::closed_line(points, stroke.into()));
+    }
+
+    pub fn outline_edge(&self, edge: HexEdge, stroke: impl Into<Stroke>) {
+        let [a, b] = edge.to_pixels(self.grid.layout());
+        self.painter.line_segment([self.to_screen(a), self.to_screen(b)], stroke.into());
+    }
+
+    /// Circles `corner`, `radius` pixels wide.
+    pub fn outline_corner(&self, corner: HexCorner, radius: f32, stroke: impl Into<Stroke>) {
+        self.painter.circle_stroke(self.to_screen(corner.to_pixel(self.grid.layout())), radius, stroke.into());
+    }
+
+    /// Connects the centers of `cells` in order.
+    pub fn polyline(&self, cells: &[Hex], stroke: impl Into<Stroke>) {
+        let points = cells.iter().map(|hex| self.center(*hex)).collect();
+        self.painter.add(Shape::line(points, stroke.into()));
+    }
+
+    /// Writes small `text` centered on `hex`, meant for one label on every cell.
+    pub fn caption(&self, hex: Hex, text: impl ToString) {
+        self.painter.text(self.center(hex), Align2::CENTER_CENTER, text.to_string(), FontId::monospace(10.0), Color32::from_white_alpha(200));
+    }
+
+    /// Writes `text` centered on `hex`, on a dark background so it stays legible over any terrain.
+    pub fn label(&self, hex: Hex, text: impl ToString) {
+        let galley = self.painter.layout_no_wrap(text.to_string(), FontId::proportional(14.0), Color32::WHITE);
+        let rect = Align2::CENTER_CENTER.anchor_size(self.center(hex), galley.size());
+        self.painter.rect_filled(rect.expand(2.0), 2.0, Color32::from_black_alpha(180));
+        self.painter.galley(rect.min, galley, Color32::WHITE);
+    }
+}
+
+/// An interaction mode for the viewport.
+///
+/// The viewport converts pointer positions to cells and forwards them here. Every
+/// stroke, from `pointer_down` to `pointer_up`, is recorded as one undo step.
+pub trait Tool {
+    fn name(&self) -> &'static str;
+
+    /// Key that selects this tool.
+    fn shortcut(&self) -> Option<Key> {
+        None
+    }
+
+    fn cursor(&self) -> CursorIcon {
+        CursorIcon::Crosshair
+    }
+
+    fn pointer_down(&mut self, _ctx: &mut ToolContext, _cell: Hex) {}
+
+    /// Called whenever the pointer enters a new cell while a button is held.
+    fn pointer_drag(&mut self, _ctx: &mut ToolContext, _cell: Hex) {}
+
+    /// Called every frame the pointer moves while a button is held, for tools
+    /// that follow `ctx.pointer` more finely than cell by cell.
+    fn pointer_move(&mut self, _ctx: &mut ToolContext) {}
+
+    fn pointer_up(&mut self, _ctx: &mut ToolContext, _cell: Hex) {}
+
+    /// Called every frame with the cell under the pointer, if any.
+    fn hover(&mut self, _grid: &Grid, _cell: Option<Hex>) {}
+
+    /// Called every frame with the world position of the pointer, if over the
+    /// viewport, for tools aiming at something finer than cells.
+    fn hover_point(&mut self, _grid: &Grid, _pointer: Option<Point>) {}
+
+    fn draw_overlay(&self, _overlay: &Overlay) {}
+
+    /// Tool specific settings shown under the tool list.
+    fn options_ui(&mut self, _ui: &mut Ui) {}
+}
+
+pub struct Toolbox {
+    tools: Vec<Box<dyn Tool>>,
+    active: usize,
+}
+
+impl Default for Toolbox {
+    fn default() -> Self {
+        Self {
+            tools: vec![
+                Box::new(Brush::default()),
+                Box::new(Brush::eraser()),
+                Box::new(EdgeBrush::default()),
+                Box::new(EdgeBrush::corners()),
+                Box::new(Bucket::default()),
+                Box::new(ShapeTool::new(ShapeKind::Line)),
+                Box::new(ShapeTool::new(ShapeKind::Ring)),
+                Box::new(ShapeTool::new(ShapeKind::Hexagon)),
+                Box::new(ShapeTool::new(ShapeKind::Rhombus)),
+                Box::new(ShapeTool::new(ShapeKind::Triangle)),
+                Box::new(Route::default()),
+                Box::new(Sight::default()),
+                Box::new(Select::default()),
+            ],
+            active: 0,
+        }
+    }
+}
+
+impl Toolbox {
+    pub fn active(&mut self) -> &mut dyn Tool {
+        self.tools[self.active].as_mut()
+    }
+
+    /// Switches tools when one of their shortcuts was pressed.
+    pub fn handle_shortcuts(&mut self, ctx: &egui::Context) {
+        if ctx.wants_keyboard_input() {
+            return;
+        }
+        let pressed = self.tools.iter().position(|tool| {
+            tool.shortcut().is_some_and(|key| ctx.input_mut(|input| input.consume_key(egui::Modifiers::NONE, key)))
+        });
+        if let Some(index) = pressed {
+            self.active = index;
+        }
+    }
+
+    pub fn ui(&mut self, ui: &mut Ui) {
+        ui.heading("Toolbox");
+        for (index, tool) in self.tools.iter().enumerate() {
+            let label = match tool.shortcut() {
+                Some(key) => format!("{} ({})", tool.name(), key.name()),
+                None => tool.name().to_owned(),
+            };
+            if ui.selectable_label(index == self.active, label).clicked() {
+                self.active = index;
+            }
+        }
+        ui.separator();
+        self.active().options_ui(ui);
+    }
+}
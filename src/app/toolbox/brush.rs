@@ -0,0 +1,88 @@
+use egui::{Color32, CursorIcon, DragValue, Key, Ui};
+
+use super::{Overlay, Tool, ToolContext};
+use crate::app::grid::{Grid, Hex, HexIterators, HexUtility, TerrainId};
+
+/// Paints or erases every cell within `radius` of the pointer.
+#[derive(Default)]
+pub struct Brush {
+    erase: bool,
+    radius: i32,
+    last: Option<Hex>,
+    hovered: Option<Hex>,
+}
+
+impl Brush {
+    pub fn eraser() -> Self {
+        Self { erase: true, ..Default::default() }
+    }
+
+    fn footprint(&self, center: Hex) -> impl Iterator<Item = Hex> {
+        center.range(self.radius)
+    }
+
+    fn value(&self, ctx: &ToolContext) -> Option<TerrainId> {
+        (!self.erase).then(|| ctx.terrain())
+    }
+
+    fn stroke_to(&mut self, ctx: &mut ToolContext, cell: Hex) {
+        //Fill the gap when the pointer skipped cells between two frames
+        let path = match self.last {
+            Some(last) => last.line(cell),
+            None => vec![cell],
+        };
+        let value = self.value(ctx);
+        let cells: Vec<_> = path.into_iter()
+            .flat_map(|center| self.footprint(center))
+            .map(|hex| (hex, value))
+            .collect();
+        ctx.set_cells(cells);
+        self.last = Some(cell);
+    }
+}
+
+impl Tool for Brush {
+    fn name(&self) -> &'static str {
+        if self.erase { "Eraser" } else { "Brush" }
+    }
+
+    fn shortcut(&self) -> Option<Key> {
+        Some(if self.erase { Key::E } else { Key::B })
+    }
+
+    fn cursor(&self) -> CursorIcon {
+        if self.erase { CursorIcon::NotAllowed } else { CursorIcon::Crosshair }
+    }
+
+    fn pointer_down(&mut self, ctx: &mut ToolContext, cell: Hex) {
+        self.last = None;
+        self.stroke_to(ctx, cell);
+    }
+
+    fn pointer_drag(&mut self, ctx: &mut ToolContext, cell: Hex) {
+        self.stroke_to(ctx, cell);
+    }
+
+    fn pointer_up(&mut self, _ctx: &mut ToolContext, _cell: Hex) {
+        self.last = None;
+    }
+
+    fn hover(&mut self, _grid: &Grid, cell: Option<Hex>) {
+        self.hovered = cell;
+    }
+
+    fn draw_overlay(&self, overlay: &Overlay) {
+        if let Some(center) = self.hovered {
+            for hex in self.footprint(center) {
+                overlay.outline_cell(hex, (1.5, Color32::WHITE));
+            }
+        }
+    }
+
+    fn options_ui(&mut self, ui: &mut Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Radius:");
+            ui.add(DragValue::new(&mut self.radius).range(0..=16));
+        });
+    }
+}
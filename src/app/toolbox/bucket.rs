@@ -0,0 +1,92 @@
+use egui::{Color32, CursorIcon, DragValue, Key, Slider, Ui};
+
+use super::{Overlay, Tool, ToolContext};
+use crate::app::grid::{FillMatch, FillOptions, Grid, Hex};
+
+/// Paints the region of matching cells connected to the clicked one.
+pub struct Bucket {
+    options: FillOptions,
+    /// Kept while another match mode is selected.
+    tolerance: u8,
+    radius: i32,
+    hovered: Option<Hex>,
+}
+
+impl Default for Bucket {
+    fn default() -> Self {
+        Self {
+            options: FillOptions::default(),
+            tolerance: 32,
+            radius: 8,
+            hovered: None,
+        }
+    }
+}
+
+impl Tool for Bucket {
+    fn name(&self) -> &'static str {
+        "Bucket fill"
+    }
+
+    fn shortcut(&self) -> Option<Key> {
+        Some(Key::G)
+    }
+
+    fn cursor(&self) -> CursorIcon {
+        CursorIcon::PointingHand
+    }
+
+    fn pointer_down(&mut self, ctx: &mut ToolContext, cell: Hex) {
+        let terrain = Some(ctx.terrain());
+        let region = ctx.grid.flood_fill(cell, &self.options);
+        ctx.set_cells(region.into_iter().map(|hex| (hex, terrain)));
+    }
+
+    fn hover(&mut self, _grid: &Grid, cell: Option<Hex>) {
+        self.hovered = cell;
+    }
+
+    fn draw_overlay(&self, overlay: &Overlay) {
+        if let Some(hex) = self.hovered {
+            overlay.outline_cell(hex, (1.5, Color32::WHITE));
+        }
+    }
+
+    fn options_ui(&mut self, ui: &mut Ui) {
+        fill_options_ui(ui, &mut self.options, &mut self.tolerance, &mut self.radius, None);
+        ui.checkbox(&mut self.options.fill_empty, "Fill empty cells")
+            .on_hover_text("Clicking an empty cell fills the empty area inside the map bounds");
+    }
+}
+
+/// Match mode and radius settings, shared with the magic wand. The radius only
+/// applies to contiguous fills, so it is hidden while `contiguous` is unchecked.
+pub(super) fn fill_options_ui(ui: &mut Ui, options: &mut FillOptions, tolerance: &mut u8, radius: &mut i32, contiguous: Option<&mut bool>) {
+    let mut similar = matches!(options.matching, FillMatch::SimilarColor { .. });
+    ui.horizontal(|ui| {
+        ui.label("Match:");
+        ui.radio_value(&mut similar, false, "Same terrain");
+        ui.radio_value(&mut similar, true, "Similar color");
+    });
+    if similar {
+        ui.add(Slider::new(tolerance, 0..=255).text("Tolerance"));
+    }
+    options.matching = if similar {
+        FillMatch::SimilarColor { tolerance: *tolerance }
+    } else {
+        FillMatch::SameTerrain
+    };
+
+    if let Some(contiguous) = contiguous {
+        ui.checkbox(contiguous, "Contiguous");
+        if !*contiguous {
+            return;
+        }
+    }
+    let mut limited = options.radius.is_some();
+    ui.horizontal(|ui| {
+        ui.checkbox(&mut limited, "Limit radius:");
+        ui.add_enabled(limited, DragValue::new(radius).range(0..=256));
+    });
+    options.radius = limited.then_some(*radius);
+}
@@ -0,0 +1,92 @@
+use egui::{Color32, CursorIcon, Key, Ui};
+
+use super::{Overlay, Tool, ToolContext};
+use crate::app::grid::{Grid, Hex, HexCorner, HexEdge, Layout, Point, Site};
+
+/// Paints or erases the edge, or the corner, nearest to the pointer.
+#[derive(Default)]
+pub struct EdgeBrush {
+    corners: bool,
+    erase: bool,
+    last: Option<Point>,
+    hovered: Option<Site>,
+}
+
+impl EdgeBrush {
+    pub fn corners() -> Self {
+        Self { corners: true, ..Default::default() }
+    }
+
+    fn site(&self, layout: Layout, point: Point) -> Site {
+        if self.corners {
+            HexCorner::from_pixel(layout, point).into()
+        } else {
+            HexEdge::from_pixel(layout, point).into()
+        }
+    }
+
+    fn stroke_to(&mut self, ctx: &mut ToolContext) {
+        let layout = ctx.grid.layout();
+        let value = (!self.erase).then(|| ctx.terrain());
+        //Sample the way from the last frame a few times per cell so no edge is skipped
+        let from = self.last.unwrap_or(ctx.pointer);
+        let to = ctx.pointer;
+        let step = layout.size.x.min(layout.size.y) / 4.0;
+        let distance = ((to.x - from.x).powi(2) + (to.y - from.y).powi(2)).sqrt();
+        let samples = (distance / step).ceil().max(1.0) as usize;
+        let mut sites: Vec<(Site, _)> = Vec::new();
+        for i in 1..=samples {
+            let t = i as f64 / samples as f64;
+            let site = self.site(layout, Point { x: from.x + (to.x - from.x) * t, y: from.y + (to.y - from.y) * t });
+            if sites.last().map(|(last, _)| *last) != Some(site) {
+                sites.push((site, value));
+            }
+        }
+        ctx.set_cells(sites);
+        self.last = Some(to);
+    }
+}
+
+impl Tool for EdgeBrush {
+    fn name(&self) -> &'static str {
+        if self.corners { "Corner" } else { "Edge" }
+    }
+
+    fn shortcut(&self) -> Option<Key> {
+        Some(if self.corners { Key::C } else { Key::W })
+    }
+
+    fn cursor(&self) -> CursorIcon {
+        if self.erase { CursorIcon::NotAllowed } else { CursorIcon::Crosshair }
+    }
+
+    fn pointer_down(&mut self, ctx: &mut ToolContext, _cell: Hex) {
+        self.last = None;
+        self.stroke_to(ctx);
+    }
+
+    fn pointer_move(&mut self, ctx: &mut ToolContext) {
+        self.stroke_to(ctx);
+    }
+
+    fn pointer_up(&mut self, _ctx: &mut ToolContext, _cell: Hex) {
+        self.last = None;
+    }
+
+    fn hover_point(&mut self, grid: &Grid, pointer: Option<Point>) {
+        self.hovered = pointer.map(|point| self.site(grid.layout(), point));
+    }
+
+    fn draw_overlay(&self, overlay: &Overlay) {
+        let stroke = (3.0, Color32::WHITE);
+        match self.hovered {
+            Some(Site::Edge(edge)) => overlay.outline_edge(edge, stroke),
+            Some(Site::Corner(corner)) => overlay.outline_corner(corner, 6.0, stroke),
+            _ => (),
+        }
+    }
+
+    fn options_ui(&mut self, ui: &mut Ui) {
+        ui.checkbox(&mut self.erase, "Erase");
+    }
+}
@@ -0,0 +1,159 @@
+use std::collections::HashMap;
+
+use egui::{Color32, DragValue, Key, PointerButton, Ui};
+
+use super::{Overlay, Tool, ToolContext};
+use crate::app::grid::{Grid, Hex, Path};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum RouteMode {
+    /// Cheapest path between two clicked cells.
+    Path,
+    /// Every cell reachable from the clicked one within the budget.
+    Reachable,
+}
+
+/// Inspects movement over the map without editing it. Right click clears the route.
+pub struct Route {
+    mode: RouteMode,
+    budget: u32,
+    show_costs: bool,
+    start: Option<Hex>,
+    goal: Option<Hex>,
+    hovered: Option<Hex>,
+    //Recomputed every frame so edits made with other tools show up
+    path: Option<Path>,
+    reachable: Vec<Hex>,
+    costs: HashMap<Hex, u32>,
+}
+
+impl Default for Route {
+    fn default() -> Self {
+        Self {
+            mode: RouteMode::Path,
+            budget: 6,
+            show_costs: false,
+            start: None,
+            goal: None,
+            hovered: None,
+            path: None,
+            reachable: Vec::new(),
+            costs: HashMap::new(),
+        }
+    }
+}
+
+impl Route {
+    /// Cell the path leads to: the second clicked cell, or the pointer until then.
+    fn target(&self) -> Option<Hex> {
+        self.goal.or(self.hovered)
+    }
+}
+
+impl Tool for Route {
+    fn name(&self) -> &'static str {
+        "Route"
+    }
+
+    fn shortcut(&self) -> Option<Key> {
+        Some(Key::P)
+    }
+
+    fn pointer_down(&mut self, ctx: &mut ToolContext, cell: Hex) {
+        if ctx.button == PointerButton::Secondary {
+            self.start = None;
+            self.goal = None;
+            return;
+        }
+        match (self.mode, self.start, self.goal) {
+            (RouteMode::Path, Some(_), None) => self.goal = Some(cell),
+            _ => {
+                self.start = Some(cell);
+                self.goal = None;
+            }
+        }
+    }
+
+    fn hover(&mut self, grid: &Grid, cell: Option<Hex>) {
+        self.hovered = cell;
+        self.path = None;
+        self.reachable.clear();
+        self.costs.clear();
+        let Some(start) = self.start else {
+            return;
+        };
+        match self.mode {
+            RouteMode::Path => {
+                self.path = self.target().and_then(|target| grid.find_path(start, target));
+            }
+            RouteMode::Reachable => {
+                self.reachable = grid.reachable(start, self.budget);
+                if self.show_costs {
+                    self.costs = grid.cost_field(start, Some(self.budget));
+                }
+            }
+        }
+    }
+
+    fn draw_overlay(&self, overlay: &Overlay) {
+        let Some(start) = self.start else {
+            if let Some(hex) = self.hovered {
+                overlay.outline_cell(hex, (1.5, Color32::WHITE));
+            }
+            return;
+        };
+        match self.mode {
+            RouteMode::Path => {
+                match (&self.path, self.target()) {
+                    (Some(path), _) => {
+                        overlay.polyline(&path.cells, (3.0, Color32::YELLOW));
+                        if let Some(end) = path.cells.last() {
+                            overlay.outline_cell(*end, (2.0, Color32::YELLOW));
+                            overlay.label(*end, path.cost);
+                        }
+                    }
+                    (None, Some(target)) => {
+                        overlay.outline_cell(target, (2.0, Color32::RED));
+                        overlay.label(target, "No route");
+                    }
+                    (None, None) => (),
+                }
+            }
+            RouteMode::Reachable => {
+                for hex in &self.reachable {
+                    overlay.outline_cell(*hex, (1.5, Color32::LIGHT_BLUE));
+                }
+                for (hex, cost) in &self.costs {
+                    overlay.label(*hex, cost);
+                }
+            }
+        }
+        overlay.outline_cell(start, (2.5, Color32::GREEN));
+    }
+
+    fn options_ui(&mut self, ui: &mut Ui) {
+        ui.horizontal(|ui| {
+            ui.radio_value(&mut self.mode, RouteMode::Path, "Path");
+            ui.radio_value(&mut self.mode, RouteMode::Reachable, "Reachable");
+        });
+        match self.mode {
+            RouteMode::Path => {
+                let status = match (&self.path, self.start) {
+                    (_, None) => "Click the start cell.".to_owned(),
+                    (Some(path), _) => format!("Cost {} over {} steps", path.cost, path.cells.len() - 1),
+                    (None, _) => "No route".to_owned(),
+                };
+                ui.label(status);
+            }
+            RouteMode::Reachable => {
+                ui.horizontal(|ui| {
+                    ui.label("Budget:");
+                    ui.add(DragValue::new(&mut self.budget).range(0..=1000));
+                });
+                ui.checkbox(&mut self.show_costs, "Show costs");
+                ui.label(format!("{} cells reachable", self.reachable.len()));
+            }
+        }
+        ui.label("Right click clears the route.");
+    }
+}
@@ -0,0 +1,200 @@
+use egui::{Color32, CursorIcon, Key, PointerButton, Ui};
+
+use super::{bucket::fill_options_ui, Overlay, Tool, ToolContext};
+use crate::app::grid::{FillOptions, Grid, Hex, HexIterators, HexMath, HexUtility, Point, SelectionOp, Transform};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum SelectMode {
+    /// Cells centered inside a dragged rectangle.
+    Rectangle,
+    /// Cells centered inside a freehand outline.
+    Lasso,
+    /// Cells within the dragged distance of the pressed one.
+    Range,
+    /// Cells matching the clicked one, like the bucket fill.
+    Wand,
+}
+
+/// What the current stroke is doing.
+enum Gesture {
+    Pick { op: SelectionOp, start: Point, start_cell: Hex, points: Vec<Point>, cell: Hex },
+    Move { start: Hex, offset: Hex, duplicate: bool, cells: Vec<Hex> },
+}
+
+/// Picks cells for the editing commands. Shift adds to the selection, Ctrl
+/// subtracts from it. Dragging a selected cell moves the selected cells, Alt
+/// copies them instead. Right click deselects everything.
+pub struct Select {
+    mode: SelectMode,
+    wand: FillOptions,
+    /// Kept while another match mode is selected.
+    tolerance: u8,
+    /// Kept while the radius is not limited.
+    radius: i32,
+    contiguous: bool,
+    gesture: Option<Gesture>,
+    hovered: Option<Hex>,
+}
+
+impl Default for Select {
+    fn default() -> Self {
+        Self {
+            mode: SelectMode::Rectangle,
+            wand: FillOptions { fill_empty: false, ..FillOptions::default() },
+            tolerance: 32,
+            radius: 8,
+            contiguous: true,
+            gesture: None,
+            hovered: None,
+        }
+    }
+}
+
+impl Select {
+    fn pick(&self, grid: &Grid, start: Point, start_cell: Hex, points: &[Point], cell: Hex, end: Point) -> Vec<Hex> {
+        match self.mode {
+            SelectMode::Rectangle => {
+                let cells = grid.cells_in_rect(start, end);
+                //A click without dragging picks the cell under the pointer
+                if cells.is_empty() && start_cell == cell {
+                    grid.cells_in_range(cell, 0)
+                } else {
+                    cells
+                }
+            }
+            SelectMode::Lasso => grid.cells_in_lasso(points),
+            SelectMode::Range => grid.cells_in_range(start_cell, start_cell.distance(cell)),
+            SelectMode::Wand => grid.magic_wand(start_cell, &self.wand, self.contiguous),
+        }
+    }
+}
+
+impl Tool for Select {
+    fn name(&self) -> &'static str {
+        "Select"
+    }
+
+    fn shortcut(&self) -> Option<Key> {
+        Some(Key::S)
+    }
+
+    fn cursor(&self) -> CursorIcon {
+        match self.gesture {
+            Some(Gesture::Move { .. }) => CursorIcon::Grabbing,
+            _ => CursorIcon::Crosshair,
+        }
+    }
+
+    fn pointer_down(&mut self, ctx: &mut ToolContext, cell: Hex) {
+        if ctx.button == PointerButton::Secondary {
+            ctx.selection.clear();
+            return;
+        }
+        let modifiers = ctx.modifiers;
+        let op = if modifiers.shift {
+            SelectionOp::Add
+        } else if modifiers.command {
+            SelectionOp::Subtract
+        } else {
+            SelectionOp::Replace
+        };
+        if op == SelectionOp::Replace && ctx.selection.contains(cell) {
+            let cells = ctx.selection.cells().collect();
+            self.gesture = Some(Gesture::Move { start: cell, offset: Hex::new(0, 0), duplicate: modifiers.alt, cells });
+            return;
+        }
+        if self.mode == SelectMode::Wand {
+            let cells = ctx.grid.magic_wand(cell, &self.wand, self.contiguous);
+            ctx.selection.apply(op, cells);
+            return;
+        }
+        let start = ctx.pointer;
+        self.gesture = Some(Gesture::Pick { op, start, start_cell: cell, points: vec![start], cell });
+    }
+
+    fn pointer_drag(&mut self, _ctx: &mut ToolContext, cell: Hex) {
+        match &mut self.gesture {
+            Some(Gesture::Pick { cell: current, .. }) => *current = cell,
+            Some(Gesture::Move { start, offset, .. }) => *offset = cell.sub(*start),
+            None => (),
+        }
+    }
+
+    fn pointer_move(&mut self, ctx: &mut ToolContext) {
+        if let Some(Gesture::Pick { points, .. }) = &mut self.gesture {
+            if points.last() != Some(&ctx.pointer) {
+                points.push(ctx.pointer);
+            }
+        }
+    }
+
+    fn pointer_up(&mut self, ctx: &mut ToolContext, _cell: Hex) {
+        match self.gesture.take() {
+            Some(Gesture::Pick { op, start, start_cell, points, cell }) => {
+                let end = points.last().copied().unwrap_or(start);
+                let cells = self.pick(ctx.grid, start, start_cell, &points, cell, end);
+                ctx.selection.apply(op, cells);
+            }
+            Some(Gesture::Move { offset, duplicate, cells, .. }) => {
+                if offset == Hex::new(0, 0) || !ctx.grid.active_layer().is_editable() {
+                    return;
+                }
+                let layer = ctx.grid.active_layer().id();
+                let mut changes = ctx.grid.transform_changes(layer, cells, Transform::Translate(offset));
+                //Copies keep the sites they were made from
+                if duplicate {
+                    changes.retain(|(_, value)| value.is_some());
+                }
+                ctx.set_cells(changes);
+                ctx.selection.translate(offset);
+            }
+            None => (),
+        }
+    }
+
+    fn hover(&mut self, _grid: &Grid, cell: Option<Hex>) {
+        self.hovered = cell;
+    }
+
+    fn draw_overlay(&self, overlay: &Overlay) {
+        let stroke = (1.5, Color32::WHITE);
+        match &self.gesture {
+            Some(Gesture::Pick { start, start_cell, points, cell, .. }) => match self.mode {
+                SelectMode::Rectangle => {
+                    let end = points.last().copied().unwrap_or(*start);
+                    let corners = [*start, Point { x: end.x, y: start.y }, end, Point { x: start.x, y: end.y }];
+                    overlay.outline_polygon(&corners, stroke);
+                }
+                SelectMode::Lasso => overlay.outline_polygon(points, stroke),
+                SelectMode::Range => {
+                    for hex in start_cell.ring(start_cell.distance(*cell)) {
+                        overlay.outline_cell(hex, stroke);
+                    }
+                    overlay.outline_cell(*start_cell, (2.0, Color32::WHITE));
+                }
+                SelectMode::Wand => (),
+            },
+            Some(Gesture::Move { offset, cells, .. }) => {
+                for hex in cells {
+                    overlay.outline_cell(hex.add(*offset), stroke);
+                }
+            }
+            None => if let Some(hex) = self.hovered {
+                overlay.outline_cell(hex, stroke);
+            },
+        }
+    }
+
+    fn options_ui(&mut self, ui: &mut Ui) {
+        ui.horizontal_wrapped(|ui| {
+            ui.radio_value(&mut self.mode, SelectMode::Rectangle, "Rectangle");
+            ui.radio_value(&mut self.mode, SelectMode::Lasso, "Lasso");
+            ui.radio_value(&mut self.mode, SelectMode::Range, "Range");
+            ui.radio_value(&mut self.mode, SelectMode::Wand, "Wand");
+        });
+        if self.mode == SelectMode::Wand {
+            fill_options_ui(ui, &mut self.wand, &mut self.tolerance, &mut self.radius, Some(&mut self.contiguous));
+        }
+        ui.label("Shift adds, Ctrl subtracts. Drag the selection to move it, Alt+drag copies it.");
+    }
+}
@@ -0,0 +1,118 @@
+use std::cmp::{max, min};
+
+use egui::{Color32, CursorIcon, Key, Ui};
+
+use super::{Overlay, Tool, ToolContext};
+use crate::app::grid::{shapes, Grid, Hex, HexIterators, HexUtility};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ShapeKind {
+    /// From the pressed cell to the released one.
+    Line,
+    /// Hollow, centered on the pressed cell and going through the released one.
+    Ring,
+    /// Filled, centered on the pressed cell and reaching the released one.
+    Hexagon,
+    /// Spanned by the pressed and released cells as opposite corners.
+    Rhombus,
+    /// Cornered on the pressed cell, sized by the distance to the released one.
+    Triangle,
+}
+
+/// Drags out a shape from the pressed cell, previewing it until the button is released.
+pub struct ShapeTool {
+    kind: ShapeKind,
+    anchor: Option<Hex>,
+    hovered: Option<Hex>,
+}
+
+impl ShapeTool {
+    pub fn new(kind: ShapeKind) -> Self {
+        Self { kind, anchor: None, hovered: None }
+    }
+
+    fn cells(&self, anchor: Hex, cell: Hex) -> Vec<Hex> {
+        let distance = anchor.distance(cell);
+        match self.kind {
+            ShapeKind::Line => anchor.line(cell),
+            ShapeKind::Ring => anchor.ring(distance).collect(),
+            ShapeKind::Hexagon => anchor.range(distance).collect(),
+            ShapeKind::Rhombus => shapes::rhombus(
+                Hex::new(min(anchor.q(), cell.q()), min(anchor.r(), cell.r())),
+                Hex::new(max(anchor.q(), cell.q()), max(anchor.r(), cell.r())),
+            ),
+            ShapeKind::Triangle => shapes::triangle(anchor, distance),
+        }
+    }
+}
+
+impl Tool for ShapeTool {
+    fn name(&self) -> &'static str {
+        match self.kind {
+            ShapeKind::Line => "Line",
+            ShapeKind::Ring => "Ring",
+            ShapeKind::Hexagon => "Hexagon",
+            ShapeKind::Rhombus => "Rhombus",
+            ShapeKind::Triangle => "Triangle",
+        }
+    }
+
+    fn shortcut(&self) -> Option<Key> {
+        Some(match self.kind {
+            ShapeKind::Line => Key::L,
+            ShapeKind::Ring => Key::O,
+            ShapeKind::Hexagon => Key::H,
+            ShapeKind::Rhombus => Key::R,
+            ShapeKind::Triangle => Key::T,
+        })
+    }
+
+    fn cursor(&self) -> CursorIcon {
+        CursorIcon::Cell
+    }
+
+    fn pointer_down(&mut self, _ctx: &mut ToolContext, cell: Hex) {
+        self.anchor = Some(cell);
+        self.hovered = Some(cell);
+    }
+
+    fn pointer_drag(&mut self, _ctx: &mut ToolContext, cell: Hex) {
+        self.hovered = Some(cell);
+    }
+
+    fn pointer_up(&mut self, ctx: &mut ToolContext, cell: Hex) {
+        let Some(anchor) = self.anchor.take() else {
+            return;
+        };
+        let terrain = Some(ctx.terrain());
+        ctx.set_cells(self.cells(anchor, cell).into_iter().map(|hex| (hex, terrain)));
+    }
+
+    fn hover(&mut self, _grid: &Grid, cell: Option<Hex>) {
+        //The pointer may leave the viewport mid drag, keep previewing the last cell
+        if cell.is_some() || self.anchor.is_none() {
+            self.hovered = cell;
+        }
+    }
+
+    fn draw_overlay(&self, overlay: &Overlay) {
+        let cells = match (self.anchor, self.hovered) {
+            (Some(anchor), Some(cell)) => self.cells(anchor, cell),
+            (None, Some(cell)) => vec![cell],
+            _ => return,
+        };
+        for hex in cells {
+            overlay.outline_cell(hex, (1.5, Color32::WHITE));
+        }
+    }
+
+    fn options_ui(&mut self, ui: &mut Ui) {
+        let hint = match self.kind {
+            ShapeKind::Line => "Drag from the first to the last cell.",
+            ShapeKind::Ring | ShapeKind::Hexagon => "Drag from the center outwards.",
+            ShapeKind::Rhombus => "Drag between two opposite corners.",
+            ShapeKind::Triangle => "Drag from the corner outwards.",
+        };
+        ui.label(hint);
+    }
+}
@@ -0,0 +1,90 @@
+use std::collections::HashSet;
+
+use egui::{Color32, DragValue, Key, PointerButton, Ui};
+
+use super::{Overlay, Tool, ToolContext};
+use crate::app::grid::{FovMode, Grid, Hex, HexUtility};
+
+/// Highlights what a cell can see. Follows the pointer until a cell is clicked,
+/// then shows the line of sight from that cell to the pointer.
+pub struct Sight {
+    mode: FovMode,
+    radius: i32,
+    origin: Option<Hex>,
+    hovered: Option<Hex>,
+    //Recomputed every frame so edits made with other tools show up
+    visible: HashSet<Hex>,
+    line: Vec<Hex>,
+    line_clear: bool,
+}
+
+impl Default for Sight {
+    fn default() -> Self {
+        Self {
+            mode: FovMode::Shadowcast,
+            radius: 8,
+            origin: None,
+            hovered: None,
+            visible: HashSet::new(),
+            line: Vec::new(),
+            line_clear: false,
+        }
+    }
+}
+
+impl Tool for Sight {
+    fn name(&self) -> &'static str {
+        "Sight"
+    }
+
+    fn shortcut(&self) -> Option<Key> {
+        Some(Key::V)
+    }
+
+    fn pointer_down(&mut self, ctx: &mut ToolContext, cell: Hex) {
+        self.origin = match ctx.button {
+            PointerButton::Secondary => None,
+            _ => Some(cell),
+        };
+    }
+
+    fn hover(&mut self, grid: &Grid, cell: Option<Hex>) {
+        self.hovered = cell;
+        self.visible.clear();
+        self.line.clear();
+        let Some(origin) = self.origin.or(cell) else {
+            return;
+        };
+        self.visible = grid.field_of_view(origin, self.radius, self.mode);
+        if let (Some(_), Some(target)) = (self.origin, cell) {
+            self.line = origin.line(target);
+            self.line_clear = grid.line_of_sight(origin, target);
+        }
+    }
+
+    fn draw_overlay(&self, overlay: &Overlay) {
+        for hex in &self.visible {
+            overlay.fill_cell(*hex, Color32::from_rgba_premultiplied(60, 60, 0, 60));
+        }
+        if let Some(origin) = self.origin.or(self.hovered) {
+            overlay.outline_cell(origin, (2.5, Color32::YELLOW));
+        }
+        if self.line.len() > 1 {
+            let color = if self.line_clear { Color32::GREEN } else { Color32::RED };
+            overlay.polyline(&self.line, (2.0, color));
+        }
+    }
+
+    fn options_ui(&mut self, ui: &mut Ui) {
+        ui.horizontal(|ui| {
+            ui.radio_value(&mut self.mode, FovMode::Shadowcast, "Shadowcast");
+            ui.radio_value(&mut self.mode, FovMode::Symmetric, "Symmetric");
+        });
+        ui.horizontal(|ui| {
+            ui.label("Radius:");
+            ui.add(DragValue::new(&mut self.radius).range(0..=64));
+        });
+        ui.label(format!("{} cells visible", self.visible.len()));
+        ui.label("Click to fix the viewpoint, right click to follow the pointer again.");
+    }
+}
@@ -0,0 +1,463 @@
+//! Batch processing of maps from the command line. Nothing here opens a window
+//! or needs an OpenGL context, so it runs on headless machines.
+
+use std::{
+    collections::BTreeMap, fmt, io::{self, Write}, path::{Path, PathBuf}, process::ExitCode
+};
+
+use crate::app::{
+    coordinates::CoordSystem,
+    grid::{
+        load_image, map_file::{color_from_str, MapFileError}, tiled::TiledError, ExportError, Generator, Grid, HexOrientation, ImageOptions,
+        Point, RasterError, RasterImport, Rules, RulesError, Sampling, Synthesis, SynthesisError, Terrain, TerrainId,
+        LAYOUT_ORIENTATION_FLAT, MAX_OCTAVES
+    },
+    palette::{Palette, PaletteError},
+};
+
+const USAGE: &str = "\
+Usage:
+  hex-editor                          Start the editor
+  hex-editor new SHAPE -o OUTPUT [--flat] [--terrain NAME]
+                                      Create a map. SHAPE is one of
+                                        hexagon RADIUS
+                                        rhombus WIDTH HEIGHT
+                                        triangle SIZE
+  hex-editor generate SHAPE -o OUTPUT [--seed N] [--scale CELLS] [--octaves N] [--flat]
+                                      Create a map of SHAPE with terrain from noise.
+                                      Features are CELLS wide (12 by default), and the
+                                      same seed always gives the same map
+  hex-editor synthesize MAP SHAPE -o OUTPUT [--rules FILE] [--seed N] [--repaint]
+                                      Fill the cells of SHAPE with terrain that fits
+                                      the painted cells of MAP, by wave function
+                                      collapse. Rules are learned from MAP, or read
+                                      from FILE. --repaint overwrites painted cells
+  hex-editor import IMAGE OUTPUT [--cells N] [--sampling MODE] [--palette FILE] [--flat]
+                                      Trace a .png or .jpg image into a map N cells
+                                      wide (32 by default). MODE is center, average or
+                                      majority.
+                                      A .gpl, .txt or .hex palette snaps colors to it
+  hex-editor convert INPUT OUTPUT     Rewrite a map in the format of OUTPUT's extension
+                                      (.json, .tmx, .tmj, .png, .svg)
+  hex-editor render INPUT OUTPUT [--cell-size PIXELS] [--margin PIXELS]
+                    [--background #RRGGBBAA] [--outline PIXELS] [--labels SYSTEM]
+                                      Draw a map into a .png or .svg image. SYSTEM is
+                                      cube, axial, odd, even or doubled
+  hex-editor validate INPUT...        Check that maps can be opened
+  hex-editor stats INPUT              Print what a map contains
+  hex-editor help                     Show this message";
+
+#[derive(Debug)]
+pub enum CliError {
+    /// The command line could not be understood.
+    Usage(String),
+    Map { path: PathBuf, error: MapFileError },
+    Tiled { path: PathBuf, error: TiledError },
+    Export { path: PathBuf, error: ExportError },
+    Raster { path: PathBuf, error: RasterError },
+    Palette { path: PathBuf, error: PaletteError },
+    Rules { path: PathBuf, error: RulesError },
+    Synthesis { path: PathBuf, error: SynthesisError },
+    /// `validate` found this many broken maps.
+    Invalid(usize),
+    /// Writing the report failed.
+    Io(io::Error),
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Usage(reason) => write!(f, "{reason}"),
+            Self::Map { path, error } => write!(f, "{}: {error}", path.display()),
+            Self::Tiled { path, error } => write!(f, "{}: {error}", path.display()),
+            Self::Export { path, error } => write!(f, "{}: {error}", path.display()),
+            Self::Raster { path, error } => write!(f, "{}: {error}", path.display()),
+            Self::Palette { path, error } => write!(f, "{}: {error}", path.display()),
+            Self::Rules { path, error } => write!(f, "{}: {error}", path.display()),
+            Self::Synthesis { path, error } => write!(f, "{}: {error}", path.display()),
+            Self::Invalid(1) => write!(f, "1 map is invalid"),
+            Self::Invalid(count) => write!(f, "{count} maps are invalid"),
+            Self::Io(error) => write!(f, "{error}"),
+        }
+    }
+}
+
+impl From<io::Error> for CliError {
+    fn from(value: io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+/// File types told apart by extension.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Format {
+    Map,
+    /// Tiled's `.tmx` and `.tmj`.
+    Tiled,
+    Png,
+    Svg,
+}
+
+impl Format {
+    fn of(path: &Path) -> Result<Self, CliError> {
+        let extension = path.extension().and_then(|extension| extension.to_str()).unwrap_or_default();
+        match extension.to_ascii_lowercase().as_str() {
+            "json" => Ok(Format::Map),
+            "tmx" | "tmj" => Ok(Format::Tiled),
+            "png" => Ok(Format::Png),
+            "svg" => Ok(Format::Svg),
+            _ => Err(CliError::Usage(format!("{}: unknown file format", path.display()))),
+        }
+    }
+}
+
+/// Runs the command in `args`, the program arguments without the program name.
+pub fn run(args: &[String]) -> ExitCode {
+    let mut stdout = io::stdout().lock();
+    match execute(args, &mut stdout) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(CliError::Usage(reason)) => {
+            eprintln!("hex-editor: {reason}\n\n{USAGE}");
+            ExitCode::from(2)
+        }
+        Err(error) => {
+            eprintln!("hex-editor: {error}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Runs a command, writing its report to `out`.
+pub fn execute(args: &[String], out: &mut impl Write) -> Result<(), CliError> {
+    let mut args: Vec<&str> = args.iter().map(String::as_str).collect();
+    if args.is_empty() {
+        return Err(CliError::Usage("missing command".into()));
+    }
+    let command = args.remove(0);
+    match command {
+        "new" => new(args),
+        "generate" => generate(args),
+        "synthesize" => synthesize(args),
+        "import" => import(args),
+        "convert" => convert(args),
+        "render" => render(args),
+        "validate" => validate(args, out),
+        "stats" => stats(args, out),
+        "help" | "-h" | "--help" => {
+            writeln!(out, "{USAGE}")?;
+            Ok(())
+        }
+        _ => Err(CliError::Usage(format!("unknown command \"{command}\""))),
+    }
+}
+
+/// Removes `name` and the value after it from `args`.
+fn take_option<'a>(args: &mut Vec<&'a str>, names: &[&str]) -> Result<Option<&'a str>, CliError> {
+    let Some(index) = args.iter().position(|arg| names.contains(arg)) else {
+        return Ok(None);
+    };
+    if index + 1 >= args.len() {
+        return Err(CliError::Usage(format!("{} needs a value", args[index])));
+    }
+    let value = args.remove(index + 1);
+    args.remove(index);
+    Ok(Some(value))
+}
+
+/// Removes `name` from `args`, returning whether it was there.
+fn take_flag(args: &mut Vec<&str>, name: &str) -> bool {
+    let found = args.iter().position(|arg| *arg == name);
+    if let Some(index) = found {
+        args.remove(index);
+    }
+    found.is_some()
+}
+
+/// Checks that only `count` positional arguments are left once options were taken.
+fn positionals(args: Vec<&str>, count: usize) -> Result<Vec<&str>, CliError> {
+    if let Some(option) = args.iter().find(|arg| arg.starts_with('-') && arg.parse::<i64>().is_err()) {
+        return Err(CliError::Usage(format!("unknown option \"{option}\"")));
+    }
+    if args.len() != count {
+        return Err(CliError::Usage(format!("expected {count} arguments, found {}", args.len())));
+    }
+    Ok(args)
+}
+
+fn number<T: std::str::FromStr>(text: &str, what: &str) -> Result<T, CliError> {
+    text.parse().map_err(|_| CliError::Usage(format!("{what} must be a number, found \"{text}\"")))
+}
+
+fn size(text: &str) -> Result<i32, CliError> {
+    match number(text, "sizes")? {
+        size if size >= 0 => Ok(size),
+        _ => Err(CliError::Usage("sizes cannot be negative".into())),
+    }
+}
+
+fn load(path: &str) -> Result<Grid, CliError> {
+    let path = PathBuf::from(path);
+    match Format::of(&path)? {
+        Format::Map => Grid::load(&path).map_err(|error| CliError::Map { path, error }),
+        Format::Tiled => Grid::load_tiled(&path).map_err(|error| CliError::Tiled { path, error }),
+        Format::Png | Format::Svg => Err(CliError::Usage(format!("{}: images cannot be read as maps", path.display()))),
+    }
+}
+
+fn save(grid: &Grid, path: &str, image: &ImageOptions) -> Result<(), CliError> {
+    let path = PathBuf::from(path);
+    match Format::of(&path)? {
+        Format::Map => grid.save(&path).map_err(|error| CliError::Map { path, error }),
+        Format::Tiled => grid.save_tiled(&path).map_err(|error| CliError::Tiled { path, error }),
+        Format::Png => grid.save_png(&path, image).map_err(|error| CliError::Export { path, error }),
+        Format::Svg => grid.save_svg(&path, image).map_err(|error| CliError::Export { path, error }),
+    }
+}
+
+/// Makes the map described by the `SHAPE` arguments left in `args`.
+fn shape(args: Vec<&str>) -> Result<Grid, CliError> {
+    let shape = args.first().copied().unwrap_or_default();
+    match shape {
+        "hexagon" => {
+            let args = positionals(args, 2)?;
+            Ok(Grid::make_hex((0, 0), size(args[1])?))
+        }
+        "rhombus" => {
+            let args = positionals(args, 3)?;
+            Ok(Grid::make_rhombus((0, 0), (size(args[1])? - 1, size(args[2])? - 1)))
+        }
+        "triangle" => {
+            let args = positionals(args, 2)?;
+            Ok(Grid::make_triangle((0, 0), size(args[1])?))
+        }
+        _ => Err(CliError::Usage(format!("unknown shape \"{shape}\""))),
+    }
+}
+
+fn new(mut args: Vec<&str>) -> Result<(), CliError> {
+    let output = take_option(&mut args, &["-o", "--output"])?
+        .ok_or_else(|| CliError::Usage("missing -o OUTPUT".into()))?;
+    let terrain = take_option(&mut args, &["--terrain"])?;
+    let flat = take_flag(&mut args, "--flat");
+    let mut grid = shape(args)?;
+    if let Some(name) = terrain {
+        let id = grid.terrains().iter()
+            .find(|(_, terrain)| terrain.name.eq_ignore_ascii_case(name))
+            .map(|(id, _)| id)
+            .ok_or_else(|| CliError::Usage(format!("unknown terrain \"{name}\"")))?;
+        let cells: Vec<_> = grid.cells().collect();
+        for hex in cells {
+            grid.paint_cell(hex, id);
+        }
+    }
+    if flat {
+        grid.set_orientation(HexOrientation::Flat, false);
+    }
+    save(&grid, output, &ImageOptions::default())
+}
+
+fn generate(mut args: Vec<&str>) -> Result<(), CliError> {
+    let output = take_option(&mut args, &["-o", "--output"])?
+        .ok_or_else(|| CliError::Usage("missing -o OUTPUT".into()))?;
+    let mut generator = Generator::default();
+    if let Some(seed) = take_option(&mut args, &["--seed"])? {
+        generator.seed = number(seed, "--seed")?;
+    }
+    if let Some(scale) = take_option(&mut args, &["--scale"])? {
+        generator.scale = number(scale, "--scale")?;
+        if !(generator.scale > 0.0 && generator.scale.is_finite()) {
+            return Err(CliError::Usage("--scale must be positive".into()));
+        }
+    }
+    if let Some(octaves) = take_option(&mut args, &["--octaves"])? {
+        generator.octaves = number(octaves, "--octaves")?;
+        if !(1..=MAX_OCTAVES).contains(&generator.octaves) {
+            return Err(CliError::Usage(format!("--octaves must be between 1 and {MAX_OCTAVES}")));
+        }
+    }
+    let flat = take_flag(&mut args, "--flat");
+    let cells: Vec<_> = shape(args)?.cells().collect();
+    let mut layout = Grid::default().layout();
+    if flat {
+        layout.orientation = LAYOUT_ORIENTATION_FLAT;
+    }
+    let grid = Grid::generate(layout, cells, &generator);
+    save(&grid, output, &ImageOptions::default())
+}
+
+fn synthesize(mut args: Vec<&str>) -> Result<(), CliError> {
+    let output = take_option(&mut args, &["-o", "--output"])?
+        .ok_or_else(|| CliError::Usage("missing -o OUTPUT".into()))?;
+    let rules = take_option(&mut args, &["--rules"])?;
+    let mut options = Synthesis::default();
+    if let Some(seed) = take_option(&mut args, &["--seed"])? {
+        options.seed = number(seed, "--seed")?;
+    }
+    options.keep_painted = !take_flag(&mut args, "--repaint");
+    if args.is_empty() {
+        return Err(CliError::Usage("missing MAP".into()));
+    }
+    let input = args.remove(0);
+    let region: Vec<_> = shape(args)?.cells().collect();
+
+    let mut grid = load(input)?;
+    let rules = match rules {
+        Some(rules) => {
+            let path = PathBuf::from(rules);
+            Rules::load(&path, grid.terrains()).map_err(|error| CliError::Rules { path, error })?
+        }
+        None => Rules::learn(&grid, grid.cells().collect::<Vec<_>>()),
+    };
+    let cells = grid.synthesize(region, &rules, &options)
+        .map_err(|error| CliError::Synthesis { path: input.into(), error })?;
+    for (hex, terrain) in cells {
+        grid.paint_cell(hex, terrain);
+    }
+    save(&grid, output, &ImageOptions::default())
+}
+
+fn import(mut args: Vec<&str>) -> Result<(), CliError> {
+    let mut options = RasterImport::default();
+    let cells = match take_option(&mut args, &["--cells"])? {
+        Some(cells) => number(cells, "--cells")?,
+        None => 32.0,
+    };
+    if !(cells > 0.0 && f64::is_finite(cells)) {
+        return Err(CliError::Usage("--cells must be positive".into()));
+    }
+    if let Some(sampling) = take_option(&mut args, &["--sampling"])? {
+        options.sampling = Sampling::ALL.into_iter()
+            .find(|option| option.name().eq_ignore_ascii_case(sampling))
+            .ok_or_else(|| CliError::Usage(format!("unknown sampling \"{sampling}\"")))?;
+    }
+    if let Some(palette) = take_option(&mut args, &["--palette"])? {
+        let path = PathBuf::from(palette);
+        let palette = Palette::load(&path).map_err(|error| CliError::Palette { path, error })?;
+        options.palette = palette.swatches.into_iter()
+            .map(|swatch| Terrain::new(swatch.name, swatch.color))
+            .collect();
+    }
+    let flat = take_flag(&mut args, "--flat");
+    let args = positionals(args, 2)?;
+
+    let path = PathBuf::from(args[0]);
+    let image = load_image(&path).map_err(|error| CliError::Raster { path: path.clone(), error })?;
+    let mut layout = Grid::default().layout();
+    if flat {
+        layout.orientation = LAYOUT_ORIENTATION_FLAT;
+    }
+    options.fit_width(&image, layout, cells);
+    let grid = Grid::from_image(layout, &image, &options).map_err(|error| CliError::Raster { path, error })?;
+    save(&grid, args[1], &ImageOptions::default())
+}
+
+fn convert(args: Vec<&str>) -> Result<(), CliError> {
+    let args = positionals(args, 2)?;
+    let grid = load(args[0])?;
+    save(&grid, args[1], &ImageOptions::default())
+}
+
+fn render(mut args: Vec<&str>) -> Result<(), CliError> {
+    let mut options = ImageOptions::default();
+    if let Some(cell_size) = take_option(&mut args, &["--cell-size"])? {
+        options.cell_size = number(cell_size, "--cell-size")?;
+        if !(options.cell_size > 0.0 && options.cell_size.is_finite()) {
+            return Err(CliError::Usage("--cell-size must be positive".into()));
+        }
+    }
+    if let Some(margin) = take_option(&mut args, &["--margin"])? {
+        options.margin = number(margin, "--margin")?;
+    }
+    if let Some(background) = take_option(&mut args, &["--background"])? {
+        options.background = color_from_str(background)
+            .ok_or_else(|| CliError::Usage(format!("--background must look like #rrggbbaa, found \"{background}\"")))?;
+    }
+    if let Some(outline) = take_option(&mut args, &["--outline"])? {
+        options.outline_width = number(outline, "--outline")?;
+        if !(options.outline_width >= 0.0 && options.outline_width.is_finite()) {
+            return Err(CliError::Usage("--outline cannot be negative".into()));
+        }
+    }
+    if let Some(labels) = take_option(&mut args, &["--labels"])? {
+        options.labels = Some(match labels {
+            "cube" => CoordSystem::Cube,
+            "axial" => CoordSystem::Axial,
+            "odd" => CoordSystem::Offset { odd: true },
+            "even" => CoordSystem::Offset { odd: false },
+            "doubled" => CoordSystem::Doubled,
+            _ => return Err(CliError::Usage(format!("unknown coordinate system \"{labels}\""))),
+        });
+    }
+    let args = positionals(args, 2)?;
+    if matches!(Format::of(Path::new(args[1]))?, Format::Map | Format::Tiled) {
+        return Err(CliError::Usage(format!("{}: render writes .png or .svg images", args[1])));
+    }
+    let grid = load(args[0])?;
+    save(&grid, args[1], &options)
+}
+
+fn validate(args: Vec<&str>, out: &mut impl Write) -> Result<(), CliError> {
+    if args.is_empty() {
+        return Err(CliError::Usage("expected maps to validate".into()));
+    }
+    let mut invalid = 0;
+    for path in args {
+        match load(path) {
+            Ok(_) => writeln!(out, "{path}: ok")?,
+            Err(error) => {
+                invalid += 1;
+                writeln!(out, "{error}")?;
+            }
+        }
+    }
+    match invalid {
+        0 => Ok(()),
+        count => Err(CliError::Invalid(count)),
+    }
+}
+
+fn stats(args: Vec<&str>, out: &mut impl Write) -> Result<(), CliError> {
+    let args = positionals(args, 1)?;
+    let grid = load(args[0])?;
+
+    let orientation = match grid.orientation() {
+        HexOrientation::Pointy => "pointy",
+        HexOrientation::Flat => "flat",
+    };
+    let Point { x, y } = grid.layout().size;
+    writeln!(out, "{}", args[0])?;
+    writeln!(out, "  orientation: {orientation}, hex size {x} × {y}")?;
+
+    let mut usage: BTreeMap<TerrainId, usize> = BTreeMap::new();
+    let mut bounds: Option<[i32; 4]> = None;
+    for (hex, terrain) in grid.layers().iter().flat_map(|layer| layer.cells()) {
+        *usage.entry(terrain).or_default() += 1;
+        let [q_min, q_max, r_min, r_max] = bounds.unwrap_or([hex.q(), hex.q(), hex.r(), hex.r()]);
+        bounds = Some([q_min.min(hex.q()), q_max.max(hex.q()), r_min.min(hex.r()), r_max.max(hex.r())]);
+    }
+    let total: usize = usage.values().sum();
+    writeln!(out, "  cells: {total} in {} layers", grid.layers().len())?;
+    if let Some([q_min, q_max, r_min, r_max]) = bounds {
+        writeln!(out, "  bounds: q {q_min}..={q_max}, r {r_min}..={r_max}")?;
+    }
+    for layer in grid.layers() {
+        let mut flags = Vec::new();
+        if !layer.visible {
+            flags.push("hidden");
+        }
+        if layer.locked {
+            flags.push("locked");
+        }
+        let flags = if flags.is_empty() { String::new() } else { format!(" ({})", flags.join(", ")) };
+        let (edges, corners) = (layer.edges().count(), layer.corners().count());
+        let sites = if edges + corners == 0 { String::new() } else { format!(", {edges} edges, {corners} corners") };
+        writeln!(out, "  layer \"{}\": {} cells{sites}{flags}", layer.name, layer.cells().count())?;
+    }
+    for (id, terrain) in grid.terrains().iter() {
+        writeln!(out, "  terrain \"{}\": {} cells", terrain.name, usage.get(&id).copied().unwrap_or_default())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,182 @@
+use std::path::PathBuf;
+
+use super::{execute, CliError};
+use crate::app::grid::{Grid, HexOrientation};
+
+fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("hex-editor-cli-{}-{name}", std::process::id()))
+}
+
+fn run(args: &[&str]) -> (Result<(), CliError>, String) {
+    let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+    let mut out = Vec::new();
+    let result = execute(&args, &mut out);
+    (result, String::from_utf8(out).unwrap())
+}
+
+#[test]
+fn test_new_and_stats() {
+    let map = temp_path("new.json");
+    let path = map.to_str().unwrap();
+    let (result, _) = run(&["new", "hexagon", "2", "-o", path, "--flat", "--terrain", "water"]);
+    result.unwrap();
+
+    let grid = Grid::load(&map).unwrap();
+    assert_eq!(HexOrientation::Flat, grid.orientation());
+    assert_eq!(19, grid.cells().count());
+
+    let (result, out) = run(&["stats", path]);
+    result.unwrap();
+    assert!(out.contains("orientation: flat"), "{out}");
+    assert!(out.contains("cells: 19 in 4 layers"), "{out}");
+    assert!(out.contains("bounds: q -2..=2, r -2..=2"), "{out}");
+    assert!(out.contains("terrain \"Water\": 19 cells"), "{out}");
+    std::fs::remove_file(map).unwrap();
+}
+
+#[test]
+fn test_convert_and_render() {
+    let map = temp_path("convert.json");
+    let image = temp_path("convert.png");
+    let (map_path, image_path) = (map.to_str().unwrap(), image.to_str().unwrap());
+    run(&["new", "rhombus", "3", "2", "-o", map_path]).0.unwrap();
+
+    run(&["render", map_path, image_path, "--cell-size", "10", "--margin", "0"]).0.unwrap();
+    let rendered = image::open(&image).unwrap();
+    assert!(rendered.width() > 0 && rendered.width() <= 64, "{}", rendered.width());
+
+    let traced = temp_path("traced.json");
+    run(&["import", image_path, traced.to_str().unwrap(), "--cells", "4", "--sampling", "majority"]).0.unwrap();
+    assert!(Grid::load(&traced).unwrap().cells().count() >= 6);
+    std::fs::remove_file(traced).unwrap();
+
+    std::fs::remove_file(&image).unwrap();
+    run(&["convert", map_path, image_path]).0.unwrap();
+    assert!(image.exists());
+
+    let svg = temp_path("render.svg");
+    run(&["render", map_path, svg.to_str().unwrap(), "--outline", "2", "--labels", "odd", "--background", "#ffffffff"]).0.unwrap();
+    let document = std::fs::read_to_string(&svg).unwrap();
+    assert_eq!(6, document.matches("<text").count());
+    std::fs::remove_file(svg).unwrap();
+
+    let tiled = temp_path("convert.tmx");
+    run(&["convert", map_path, tiled.to_str().unwrap()]).0.unwrap();
+    run(&["convert", tiled.to_str().unwrap(), map_path]).0.unwrap();
+    assert_eq!(6, Grid::load(&map).unwrap().cells().count());
+    std::fs::remove_file(tiled).unwrap();
+
+    assert!(matches!(run(&["convert", image_path, map_path]).0, Err(CliError::Usage(_))));
+    assert!(matches!(run(&["render", map_path, "out.bmp"]).0, Err(CliError::Usage(_))));
+    std::fs::remove_file(map).unwrap();
+    std::fs::remove_file(image).unwrap();
+}
+
+#[test]
+fn test_import_jpeg() {
+    let photo = temp_path("photo.jpg");
+    let traced = temp_path("photo.json");
+    image::RgbImage::from_fn(32, 16, |x, _| if x < 16 { image::Rgb([240, 20, 20]) } else { image::Rgb([20, 20, 240]) })
+        .save(&photo)
+        .unwrap();
+    run(&["import", photo.to_str().unwrap(), traced.to_str().unwrap(), "--cells", "4"]).0.unwrap();
+    let grid = Grid::load(&traced).unwrap();
+    assert!(grid.cells().count() >= 4);
+    //Compression blurs the colors a little, red still reads as red
+    let reddish = grid.cells()
+        .map(|hex| grid.terrains().color(grid.cell(hex).unwrap()))
+        .any(|color| color.r() > 200 && color.b() < 60);
+    assert!(reddish);
+    std::fs::remove_file(photo).unwrap();
+    std::fs::remove_file(traced).unwrap();
+}
+
+#[test]
+fn test_validate() {
+    let good = temp_path("good.json");
+    let bad = temp_path("bad.json");
+    run(&["new", "triangle", "3", "-o", good.to_str().unwrap()]).0.unwrap();
+    std::fs::write(&bad, "{\"format\": \"something else\"}").unwrap();
+
+    let (result, out) = run(&["validate", good.to_str().unwrap()]);
+    result.unwrap();
+    assert!(out.ends_with(": ok\n"), "{out}");
+
+    let (result, out) = run(&["validate", good.to_str().unwrap(), bad.to_str().unwrap()]);
+    assert!(matches!(result, Err(CliError::Invalid(1))));
+    assert_eq!(2, out.lines().count(), "{out}");
+    std::fs::remove_file(good).unwrap();
+    std::fs::remove_file(bad).unwrap();
+}
+
+#[test]
+fn test_generate() {
+    let (first, second) = (temp_path("generated-1.json"), temp_path("generated-2.json"));
+    for (path, seed) in [(&first, "42"), (&second, "42")] {
+        run(&["generate", "hexagon", "8", "-o", path.to_str().unwrap(), "--seed", seed, "--scale", "6"]).0.unwrap();
+    }
+    assert_eq!(std::fs::read(&first).unwrap(), std::fs::read(&second).unwrap());
+    let grid = Grid::load(&first).unwrap();
+    assert_eq!(217, grid.cells().count());
+    assert!(grid.cells().any(|hex| grid.cell(hex) != grid.cell((0, 0))), "A single terrain was generated");
+
+    run(&["generate", "hexagon", "8", "-o", second.to_str().unwrap(), "--seed", "43", "--scale", "6"]).0.unwrap();
+    assert_ne!(std::fs::read(&first).unwrap(), std::fs::read(&second).unwrap());
+    std::fs::remove_file(first).unwrap();
+    std::fs::remove_file(second).unwrap();
+}
+
+#[test]
+fn test_synthesize() {
+    let (map, rules, output) = (temp_path("example.json"), temp_path("rules.txt"), temp_path("synthesized.json"));
+    let (map_path, output_path) = (map.to_str().unwrap(), output.to_str().unwrap());
+    run(&["new", "triangle", "1", "-o", map_path, "--terrain", "sand"]).0.unwrap();
+    std::fs::write(&rules, "Sand, any, Water\nWater, any, Water\n").unwrap();
+
+    let (result, _) = run(&["synthesize", map_path, "hexagon", "2", "-o", output_path, "--rules", rules.to_str().unwrap()]);
+    result.unwrap();
+    let grid = Grid::load(&output).unwrap();
+    assert_eq!(19, grid.cells().count());
+    let sand = grid.cells().filter(|hex| grid.cell(*hex) == grid.cell((0, 0))).count();
+    assert!((3..19).contains(&sand), "{sand}");
+
+    //Learned from the map itself, sand only goes next to sand
+    run(&["synthesize", map_path, "hexagon", "2", "-o", output_path, "--seed", "5"]).0.unwrap();
+    let grid = Grid::load(&output).unwrap();
+    assert!(grid.cells().all(|hex| grid.cell(hex) == grid.cell((0, 0))));
+
+    std::fs::write(&rules, "Sand, any, Grass\n").unwrap();
+    let (result, _) = run(&["synthesize", map_path, "hexagon", "2", "-o", output_path, "--rules", rules.to_str().unwrap()]);
+    assert!(matches!(result, Err(CliError::Synthesis { .. })), "{result:?}");
+    std::fs::write(&rules, "Sand, any, Lava\n").unwrap();
+    let (result, _) = run(&["synthesize", map_path, "hexagon", "2", "-o", output_path, "--rules", rules.to_str().unwrap()]);
+    assert!(matches!(result, Err(CliError::Rules { .. })), "{result:?}");
+    for path in [map, rules, output] {
+        std::fs::remove_file(path).unwrap();
+    }
+}
+
+#[test]
+fn test_usage_errors() {
+    for args in [
+        &[][..],
+        &["frobnicate"],
+        &["new", "hexagon", "2"],
+        &["new", "hexagon", "-o"],
+        &["new", "square", "2", "-o", "out.json"],
+        &["new", "hexagon", "x", "-o", "out.json"],
+        &["new", "hexagon", "2", "-o", "out.json", "--terrain", "lava"],
+        &["stats", "a.json", "--verbose"],
+        &["render", "a.json", "b.png", "--cell-size", "0"],
+        &["render", "a.json", "b.png", "--background", "white"],
+        &["render", "a.json", "b.svg", "--labels", "polar"],
+        &["import", "a.png", "b.json", "--sampling", "median"],
+        &["import", "a.png", "b.json", "--cells", "-1"],
+        &["generate", "hexagon", "4", "-o", "out.json", "--octaves", "0"],
+        &["generate", "hexagon", "4", "-o", "out.json", "--seed", "-3"],
+        &["synthesize", "-o", "out.json"],
+        &["synthesize", "a.json", "hexagon", "-o", "out.json"],
+    ] {
+        assert!(matches!(run(args).0, Err(CliError::Usage(_))), "{args:?}");
+    }
+}
@@ -2,18 +2,33 @@
 
 //#[allow(unused)]
 mod app;
+mod cli;
+
+use std::process::ExitCode;
 
 use app::Editor;
 
-fn main() -> Result<(), eframe::Error> {
+fn main() -> ExitCode {
+    //Any argument runs a command line tool instead of the editor
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if !args.is_empty() {
+        return cli::run(&args);
+    }
     let options = eframe::NativeOptions {
         ..Default::default()
     };
-    eframe::run_native(
+    let result = eframe::run_native(
         "HexEditor",
         options,
         Box::new(|cc| {
-            Ok(Box::new(Editor::new(&cc)))
+            Ok(Box::new(Editor::new(cc)))
         })
-    )
+    );
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("hex-editor: {error}");
+            ExitCode::FAILURE
+        }
+    }
 }
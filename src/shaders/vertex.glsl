@@ -1,15 +1,11 @@
 
-const vec4 colors[3] = vec4[3](
-    vec4(1.0, 0.0, 0.0, 1.0),
-    vec4(0.0, 1.0, 0.0, 1.0),
-    vec4(0.0, 0.0, 1.0, 1.0)
-);
-
 in vec2 v_position;
+in vec4 v_fill;
 out vec4 v_color;
-uniform float u_angle;
+uniform mat3 u_view_projection;
 
 void main() {
-    v_color = colors[gl_VertexID % 3];
-    gl_Position = vec4( v_position.x, -v_position.y, 0.5, 1.0 );
-}
+    v_color = v_fill;
+    vec3 position = u_view_projection * vec3(v_position, 1.0);
+    gl_Position = vec4( position.xy, 0.5, 1.0 );
+}
\ No newline at end of file